@@ -0,0 +1,333 @@
+//! Who may take a seat in a room.
+//!
+//! A room can ask for a password, or for an invite code the server generates at startup.
+//! A new player sends it on a second line after their name. The host (a spectator who
+//! joined with the host key) can kick a player out, or ban a name for as long as the
+//! server runs. Names can't have spaces nor any of the characters the game notation and
+//! the markers of the protocol are made of.
+
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use tokio::sync::{watch, Mutex, Notify};
+
+pub const KICK_COMMAND: &str = "/kick";
+pub const BAN_COMMAND: &str = "/ban";
+const INVITE_CODE_LEN: usize = 6;
+// no 0/O or 1/I, the code is read aloud or copied by hand
+const INVITE_CODE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+// what the notation and the markers around names are made of
+const NAME_FORBIDDEN_CHARS: &str = ":+>#=%\"~*@|$&^";
+
+/// What a room asks of new players.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Secret {
+    PASSWORD(String),
+    INVITE(String),
+}
+
+/// Why a player was refused a seat.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AccessError {
+    /// Wrong or missing password
+    PASSWORD,
+    /// Wrong or missing invite code
+    INVITE,
+    /// The name was banned by the host
+    BANNED,
+    /// The host sent the player away from the game under way
+    KICKED,
+    /// The name has spaces or characters the game uses around names
+    NAME,
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AccessError::PASSWORD => write!(f, "Wrong or missing room password."),
+            AccessError::INVITE => write!(f, "Wrong or missing invite code."),
+            AccessError::BANNED => write!(f, "You are banned from this room."),
+            AccessError::KICKED => write!(f, "You were sent away from this game."),
+            AccessError::NAME => write!(
+                f,
+                "Names can't have spaces nor any of {}",
+                NAME_FORBIDDEN_CHARS
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AccessError {}
+
+/// What the host can do to a player.
+#[derive(Debug, PartialEq, Clone)]
+pub enum HostCommand {
+    /// Out of the game, a new join is fine
+    KICK(String),
+    /// Out of the game, and the name is refused from now on
+    BAN(String),
+}
+
+///
+///# The secret of a room and the names the host sent away
+///
+#[derive(Debug, Default)]
+pub struct Access {
+    secret: Option<Secret>,
+    banned: Mutex<HashSet<String>>,
+    kicked: Mutex<HashSet<String>>,
+    /// Set to close the connection of a kicked player
+    doors: Mutex<HashMap<String, watch::Sender<bool>>>,
+    /// Rung on every kick or ban
+    pub changed: Notify,
+}
+
+impl Access {
+    pub fn new(secret: Option<Secret>) -> Access {
+        Access {
+            secret,
+            ..Access::default()
+        }
+    }
+
+    pub fn secret(&self) -> Option<&Secret> {
+        self.secret.as_ref()
+    }
+
+    /// Checks a new player, who gave `presented` after their name.
+    pub async fn admit(&self, name: &str, presented: Option<&str>) -> Result<(), AccessError> {
+        if !valid_name(name) {
+            return Err(AccessError::NAME);
+        }
+        if self.banned.lock().await.contains(name) {
+            return Err(AccessError::BANNED);
+        }
+
+        let presented = presented.map(str::trim);
+        match &self.secret {
+            Some(Secret::PASSWORD(password)) if presented != Some(password.as_str()) => {
+                return Err(AccessError::PASSWORD);
+            }
+            Some(Secret::INVITE(code))
+                if !presented.is_some_and(|p| p.eq_ignore_ascii_case(code)) =>
+            {
+                return Err(AccessError::INVITE);
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// A kicked player who joined again took a seat before the game started: the kick only
+    /// sent away the connection that was there.
+    pub async fn seated(&self, name: &str) {
+        self.kicked.lock().await.remove(name);
+        if let Some(door) = self.doors.lock().await.get(name) {
+            door.send_replace(false);
+        }
+    }
+
+    /// What the connection of `name` watches, to hang up once they're kicked.
+    pub async fn door(&self, name: &str) -> watch::Receiver<bool> {
+        let mut doors = self.doors.lock().await;
+
+        doors
+            .entry(name.to_string())
+            .or_insert_with(|| watch::Sender::new(false))
+            .subscribe()
+    }
+
+    pub async fn kick(&self, name: &str) {
+        self.kicked.lock().await.insert(name.to_string());
+
+        if let Some(door) = self.doors.lock().await.get(name) {
+            door.send_replace(true);
+        }
+        self.changed.notify_waiters();
+    }
+
+    pub async fn ban(&self, name: &str) {
+        self.banned.lock().await.insert(name.to_string());
+        self.kick(name).await;
+    }
+
+    pub async fn is_kicked(&self, name: &str) -> bool {
+        self.kicked.lock().await.contains(name)
+    }
+}
+
+impl Secret {
+    /// A password from the command line, or a freshly generated invite code.
+    pub fn from_args(password: Option<String>, invite: bool) -> Option<Secret> {
+        match (password, invite) {
+            (Some(password), _) => Some(Secret::PASSWORD(password)),
+            (None, true) => Some(Secret::INVITE(invite_code())),
+            (None, false) => None,
+        }
+    }
+}
+
+pub fn invite_code() -> String {
+    let mut rng = rand::rng();
+
+    (0..INVITE_CODE_LEN)
+        .map(|_| INVITE_CODE_CHARS[rng.random_range(0..INVITE_CODE_CHARS.len())] as char)
+        .collect()
+}
+
+/// The message a new player sends: their name, then the secret of the room if any.
+pub fn join_hello(name: &str, secret: Option<&str>) -> String {
+    match secret {
+        Some(secret) => format!("{}\n{}", name, secret),
+        None => name.to_string(),
+    }
+}
+
+/// The name and the secret of a `join_hello`.
+pub fn parse_join(msg: &str) -> (&str, Option<&str>) {
+    match msg.trim().split_once('\n') {
+        Some((name, secret)) => (name.trim(), Some(secret.trim())),
+        None => (msg.trim(), None),
+    }
+}
+
+/// Whether `name` can be a player name, written as it is in notation and markers.
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || NAME_FORBIDDEN_CHARS.contains(c))
+}
+
+/// `/kick <name>` or `/ban <name>`, None for anything else.
+pub fn parse_host_command(msg: &str) -> Option<HostCommand> {
+    let (command, name) = msg.trim().split_once(char::is_whitespace)?;
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return None;
+    }
+
+    match command {
+        KICK_COMMAND => Some(HostCommand::KICK(name)),
+        BAN_COMMAND => Some(HostCommand::BAN(name)),
+        _ => None,
+    }
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::GameRecord;
+    use crate::{notation, notes, opponents, Game, Player};
+
+    #[tokio::test]
+    async fn test_admit_with_password() {
+        let access = Access::new(Some(Secret::PASSWORD(String::from("hunter2"))));
+
+        assert_eq!(access.admit("me", Some("hunter2")).await, Ok(()));
+        assert_eq!(
+            access.admit("me", Some("nope")).await,
+            Err(AccessError::PASSWORD)
+        );
+        assert_eq!(access.admit("me", None).await, Err(AccessError::PASSWORD));
+    }
+
+    #[tokio::test]
+    async fn test_admit_with_invite_code() {
+        let code = invite_code();
+        assert_eq!(code.len(), INVITE_CODE_LEN);
+
+        let access = Access::new(Some(Secret::INVITE(code.clone())));
+        assert_eq!(access.admit("me", Some(&code.to_lowercase())).await, Ok(()));
+        assert_eq!(access.admit("me", Some("")).await, Err(AccessError::INVITE));
+    }
+
+    #[tokio::test]
+    async fn test_kick_and_ban() {
+        let access = Access::default();
+        let door = access.door("bob").await;
+
+        access.kick("bob").await;
+        assert!(access.is_kicked("bob").await);
+        assert!(*door.borrow());
+
+        // kicked players may join again, and are let back once seated
+        assert_eq!(access.admit("bob", None).await, Ok(()));
+        assert!(access.is_kicked("bob").await);
+        access.seated("bob").await;
+        assert!(!access.is_kicked("bob").await);
+        assert!(!*door.borrow());
+
+        // banned ones can't
+
+        access.ban("bob").await;
+        assert_eq!(access.admit("bob", None).await, Err(AccessError::BANNED));
+        assert!(access.is_kicked("bob").await);
+    }
+
+    #[tokio::test]
+    async fn test_names() {
+        let access = Access::default();
+        assert_eq!(access.admit("Ann Lee", None).await, Err(AccessError::NAME));
+        assert_eq!(access.admit("a:b", None).await, Err(AccessError::NAME));
+        assert_eq!(access.admit("50%", None).await, Err(AccessError::NAME));
+
+        // whatever is let in is read back as it was written
+        let names = ["o'brien", "zoë", "ann_lee", "a-b.c"];
+        assert!(names.iter().all(|name| valid_name(name)));
+        let mut game = Game::new(24);
+        for name in names {
+            game.players.push(Player::new(String::from(name), 4));
+        }
+        game.init_set();
+        game.shuffle_avail_card();
+        game.draw("o'brien", 0);
+        game.guess("o'brien", "zoë", 0, 99);
+        game.save_side_card("o'brien", true);
+        game.forfeit("a-b.c");
+        let record = GameRecord::of(&game, 1_792_332_300);
+        assert_eq!(
+            notation::parse(&notation::write_private(&record)),
+            Ok(record)
+        );
+
+        let (opponents, _) =
+            opponents::split_opponents(&opponents::opponent_markers(&game, "zoë", "o'brien"));
+        let told: Vec<&str> = opponents.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(told, ["o'brien", "ann_lee", "a-b.c"]);
+        let (inserted, _) = notes::split_inserted(&notes::inserted_markers(&game));
+        assert_eq!(
+            inserted[0],
+            (String::from("o'brien"), game.players[0].inserted.clone())
+        );
+        assert_eq!(inserted.len(), 4);
+    }
+
+    #[test]
+    fn test_parse_join() {
+        assert_eq!(parse_join("alice"), ("alice", None));
+        assert_eq!(
+            parse_join(&join_hello("alice", Some("K3Y"))),
+            ("alice", Some("K3Y"))
+        );
+        assert_eq!(parse_join(" alice \n K3Y \n"), ("alice", Some("K3Y")));
+    }
+
+    #[test]
+    fn test_parse_host_command() {
+        assert_eq!(
+            parse_host_command("/kick bob"),
+            Some(HostCommand::KICK(String::from("bob")))
+        );
+        assert_eq!(
+            parse_host_command(" /ban  bob \n"),
+            Some(HostCommand::BAN(String::from("bob")))
+        );
+        assert_eq!(parse_host_command("/kick"), None);
+        assert_eq!(parse_host_command("/say hi"), None);
+    }
+}
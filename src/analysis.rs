@@ -0,0 +1,268 @@
+//! Looks back at a finished game, decision by decision.
+//!
+//! Each guess is weighed with what its player could see when they made it: the values the
+//! card could still have (see `bot::candidates`), all taken as equally likely. The report
+//! gives the chances of the guess made, the likeliest guess there was, and whether guessing
+//! on was worth more than stopping: a right guess reveals an opponent card, a wrong one the
+//! card the player drew, and stopping keeps it hidden.
+
+use crate::archive::GameRecord;
+use crate::bot;
+use crate::history::GameEvent;
+use crate::replay::Replay;
+use crate::Game;
+use serde::Serialize;
+use std::fmt;
+
+/// A guess and its chances.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Pick {
+    pub opponent: String,
+    pub card_idx: usize,
+    pub value: u32,
+    pub probability: f64,
+}
+
+/// A guess, or a stop after a right guess.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Decision {
+    pub turn: usize,
+    /// The move, from 1
+    pub step: usize,
+    pub player: String,
+    /// The guess made, none when the player stopped
+    pub guess: Option<Pick>,
+    /// The likeliest guess there was
+    pub best: Option<Pick>,
+    /// Cards won on average by making the best guess, stopping being worth nothing
+    pub continue_value: f64,
+    /// The player had guessed right and could stop instead
+    pub could_stop: bool,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Report {
+    pub seats: Vec<String>,
+    pub winner: Option<String>,
+    pub decisions: Vec<Decision>,
+}
+
+impl Report {
+    pub fn of(record: &GameRecord) -> Result<Report, String> {
+        let replay = Replay::new(record.clone())?;
+        let mut decisions = Vec::new();
+
+        for step in 1..=replay.moves() {
+            let event = match replay.event(step) {
+                Some(event) => event,
+                None => continue,
+            };
+            let guessed_right = matches!(
+                replay.event(step - 1),
+                Some(GameEvent::GUESS { player, right: true, .. }) if player == event.player()
+            );
+
+            // the position the player had in front of them
+            let mut game = Game::with_seed(record.set_cards, [0; 32]);
+            game.players = replay.players(step - 1).to_vec();
+            let me = event.player();
+
+            let guess = match event {
+                GameEvent::GUESS {
+                    opponent,
+                    card_idx,
+                    value,
+                    ..
+                } => Some(pick(&game, me, opponent, *card_idx, *value)),
+                GameEvent::SAVE { hidden: true, .. } if guessed_right => None,
+                _ => continue,
+            };
+            let best = bot::best_guess(&game, me)
+                .map(|best| pick(&game, me, &best.opponent, best.card_idx, best.candidates[0]));
+
+            // a wrong guess costs the drawn card, if there is one to reveal
+            let at_stake = game
+                .players
+                .iter()
+                .any(|player| player.name == me && player.side_card.is_some());
+            let continue_value = match &best {
+                Some(best) if at_stake => 2.0 * best.probability - 1.0,
+                Some(best) => best.probability,
+                None => 0.0,
+            };
+
+            decisions.push(Decision {
+                turn: replay.turn(step),
+                step,
+                player: me.to_string(),
+                guess,
+                best,
+                continue_value,
+                could_stop: guessed_right,
+            });
+        }
+
+        Ok(Report {
+            seats: record.seats.clone(),
+            winner: record.winner.clone(),
+            decisions,
+        })
+    }
+}
+
+impl fmt::Display for Pick {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}'s card {} is {} ({:.0}%)",
+            self.opponent,
+            self.card_idx,
+            self.value,
+            self.probability * 100.0
+        )
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}. {} ", self.turn, self.player)?;
+        match &self.guess {
+            Some(guess) => write!(f, "guessed {}", guess)?,
+            None => write!(f, "stopped")?,
+        }
+        match &self.best {
+            Some(best) if Some(best) == self.guess.as_ref() => write!(f, ", the best guess")?,
+            Some(best) => write!(f, ", best was {}", best)?,
+            None => {}
+        }
+
+        if self.could_stop {
+            let better = match self.continue_value > 0.0 {
+                true => "guessing on",
+                false => "stopping",
+            };
+            let right = match (self.guess.is_some(), self.continue_value > 0.0) {
+                (true, true) | (false, false) => "right",
+                _ => "wrong",
+            };
+            write!(
+                f,
+                "; guessing on was worth {:+.2} cards, {} was better: {} call",
+                self.continue_value, better, right
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Analysis, with what each player could see:")?;
+        for decision in self.decisions.iter() {
+            writeln!(f, "{}", decision)?;
+        }
+
+        for seat in self.seats.iter() {
+            let chances: Vec<f64> = self
+                .decisions
+                .iter()
+                .filter(|decision| decision.player == *seat)
+                .filter_map(|decision| decision.guess.as_ref())
+                .map(|guess| guess.probability)
+                .collect();
+            if chances.is_empty() {
+                continue;
+            }
+            writeln!(
+                f,
+                "{}: {} guesses, {:.0}% likely on average",
+                seat,
+                chances.len(),
+                chances.iter().sum::<f64>() * 100.0 / chances.len() as f64
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The chances of `me` guessing the card `card_idx` of `opponent` is `value`.
+fn pick(game: &Game, me: &str, opponent: &str, card_idx: usize, value: u32) -> Pick {
+    let candidates = game
+        .players
+        .iter()
+        .find(|player| player.name == opponent)
+        .filter(|player| card_idx < player.deck.len())
+        .map_or(Vec::new(), |player| {
+            bot::candidates(game, me, player, card_idx)
+        });
+
+    Pick {
+        opponent: opponent.to_string(),
+        card_idx,
+        value,
+        probability: match candidates.contains(&value) {
+            true => 1.0 / candidates.len() as f64,
+            false => 0.0,
+        },
+    }
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation;
+
+    const RECORD: &str = "[Set \"24\"]
+
+alice: W0 B3 B10 W10
+bob: B7 B8 W9 B11
+
+1. alice+W3 alice>bob#3=11 ! alice>bob#0=4 x alice:show
+2. bob+B1 bob>alice#4=10 ! bob:hide
+";
+
+    fn report() -> Report {
+        Report::of(&notation::parse(RECORD).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_decisions() {
+        let report = report();
+
+        // two guesses of alice, one of bob, then bob stopping
+        assert_eq!(report.decisions.len(), 4);
+        let first = &report.decisions[0];
+        assert_eq!(first.step, 2);
+        assert!(!first.could_stop);
+        // alice holds B3 and B10, bob's B11 could be any other black card
+        assert_eq!(first.guess.as_ref().unwrap().probability, 1.0 / 10.0);
+
+        let second = &report.decisions[1];
+        assert!(second.could_stop);
+        // below B11 and no B3
+        let guess = second.guess.as_ref().unwrap();
+        assert_eq!(guess.probability, 1.0 / 9.0);
+        assert!(second.best.as_ref().unwrap().probability >= guess.probability);
+
+        let stop = &report.decisions[3];
+        assert_eq!(stop.player, "bob");
+        assert!(stop.guess.is_none());
+        assert!(stop.could_stop);
+        assert!(stop.continue_value < 0.0);
+    }
+
+    #[test]
+    fn test_text() {
+        let text = report().to_string();
+
+        assert!(text.contains("1. alice guessed bob's card 3 is 11 (10%)"));
+        assert!(text.contains("2. bob stopped, best was "));
+        assert!(text.contains("stopping was better: right call\n"));
+        assert!(text.contains("alice: 2 guesses, "));
+    }
+}
@@ -0,0 +1,303 @@
+//! The games a server finished, kept in a file so they outlive the process.
+//!
+//! Each finished game is appended to the archive as one line of JSON: the rules, the seed of
+//! the shuffles, the seats, every move and the winner. Games are numbered from 1, in the
+//! order they were played.
+
+use crate::history::GameEvent;
+use crate::notation;
+use crate::Game;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_FILE: &str = "games.jsonl";
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct GameRecord {
+    /// Unix time, in seconds
+    pub started: u64,
+    pub finished: u64,
+    pub set_cards: u32,
+    pub cards_per_player: u32,
+    /// In hex, see `fairness`
+    pub seed: String,
+    /// In the order the cards were dealt
+    pub seats: Vec<String>,
+    pub events: Vec<GameEvent>,
+    pub winner: Option<String>,
+}
+
+impl GameRecord {
+    /// The record of `game`, finished now.
+    pub fn of(game: &Game, started: u64) -> GameRecord {
+        let cards_per_player = game
+            .history
+            .iter()
+            .find_map(|event| match event {
+                GameEvent::DEAL { cards, .. } => Some(cards.len() as u32),
+                _ => None,
+            })
+            .unwrap_or(0);
+
+        GameRecord {
+            started,
+            finished: now(),
+            set_cards: game.set_cards,
+            cards_per_player,
+            seed: game
+                .seed
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+            seats: game.seats.clone(),
+            events: game.history.clone(),
+            winner: game.winner.as_ref().map(|winner| winner.name.clone()),
+        }
+    }
+
+    /// One line about the game, numbered `id`.
+    pub fn summary(&self, id: usize) -> String {
+        format!(
+            "{:>4}  {}  {:<30}  {}",
+            id,
+            date(self.started),
+            self.seats.join(", "),
+            match &self.winner {
+                Some(winner) => format!("won by {}", winner),
+                None => String::from("no winner"),
+            }
+        )
+    }
+
+    /// The whole game, move by move.
+    pub fn describe(&self) -> String {
+        let mut text = format!(
+            "Played {} to {}\nRules: {} cards, {} per player\nSeed: {}\nSeats: {}\n\n",
+            date(self.started),
+            date(self.finished),
+            self.set_cards,
+            self.cards_per_player,
+            self.seed,
+            self.seats.join(", ")
+        );
+
+        for event in self.events.iter() {
+            text.push_str(&format!("{}\n", event));
+        }
+        if let Some(winner) = &self.winner {
+            text.push_str(&format!("{} won the game.\n", winner));
+        }
+
+        text
+    }
+}
+
+/// The subcommands of the servers.
+#[derive(Debug, clap::Subcommand)]
+pub enum ServerCommand {
+    /// Lists, shows or exports the games of the archive, and exits
+    ARCHIVE {
+        #[command(subcommand)]
+        command: ArchiveCommand,
+    },
+}
+
+/// What to do with the archive, from the command line.
+#[derive(Debug, clap::Subcommand)]
+pub enum ArchiveCommand {
+    /// List the games, oldest first
+    LIST,
+    /// Show every move of a game
+    SHOW { id: usize },
+    /// Write a game out
+    EXPORT {
+        id: usize,
+        /// File to write to, standard output if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        #[arg(short, long, value_enum, default_value_t = ExportFormat::JSON)]
+        format: ExportFormat,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, clap::ValueEnum)]
+pub enum ExportFormat {
+    JSON,
+    /// The game record notation, see `notation`, without the cards nobody saw
+    DVC,
+    /// The same with every card, to play the game again or analyze it
+    #[value(name = "dvc-private")]
+    PRIVATE,
+}
+
+/// Unix time, in seconds.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+/// Adds a finished game at the end of the archive, creating it if needed.
+pub fn append(path: &Path, record: &GameRecord) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+
+    writeln!(file, "{}", serde_json::to_string(record)?)
+}
+
+/// Every game of the archive, none if there is no archive yet.
+pub fn load(path: &Path) -> io::Result<Vec<GameRecord>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| {
+            serde_json::from_str(line).map_err(|error| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", idx + 1, error),
+                )
+            })
+        })
+        .collect()
+}
+
+/// One game from a file, in notation or in JSON as `export` writes it.
+pub fn read(path: &Path) -> io::Result<GameRecord> {
+    let text = fs::read_to_string(path)?;
+    let invalid = |error: String| io::Error::new(io::ErrorKind::InvalidData, error);
+
+    if text.trim_start().starts_with('{') {
+        serde_json::from_str(&text).map_err(|error| invalid(error.to_string()))
+    } else {
+        notation::parse(&text).map_err(|error| invalid(error.to_string()))
+    }
+}
+
+/// Runs `command` on the archive at `path`.
+pub fn run(path: &Path, command: &ArchiveCommand) -> io::Result<()> {
+    let games = load(path)?;
+    let game = |id: usize| {
+        games.get(id.wrapping_sub(1)).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no game {} in {}", id, path.display()),
+            )
+        })
+    };
+
+    match command {
+        ArchiveCommand::LIST => {
+            for (idx, record) in games.iter().enumerate() {
+                println!("{}", record.summary(idx + 1));
+            }
+        }
+        ArchiveCommand::SHOW { id } => print!("{}", game(*id)?.describe()),
+        ArchiveCommand::EXPORT { id, output, format } => {
+            let text = match format {
+                ExportFormat::JSON => serde_json::to_string_pretty(game(*id)?)? + "\n",
+                ExportFormat::DVC => notation::write(game(*id)?),
+                ExportFormat::PRIVATE => notation::write_private(game(*id)?),
+            };
+            match output {
+                Some(output) => fs::write(output, text)?,
+                None => print!("{}", text),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// "2026-10-18 14:05", in UTC.
+fn date(secs: u64) -> String {
+    // days to a civil date, from Howard Hinnant's algorithm
+    let days = (secs / 86400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        secs % 86400 / 3600,
+        secs % 3600 / 60
+    )
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::dealt;
+
+    fn finished_game() -> Game {
+        let mut game = dealt();
+        game.shuffle_avail_card();
+        game.draw("alice", 0);
+        game.guess("alice", "bob", 0, 99);
+        game.save_side_card("alice", false);
+        game.forfeit("bob");
+        game.game_status();
+        game
+    }
+
+    #[test]
+    fn test_date() {
+        assert_eq!(date(0), "1970-01-01 00:00");
+        assert_eq!(date(1_792_332_300), "2026-10-18 14:05");
+    }
+
+    #[test]
+    fn test_record_of_a_game() {
+        let record = GameRecord::of(&finished_game(), now());
+
+        assert_eq!(record.seats, vec!["alice", "bob"]);
+        assert_eq!(record.cards_per_player, 4);
+        assert_eq!(record.seed.len(), 64);
+        assert_eq!(record.winner.as_deref(), Some("alice"));
+        // the deal, then the moves
+        assert_eq!(record.events.len(), 6);
+        assert!(record.describe().contains(
+            "alice guessed bob's card 0 is 99: wrong\nalice revealed their drawn card\n"
+        ));
+    }
+
+    #[test]
+    fn test_append_and_load() {
+        let path =
+            std::env::temp_dir().join(format!("davincicode-archive-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        assert!(load(&path).unwrap().is_empty());
+
+        let record = GameRecord::of(&finished_game(), now());
+        append(&path, &record).unwrap();
+        append(&path, &record).unwrap();
+
+        assert_eq!(load(&path).unwrap(), vec![record.clone(), record]);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,353 @@
+//! A simple player that takes over the seat of someone who went idle, or plays against
+//! people practicing offline.
+//!
+//! It draws a random card and guesses the opponent card it knows the most about. Then it
+//! keeps guessing while it is sure of a value, risks one more guess when few values are
+//! left, and keeps its card hidden otherwise. Easier bots guess any card, harder ones
+//! only take risks they can't avoid.
+
+use crate::{card_cmp, Card, CardStatus, Color, Game, Player};
+use rand::seq::IndexedRandom;
+use rand::Rng;
+use std::cmp::Ordering;
+
+/// Above this many possible values, a guess is too much of a gamble for the bot.
+const RISKY_CANDIDATES: usize = 3;
+
+/// How well a bot plays.
+#[derive(Debug, PartialEq, Clone, Copy, clap::ValueEnum)]
+pub enum Difficulty {
+    /// One guess per turn, on any hidden card
+    EASY,
+    /// The bot that plays for idle players
+    NORMAL,
+    /// Only guesses it is sure of, unless it can't draw
+    HARD,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Guess {
+    pub opponent: String,
+    pub card_idx: usize,
+    pub candidates: Vec<u32>,
+}
+
+/// Values the hidden card `card_idx` of `opponent` can still have, as far as `me` can tell
+/// from what they see (their own cards and every revealed card), from the deck order and
+/// from the wrong guesses made on it.
+pub fn candidates(game: &Game, me: &str, opponent: &Player, card_idx: usize) -> Vec<u32> {
+    let color = opponent.deck[card_idx].color;
+    let seen = seen_values(game, me, color);
+
+    (0..game.set_cards / 2)
+        .filter(|value| !seen.contains(value))
+        .filter(|value| !opponent.ruled_out(card_idx).contains(value))
+        .filter(|value| fits_in_deck(opponent, card_idx, &Card::new(*value, color)))
+        .collect()
+}
+
+/// The hidden opponent card with the fewest possible values, if any is left.
+pub fn best_guess(game: &Game, me: &str) -> Option<Guess> {
+    let mut best: Option<Guess> = None;
+
+    for opponent in game.players.iter().filter(|player| player.name != me) {
+        for (card_idx, card) in opponent.deck.iter().enumerate() {
+            if card.status == CardStatus::REVEALED {
+                continue;
+            }
+
+            let values = candidates(game, me, opponent, card_idx);
+            if values.is_empty() {
+                continue;
+            }
+
+            let better = match &best {
+                Some(guess) => values.len() < guess.candidates.len(),
+                None => true,
+            };
+            if better {
+                best = Some(Guess {
+                    opponent: opponent.name.clone(),
+                    card_idx,
+                    candidates: values,
+                });
+            }
+        }
+    }
+
+    best
+}
+
+/// Any hidden opponent card, if any is left.
+pub fn random_guess(game: &Game, me: &str) -> Option<Guess> {
+    let mut guesses = Vec::new();
+
+    for opponent in game.players.iter().filter(|player| player.name != me) {
+        for (card_idx, card) in opponent.deck.iter().enumerate() {
+            if card.status == CardStatus::HIDDEN {
+                guesses.push(Guess {
+                    opponent: opponent.name.clone(),
+                    card_idx,
+                    candidates: candidates(game, me, opponent, card_idx),
+                });
+            }
+        }
+    }
+
+    guesses
+        .into_iter()
+        .filter(|guess| !guess.candidates.is_empty())
+        .collect::<Vec<_>>()
+        .choose(&mut rand::rng())
+        .cloned()
+}
+
+/// Draws a random card (if any is left) and keeps it hidden. Returns what the others see.
+pub fn safe_move(game: &mut Game, me: &str) -> String {
+    let drawn = draw(game, me);
+    game.save_side_card(me, true);
+
+    if drawn {
+        format!("{} drew a card and kept it hidden.\n", me)
+    } else {
+        format!("{} passed.\n", me)
+    }
+}
+
+/// Plays a whole turn for `me`. Returns what the others see.
+pub fn play_turn(game: &mut Game, me: &str) -> String {
+    play_turn_as(game, me, Difficulty::NORMAL)
+}
+
+/// Plays a whole turn for `me`, as well as `difficulty` allows. Returns what the others see.
+pub fn play_turn_as(game: &mut Game, me: &str, difficulty: Difficulty) -> String {
+    let mut summary = String::new();
+    let mut risked = false;
+    let mut guessed = false;
+
+    let drawn = draw(game, me);
+    if drawn {
+        summary.push_str(format!("{} drew a card.\n", me).as_str());
+    }
+
+    loop {
+        let guess = match difficulty {
+            Difficulty::EASY => random_guess(game, me),
+            Difficulty::NORMAL | Difficulty::HARD => best_guess(game, me),
+        };
+        let guess = match guess {
+            Some(guess) => guess,
+            None => break,
+        };
+
+        let sure = guess.candidates.len() == 1;
+        let stop = match difficulty {
+            Difficulty::EASY => guessed,
+            // a turn needs at least one guess, the following ones have to be safer
+            Difficulty::NORMAL => {
+                guessed && !sure && (risked || guess.candidates.len() > RISKY_CANDIDATES)
+            }
+            // a drawn card can be kept hidden instead of a long shot
+            Difficulty::HARD => {
+                !sure && (guessed || (drawn && guess.candidates.len() > RISKY_CANDIDATES))
+            }
+        };
+        if stop {
+            break;
+        }
+        risked |= !sure;
+        guessed = true;
+
+        let value = *guess
+            .candidates
+            .choose(&mut rand::rng())
+            .expect("No candidate");
+
+        let right = game.guess(me, &guess.opponent, guess.card_idx, value) == Some(true);
+
+        summary.push_str(
+            format!(
+                "{} guessed {}'s card {} is {}: {}\n",
+                me,
+                guess.opponent,
+                guess.card_idx,
+                value,
+                if right { "right" } else { "wrong" }
+            )
+            .as_str(),
+        );
+
+        if !right {
+            game.save_side_card(me, false);
+            summary.push_str(format!("{} revealed their drawn card.\n", me).as_str());
+
+            return summary;
+        }
+
+        // nobody left to guess, the game is over
+        if game.game_status() {
+            return summary;
+        }
+    }
+
+    if game
+        .players
+        .iter()
+        .any(|player| player.name == me && player.side_card.is_some())
+    {
+        summary.push_str(format!("{} kept their card hidden.\n", me).as_str());
+    }
+    game.save_side_card(me, true);
+
+    summary
+}
+
+fn draw(game: &mut Game, me: &str) -> bool {
+    if game.card_avail.is_empty() {
+        return false;
+    }
+
+    let pick = rand::rng().random_range(0..game.card_avail.len());
+    match game.players.iter().find(|player| player.name == me) {
+        // resuming a turn: the card was already drawn
+        Some(player) if player.side_card.is_some() => true,
+        Some(_) => game.bot_draw(me, pick).is_some(),
+        None => false,
+    }
+}
+
+fn seen_values(game: &Game, me: &str, color: Color) -> Vec<u32> {
+    let mut seen = Vec::new();
+
+    for player in game.players.iter().chain(game.lost_players.iter()) {
+        let own = player.name == me;
+
+        for card in player.deck.iter().chain(player.side_card.iter()) {
+            if card.color == color && (own || card.status == CardStatus::REVEALED) {
+                seen.push(card.value);
+            }
+        }
+    }
+
+    seen
+}
+
+fn fits_in_deck(opponent: &Player, card_idx: usize, card: &Card) -> bool {
+    opponent
+        .deck
+        .iter()
+        .enumerate()
+        .filter(|(_, other)| other.status == CardStatus::REVEALED)
+        .all(|(idx, other)| match idx.cmp(&card_idx) {
+            Ordering::Less => card_cmp(other, card) == Ordering::Less,
+            Ordering::Greater => card_cmp(other, card) == Ordering::Greater,
+            Ordering::Equal => true,
+        })
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::card;
+
+    fn two_players() -> Game {
+        let mut game = Game::new(24);
+
+        let mut me = Player::new(String::from("me"), 2);
+        me.deck = vec![
+            card(4, Color::BLACK, CardStatus::HIDDEN),
+            card(6, Color::WHITE, CardStatus::HIDDEN),
+        ];
+        let mut op = Player::new(String::from("op"), 3);
+        op.deck = vec![
+            card(3, Color::BLACK, CardStatus::REVEALED),
+            card(5, Color::BLACK, CardStatus::HIDDEN),
+            card(7, Color::BLACK, CardStatus::REVEALED),
+        ];
+        me.status = crate::PlayerStatus::PLAYING;
+        op.status = crate::PlayerStatus::PLAYING;
+
+        game.players = vec![me, op];
+        game
+    }
+
+    #[test]
+    fn test_candidates_between_revealed() {
+        let game = two_players();
+
+        // between B3 and B7, and B4 is in my hand
+        assert_eq!(candidates(&game, "me", &game.players[1], 1), vec![5, 6]);
+    }
+
+    #[test]
+    fn test_best_guess() {
+        let mut game = two_players();
+        game.players[1].deck[2] = card(6, Color::BLACK, CardStatus::REVEALED);
+
+        let guess = best_guess(&game, "me").unwrap();
+        assert_eq!(guess.opponent, "op");
+        assert_eq!(guess.card_idx, 1);
+        assert_eq!(guess.candidates, vec![5]);
+    }
+
+    #[test]
+    fn test_sure_guess_wins() {
+        let mut game = two_players();
+        game.players[1].deck[2] = card(6, Color::BLACK, CardStatus::REVEALED);
+
+        let summary = play_turn(&mut game, "me");
+
+        assert!(summary.contains("right"));
+        assert_eq!(game.state, crate::GameState::END);
+        assert_eq!(game.winner.unwrap().name, "me");
+    }
+
+    #[test]
+    fn test_hard_bot_skips_long_shots() {
+        let mut game = two_players();
+        game.players[1].deck[0] = card(3, Color::BLACK, CardStatus::HIDDEN);
+        game.card_avail = vec![Card::new(9, Color::WHITE)];
+
+        let summary = play_turn_as(&mut game, "me", Difficulty::HARD);
+
+        assert!(!summary.contains("guessed"));
+        assert_eq!(game.players[0].deck.len(), 3);
+    }
+
+    #[test]
+    fn test_easy_bot_guesses_once() {
+        let mut game = two_players();
+        // two sure guesses, a normal bot would make both
+        game.players[1].deck = vec![
+            card(0, Color::BLACK, CardStatus::HIDDEN),
+            card(1, Color::BLACK, CardStatus::REVEALED),
+            card(2, Color::BLACK, CardStatus::HIDDEN),
+            card(3, Color::BLACK, CardStatus::REVEALED),
+        ];
+        game.players[1].ncards = 4;
+
+        let summary = play_turn_as(&mut game, "me", Difficulty::EASY);
+
+        assert_eq!(summary.matches("guessed").count(), 1);
+        assert!(summary.contains("right"));
+        assert_eq!(game.state, crate::GameState::INIT);
+    }
+
+    #[test]
+    fn test_safe_move_keeps_card_hidden() {
+        let mut game = two_players();
+        game.card_avail = vec![Card::new(9, Color::WHITE)];
+
+        safe_move(&mut game, "me");
+
+        assert!(game.card_avail.is_empty());
+        assert_eq!(game.players[0].deck.len(), 3);
+        assert!(game.players[0]
+            .deck
+            .iter()
+            .all(|card| card.status == CardStatus::HIDDEN));
+    }
+}
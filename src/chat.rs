@@ -0,0 +1,154 @@
+//! Chat between the players of a room.
+//!
+//! A player sends `/say <message>` at any time, in or out of their turn; it is never taken
+//! as the answer to a prompt. The server relays it to every player and spectator as
+//! `~~<name>: <message>~~`, which clients show apart from the game.
+
+use crate::spectate::Spectators;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, Mutex};
+
+/// What a player types (and the client sends as is) to chat.
+pub const SAY_COMMAND: &str = "/say";
+const MAX_MESSAGE_LEN: usize = 200;
+
+///
+///# Everyone a chat message is relayed to
+///
+#[derive(Debug)]
+pub struct Chat {
+    members: Mutex<HashMap<String, mpsc::Sender<String>>>,
+    spectators: Arc<Spectators>,
+}
+
+impl Chat {
+    pub fn new(spectators: Arc<Spectators>) -> Chat {
+        Chat {
+            members: Mutex::new(HashMap::new()),
+            spectators,
+        }
+    }
+
+    /// Relays chat to `outgoing`, in place of any previous connection of `name`.
+    pub async fn join(&self, name: &str, outgoing: mpsc::Sender<String>) {
+        self.members.lock().await.insert(name.to_string(), outgoing);
+    }
+
+    pub async fn leave(&self, name: &str) {
+        self.members.lock().await.remove(name);
+    }
+
+    /// Relays a message from `name` to every player (them included) and spectator. A player
+    /// who is falling behind misses it.
+    pub async fn say(&self, name: &str, message: &str) {
+        let message = clean(message);
+        if message.is_empty() {
+            return;
+        }
+        let line = chat_line(name, &message);
+
+        self.members.lock().await.retain(|_, outgoing| {
+            !matches!(
+                outgoing.try_send(line.clone()),
+                Err(TrySendError::Closed(_))
+            )
+        });
+        self.spectators.public(&line).await;
+    }
+}
+
+/// The message of a `/say` command, None if `msg` isn't one.
+pub fn parse_say(msg: &str) -> Option<&str> {
+    let rest = msg.trim_start().strip_prefix(SAY_COMMAND)?;
+
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    Some(rest.trim())
+}
+
+pub fn chat_line(name: &str, message: &str) -> String {
+    format!("~~{}: {}~~\n", name, message)
+}
+
+/// Splits what the server sent into the chat lines ("name: message") and the rest.
+pub fn split_chat(response: &str) -> (Vec<String>, String) {
+    let mut lines = Vec::new();
+    let mut rest = String::new();
+    let mut remaining = response;
+
+    while let Some(start) = remaining.find("~~") {
+        let end = match remaining[start + 2..].find("~~") {
+            Some(len) => start + 2 + len,
+            None => break,
+        };
+
+        rest.push_str(&remaining[..start]);
+        lines.push(remaining[start + 2..end].to_string());
+
+        remaining = &remaining[end + 2..];
+        // the newline ending the chat line isn't part of the game messages
+        remaining = remaining.strip_prefix('\n').unwrap_or(remaining);
+    }
+    rest.push_str(remaining);
+
+    (lines, rest)
+}
+
+fn clean(message: &str) -> String {
+    message
+        .chars()
+        .filter(|c| *c != '~' && !c.is_control())
+        .take(MAX_MESSAGE_LEN)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_say() {
+        assert_eq!(parse_say("/say hello there"), Some("hello there"));
+        assert_eq!(parse_say("  /say  hi \n"), Some("hi"));
+        assert_eq!(parse_say("/say"), Some(""));
+        assert_eq!(parse_say("/sayhi"), None);
+        assert_eq!(parse_say("3"), None);
+    }
+
+    #[test]
+    fn test_split_chat() {
+        let (lines, rest) = split_chat("~~alice: hi~~\nIt's your turn~~bob: go!~~\n");
+
+        assert_eq!(lines, vec!["alice: hi", "bob: go!"]);
+        assert_eq!(rest, "It's your turn");
+
+        let (lines, rest) = split_chat("no chat here");
+        assert!(lines.is_empty());
+        assert_eq!(rest, "no chat here");
+    }
+
+    #[tokio::test]
+    async fn test_say_relays_to_everyone() {
+        let chat = Chat::new(Arc::new(Spectators::default()));
+        let (alice, mut alice_rx) = mpsc::channel(8);
+        let (bob, mut bob_rx) = mpsc::channel(8);
+        chat.join("alice", alice).await;
+        chat.join("bob", bob).await;
+
+        chat.say("alice", "good ~~luck~~\u{7}").await;
+        chat.say("alice", "   ").await;
+
+        assert_eq!(alice_rx.try_recv().unwrap(), "~~alice: good luck~~\n");
+        assert_eq!(bob_rx.try_recv().unwrap(), "~~alice: good luck~~\n");
+        assert!(bob_rx.try_recv().is_err());
+    }
+}
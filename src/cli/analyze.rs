@@ -0,0 +1,39 @@
+use clap::Parser;
+use davincicode::analysis::Report;
+use davincicode::archive;
+use std::path::PathBuf;
+use std::process;
+
+/// Compares every decision of a saved game with the odds, as JSON
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// The game, in notation or in JSON as the archive exports it
+    file: PathBuf,
+
+    /// Print the report as text instead
+    #[arg(short, long)]
+    text: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let report = archive::read(&args.file)
+        .map_err(|error| error.to_string())
+        .and_then(|record| Report::of(&record));
+    match report {
+        Ok(report) if args.text => print!("{}", report),
+        Ok(report) => match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(error) => {
+                eprintln!("Can't write the report: {}", error);
+                process::exit(1);
+            }
+        },
+        Err(error) => {
+            eprintln!("Can't analyze {}: {}", args.file.display(), error);
+            process::exit(1);
+        }
+    }
+}
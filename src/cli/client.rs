@@ -1,22 +1,39 @@
 use colored::Colorize;
 use std::io::{self, Write};
+use std::path::PathBuf;
 use std::process;
-use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
-use tokio::sync::{Mutex, Notify};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, ReadHalf, WriteHalf};
+use tokio::sync::mpsc;
 use tokio::task;
-use tokio::time::{self, Duration};
+use tokio::time::{sleep, Duration};
+
+use davincicode::access;
+use davincicode::chat;
+use davincicode::conn::OUTGOING_QUEUE;
+use davincicode::fairness;
+use davincicode::hint;
+use davincicode::prompt;
+use davincicode::session::RESUME_PREFIX;
+use davincicode::spectate::SPECTATE_HELLO;
+use davincicode::tls::{self, Trust};
+use davincicode::transport::{self, Endpoint, Stream};
+use davincicode::Card;
+use tokio_rustls::TlsConnector;
 
 use clap::Parser;
 
+const RECONNECT_ATTEMPTS: u32 = 20;
+const RECONNECT_DELAY_SECS: u64 = 3;
+
 /// The client to the davinci code game
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// User name
-    #[arg(short, long)]
-    name: String,
+    #[arg(short, long, required_unless_present = "spectate")]
+    name: Option<String>,
 
     /// Server address
     #[arg(short, long, default_value_t = String::from("127.0.0.1"))]
@@ -25,108 +42,332 @@ struct Args {
     /// Server port
     #[arg(short, long, default_value_t = String::from("8079" ))]
     port: String,
+
+    /// Watch the game instead of playing
+    #[arg(short, long)]
+    spectate: bool,
+
+    /// Host key printed by the server, to watch with every card revealed
+    #[arg(short = 'k', long, requires = "spectate")]
+    host_key: Option<String>,
+
+    /// Password or invite code of the room
+    #[arg(short, long, conflicts_with = "spectate")]
+    code: Option<String>,
+
+    /// Unix domain socket of a server on this machine, instead of its address and port
+    #[arg(short, long, conflicts_with_all = ["tls_ca", "tls_fingerprint"])]
+    unix: Option<PathBuf>,
+
+    /// Connect over TLS, trusting the server certificate (or its CA) in this PEM file
+    #[arg(long, conflicts_with = "tls_fingerprint")]
+    tls_ca: Option<PathBuf>,
+
+    /// Connect over TLS, trusting the server certificate with this SHA-256 fingerprint
+    #[arg(long)]
+    tls_fingerprint: Option<String>,
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
 
-    let address = format!("{}:{}", args.addr, args.port);
-    let mut buffer = [0u8; 1024];
+    let address = match args.unix {
+        Some(path) => Endpoint::UNIX(path),
+        None => Endpoint::TCP(format!("{}:{}", args.addr, args.port)),
+    };
+
+    let tls = Trust::from_args(args.tls_ca, args.tls_fingerprint).map(|trust| {
+        tls::connector(&trust).unwrap_or_else(|error| {
+            eprintln!("Cannot set up TLS: {}", error);
+            process::exit(1);
+        })
+    });
 
-    let mut stream = TcpStream::connect(address.clone()).await.unwrap();
+    let mut stream = transport::connect(&address, tls.as_ref()).await.unwrap();
     println!("{} {}", "Connected to server at".green(), address);
 
-    // send the "init" message after connecting
-    let init_message = "init";
-    stream.write_all(init_message.as_bytes()).await.unwrap();
-    stream.flush().await.unwrap();
+    if args.spectate {
+        let host = args.host_key.is_some();
+        let hello = match args.host_key {
+            Some(key) => format!("{}:{}", SPECTATE_HELLO, key.trim()),
+            None => SPECTATE_HELLO.to_string(),
+        };
+        handshake(&mut stream, &hello).await.unwrap();
+        println!("{}", "Watching the game".green());
 
-    let bytes_read = stream.read(&mut buffer).await.unwrap();
-    let response = String::from_utf8_lossy(&buffer[..bytes_read]);
-    println!("{} {}", "Response from server:".blue(), response);
+        spectate(stream, host).await;
+        return;
+    }
 
-    // send your name
-    let name = args.name.trim();
-    stream.write_all(name.as_bytes()).await.unwrap();
-    stream.flush().await.unwrap();
+    // send the "init" message after connecting, then your name
+    let name = args.name.unwrap_or_default();
+    let name = name.trim();
+    let hello = access::join_hello(name, args.code.as_deref());
+    let response = handshake(&mut stream, &hello).await.unwrap();
+    println!("{} {}", "Response from server:".blue(), response);
 
     println!("{}", "Sent name and init to server".green());
 
-    // an Arc<Mutex<_>> to share the stream across tasks safely
-    let shared_stream = Arc::new(Mutex::new(stream));
-    let cloned_stream = Arc::clone(&shared_stream);
-
-    // a Notify to signal the client when it's their turn to move
-    let turn_notify = Arc::new(Notify::new());
-    let cloned_notify = Arc::clone(&turn_notify);
-
-    // spawn the listener task
-    let receive_task = task::spawn(async move {
-        let mut cloned_buffer = [0u8; 1024];
-
-        loop {
-            // println!("rx Waiting lock");
-            let mut stream = cloned_stream.lock().await;
-            // println!("rx aquired lock");
-
-            let read_timeout =
-                time::timeout(Duration::from_secs(1), stream.read(&mut cloned_buffer));
-
-            match read_timeout.await {
-                Ok(result) => {
-                    let bytes_read = result.unwrap();
-                    let response = String::from_utf8_lossy(&cloned_buffer[..bytes_read]);
-
-                    println!("\n{} {}", "Response from server:".blue(), response);
-
-                    // is it my turn?
-                    if response.trim().contains("It's your turn") {
-                        cloned_notify.notify_one();
-                        // println!("Notified tx");
-                    } else if response.trim().contains("You won! Congrats!") {
-                        println!("{}\n", "Nice, exiting.".green());
-                        process::exit(0);
-                    } else {
-                        //println!("not a cmd with rq see? ==> {}\n", response);
-                    }
+    // the reader owns the read half of the stream, the writer the write half
+    let (reader, writer) = tokio::io::split(stream);
+    let (outgoing, outgoing_rx) = mpsc::channel(OUTGOING_QUEUE);
+    let (new_writer, new_writer_rx) = mpsc::channel(1);
+
+    // set when the server asks us something, anything else typed is only chat
+    let my_turn = Arc::new(AtomicBool::new(false));
+    // where we drew, to check the log of the pile against once the game is over
+    let picks = Arc::new(Mutex::new(fairness::Picks::default()));
 
-                    if bytes_read == 0 {
+    task::spawn(write_answers(writer, outgoing_rx, new_writer_rx));
+    let receive_task = task::spawn(read_responses(
+        reader,
+        new_writer,
+        address,
+        tls,
+        Arc::clone(&my_turn),
+        Arc::clone(&picks),
+    ));
+
+    // main loop to send messages
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Ok(Some(input)) = lines.next_line().await {
+        if input.trim().is_empty() {
+            continue;
+        }
+
+        // `hint` asks the server about the guess to make, it's never an answer
+        let input = match input.trim() {
+            "hint" => hint::HINT_COMMAND.to_string(),
+            _ => input,
+        };
+
+        // chat and hints go through at any time, answers only when asked
+        let chatting =
+            chat::parse_say(&input).is_some() || hint::is_request(&input) || input.trim() == "exit";
+        if !chatting && !my_turn.swap(false, Ordering::SeqCst) {
+            println!(
+                "{} {} {}",
+                "Not your turn, use".yellow(),
+                chat::SAY_COMMAND,
+                "<message> to chat, or hint.".yellow()
+            );
+            continue;
+        }
+        if !chatting {
+            picks.lock().unwrap().answered(&input);
+        }
+
+        if outgoing.send(input.trim().to_string()).await.is_err() {
+            break;
+        }
+
+        if input.trim() == "exit" {
+            break;
+        }
+        println!("{}", ">>>".green());
+    }
+
+    // the writer sends what is still queued, the reader runs until the server hangs up
+    drop(outgoing);
+    let _ = receive_task.await;
+}
+
+/// Prints what the server sends, and takes our seat back when the connection drops. The
+/// write half of a new connection goes to the writer.
+async fn read_responses(
+    mut reader: ReadHalf<Stream>,
+    new_writer: mpsc::Sender<WriteHalf<Stream>>,
+    address: Endpoint,
+    tls: Option<TlsConnector>,
+    my_turn: Arc<AtomicBool>,
+    picks: Arc<Mutex<fairness::Picks>>,
+) {
+    let mut buffer = [0u8; 1024];
+    let mut token: Option<String> = None;
+    let mut commit: Option<String> = None;
+    let mut my_cards: Vec<Card> = Vec::new();
+    // a reveal that didn't fit in one read
+    let mut reveal = String::new();
+
+    loop {
+        let bytes_read = reader.read(&mut buffer).await.unwrap_or(0);
+
+        if bytes_read == 0 {
+            // connection lost, try to take our seat back
+            let token = match token.clone() {
+                Some(token) => token,
+                None => break,
+            };
+            println!("\n{}", "Connection lost, reconnecting...".yellow());
+
+            match reconnect(&address, &token, tls.as_ref()).await {
+                Some(new_stream) => {
+                    let (new_reader, writer) = tokio::io::split(new_stream);
+                    reader = new_reader;
+                    if new_writer.send(writer).await.is_err() {
                         break;
                     }
-                    // println!("Waiting for your turn.");
-                }
-                Err(_) => {
-                    //println!("No response, moving on");
+                    println!("{} {}", "Reconnected to".green(), address);
+                    continue;
                 }
+                None => break,
             }
-            drop(stream); // release the lock
         }
-    });
 
-    // main loop to send messages
+        let response = String::from_utf8_lossy(&buffer[..bytes_read]);
+
+        let (chat_lines, response) = chat::split_chat(&response);
+        for line in chat_lines {
+            println!("{} {}", "[chat]".magenta(), line);
+        }
+        let (hints, response) = hint::split_hints(&response);
+        for hint in hints {
+            println!("{} {}", "[hint]".cyan(), hint);
+        }
+        let (asked, response) = prompt::split_prompt(&response);
+        if response.trim().is_empty() {
+            continue;
+        }
+
+        println!("\n{} {}", "Response from server:".blue(), response);
+
+        if let Some(new_token) = parse_token(&response) {
+            token = Some(new_token);
+        }
+        if let Some(new_commit) = fairness::parse_commit(&response) {
+            commit = Some(new_commit);
+        }
+        if let Some(cards) = fairness::own_cards(&response) {
+            my_cards = cards;
+        }
+
+        // the seed comes last, once the game is over
+        if response.contains("%%reveal:") || !reveal.is_empty() {
+            reveal.push_str(&response);
+            if let Some(reveal) = fairness::Reveal::parse(&reveal) {
+                let my_picks = picks.lock().unwrap().picked();
+                let verdict = fairness::verdict(commit.as_deref(), &reveal, &my_cards, &my_picks);
+                println!("\n{}\n", verdict.yellow());
+                process::exit(0);
+            }
+        }
+
+        // is it my turn? asked again after a wrong answer, it still is
+        if asked {
+            picks.lock().unwrap().asked(&response);
+            my_turn.store(true, Ordering::SeqCst);
+            print!("<<< ");
+            io::stdout().flush().unwrap();
+        } else if response.trim().contains("You won! Congrats!") {
+            println!("{}\n", "Nice, you won.".green());
+        }
+    }
+
+    println!("{}", "Lost the connection to the server.".red());
+    process::exit(1);
+}
+
+/// Sends what we type, on the latest connection.
+async fn write_answers(
+    mut writer: WriteHalf<Stream>,
+    mut outgoing: mpsc::Receiver<String>,
+    mut new_writer: mpsc::Receiver<WriteHalf<Stream>>,
+) {
     loop {
-        turn_notify.notified().await;
+        tokio::select! {
+            msg = outgoing.recv() => {
+                let msg = match msg {
+                    Some(msg) => msg,
+                    None => return,
+                };
+                // a broken connection shows up in the reader, which reconnects;
+                // the server asks again once we're back
+                let _ = writer.write_all(msg.as_bytes()).await;
+                let _ = writer.flush().await;
+            }
+            Some(replacement) = new_writer.recv() => writer = replacement,
+        }
+    }
+}
 
-        print!("<<< ");
-        io::stdout().flush().unwrap();
+/// Sends "init", waits for the server's ack, then sends `hello` (the name,
+/// resume:<token> when taking a seat back, or spectate[:<host key>] to watch).
+async fn handshake(stream: &mut Stream, hello: &str) -> Result<String, io::Error> {
+    let mut buffer = [0u8; 1024];
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
+    stream.write_all("init".as_bytes()).await?;
+    stream.flush().await?;
 
-        let mut stream = shared_stream.lock().await;
+    let bytes_read = stream.read(&mut buffer).await?;
+    let response = String::from_utf8_lossy(&buffer[..bytes_read]).to_string();
 
-        stream.write_all(input.trim().as_bytes()).await.unwrap();
-        stream.flush().await.unwrap();
+    stream.write_all(hello.as_bytes()).await?;
+    stream.flush().await?;
 
-        if input.trim() == "exit" {
+    Ok(response)
+}
+
+/// Prints the game as it goes, never answering anything. The host can still type
+/// /kick <name> or /ban <name>.
+async fn spectate(stream: Stream, host: bool) {
+    let mut buffer = [0u8; 1024];
+    let (mut reader, mut writer) = tokio::io::split(stream);
+
+    if host {
+        task::spawn(async move {
+            let mut lines = BufReader::new(tokio::io::stdin()).lines();
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                if access::parse_host_command(&line).is_none() {
+                    println!(
+                        "{} {} <name>, {} <name>",
+                        "Commands:".yellow(),
+                        access::KICK_COMMAND,
+                        access::BAN_COMMAND
+                    );
+                    continue;
+                }
+
+                let _ = writer.write_all(line.trim().as_bytes()).await;
+                let _ = writer.flush().await;
+            }
+        });
+    }
+
+    loop {
+        let bytes_read = reader.read(&mut buffer).await.unwrap_or(0);
+        if bytes_read == 0 {
             break;
         }
-        println!("{}", ">>>".green());
 
-        drop(stream); // releas the lock
+        print!("{}", String::from_utf8_lossy(&buffer[..bytes_read]));
+        io::stdout().flush().unwrap();
     }
 
-    // join
-    receive_task.await.unwrap();
+    println!("{}", "The game is over.".green());
+}
+
+async fn reconnect(address: &Endpoint, token: &str, tls: Option<&TlsConnector>) -> Option<Stream> {
+    let hello = format!("{}{}", RESUME_PREFIX, token);
+
+    for _ in 0..RECONNECT_ATTEMPTS {
+        sleep(Duration::from_secs(RECONNECT_DELAY_SECS)).await;
+
+        if let Ok(mut stream) = transport::connect(address, tls).await {
+            if handshake(&mut stream, &hello).await.is_ok() {
+                return Some(stream);
+            }
+        }
+    }
+
+    None
+}
+
+fn parse_token(response: &str) -> Option<String> {
+    // the server sends it as $$<token>$$
+    let start = response.find("$$")? + 2;
+    let len = response[start..].find("$$")?;
+
+    Some(response[start..start + len].to_string())
 }
@@ -1,11 +1,31 @@
 use colored::Colorize;
 use rand::Rng;
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process;
 use std::sync::Arc;
 use std::vec;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{Mutex, Semaphore};
+use tokio::sync::mpsc;
+use tokio::time::{timeout, timeout_at, Duration, Instant};
+
+use davincicode::access::{self, Access, Secret};
+use davincicode::analysis;
+use davincicode::archive::{self, GameRecord, ServerCommand};
+use davincicode::bot;
+use davincicode::conn::{self, Conn, SlowPolicy};
+use davincicode::fairness;
+use davincicode::hint;
+use davincicode::prompt;
+use davincicode::room::{self, IdlePolicy, Room};
+use davincicode::session::{self, Sessions};
+use davincicode::spectate::{self, Spectators, View};
+use davincicode::tls;
+use davincicode::transport::{Listener, Stream};
+use davincicode::web;
+use socket2::{SockRef, TcpKeepalive};
+use tokio_rustls::TlsAcceptor;
 
 use clap::Parser;
 
@@ -24,32 +44,109 @@ struct Args {
     /// Number of players
     #[arg(short, long, default_value_t = 2)]
     nplayers: u16,
+
+    /// Seconds to wait for a disconnected player to come back before applying the idle policy
+    #[arg(short, long, default_value_t = session::DEFAULT_GRACE_SECS)]
+    grace: u64,
+
+    /// Seconds a player has for each decision, 0 to wait forever
+    #[arg(short, long, default_value_t = room::DEFAULT_DECISION_SECS)]
+    decision_timeout: u64,
+
+    /// What happens to players who time out or don't come back
+    #[arg(short, long, value_enum, default_value_t = IdlePolicy::AUTO)]
+    idle_policy: IdlePolicy,
+
+    /// What happens to players who read slower than the game writes to them
+    #[arg(long, value_enum, default_value_t = SlowPolicy::DISCONNECT)]
+    slow_clients: SlowPolicy,
+
+    /// Seconds spectators wait to see the whole table, 0 to only show them the revealed cards
+    #[arg(short, long, default_value_t = 0)]
+    spectator_delay: u64,
+
+    /// Port serving the browser client and its WebSocket connections, off by default
+    #[arg(short, long)]
+    web_port: Option<String>,
+
+    /// Unix domain socket to listen on, instead of the TCP port
+    #[arg(short, long, conflicts_with_all = ["tls_cert", "tls_key"])]
+    unix: Option<PathBuf>,
+
+    /// PEM certificate to serve TLS with, plain TCP if not given
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key of the TLS certificate
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Password players need to join the room
+    #[arg(long, conflicts_with = "invite")]
+    password: Option<String>,
+
+    /// Generate an invite code players need to join the room
+    #[arg(long)]
+    invite: bool,
+
+    /// Writes a self-signed cert.pem and key.pem for this address to a directory, and exits
+    #[arg(long)]
+    gen_cert: Option<PathBuf>,
+
+    /// File finished games are appended to
+    #[arg(long, default_value = archive::DEFAULT_FILE)]
+    archive: PathBuf,
+
+    /// Don't keep finished games
+    #[arg(long)]
+    no_archive: bool,
+
+    /// Ranked room: no hints for the players
+    #[arg(long)]
+    ranked: bool,
+
+    #[command(subcommand)]
+    command: Option<ServerCommand>,
 }
 
 const GAME_END_CODE: i32 = -44;
+const PLAYER_GONE_CODE: i32 = -45;
+const PLAYER_IDLE_CODE: i32 = -46;
+const HANDSHAKE_TIMEOUT_SECS: u64 = 10;
+const KEEPALIVE_SECS: u64 = 10;
 const CARD_MAX_VAL: u32 = 11;
 const CARD_PER_PLAYER: u32 = 4;
 const START_CARD_N: u32 = 24;
 
-async fn loop_read_uint(stream: &mut TcpStream, msg: String, range: Vec<u32>) -> Option<u32> {
+async fn loop_read_uint(
+    stream: &mut Conn,
+    the_game: &davincicode::Game,
+    msg: String,
+    range: Vec<u32>,
+    decision_timeout: Option<Duration>,
+) -> Result<u32, i32> {
     let result: u32;
 
     if range.len() > 2 {
         println!("Only 2 values allowed (min-max).");
-        return None;
+        return Err(-1);
     }
 
     let max = range[1];
+    let deadline = decision_timeout.map(|decision_timeout| Instant::now() + decision_timeout);
+    // whatever was typed out of turn isn't an answer to this
+    stream.discard_pending();
+
     loop {
         let mut to_send = String::new();
         let msg = format!("{} (0-{})", msg, max);
 
-        to_send.push_str(format!("{}\n", msg.blue()).as_str());
-        if send_something(stream, &to_send).await {
-            return None;
+        to_send.push_str(&prompt::question_line(&format!("{}", msg.blue())));
+        if send_in_game(stream, the_game, &to_send).await {
+            return Err(PLAYER_GONE_CODE);
         }
 
-        let response = recv_something(stream).await;
+        let response = recv_before(stream, deadline).await?;
         let parsed_response = response.parse::<u32>();
 
         match parsed_response {
@@ -65,35 +162,40 @@ async fn loop_read_uint(stream: &mut TcpStream, msg: String, range: Vec<u32>) ->
         }
     }
 
-    Some(result)
+    Ok(result)
 }
 
 async fn loop_read_str(
-    stream: &mut TcpStream,
+    stream: &mut Conn,
+    the_game: &davincicode::Game,
     msg: String,
     variant: Vec<String>,
-) -> Option<String> {
+    decision_timeout: Option<Duration>,
+) -> Result<String, i32> {
     if variant.len() > 2 {
         println!("Only 2 variant allowed.");
-        return None;
+        return Err(-1);
     }
     let v0 = variant[0].clone();
     let v1 = variant[1].clone();
+    let deadline = decision_timeout.map(|decision_timeout| Instant::now() + decision_timeout);
+    // whatever was typed out of turn isn't an answer to this
+    stream.discard_pending();
 
     loop {
         let mut to_send = String::new();
         let msg = format!("{} ({}/{})", msg, variant[0], variant[1]);
 
-        to_send.push_str(format!("{}\n", msg.blue()).as_str());
-        if send_something(stream, &to_send).await {
-            return None;
+        to_send.push_str(&prompt::question_line(&format!("{}", msg.blue())));
+        if send_in_game(stream, the_game, &to_send).await {
+            return Err(PLAYER_GONE_CODE);
         }
 
-        let response = recv_something(stream).await;
+        let response = recv_before(stream, deadline).await?;
 
         let value = response.as_str();
         if value == v0 || value == v1 {
-            return Some(response);
+            return Ok(response);
         } else {
             println!("{}\n", "Dunno, asking again".red());
         }
@@ -101,10 +203,11 @@ async fn loop_read_str(
 }
 
 async fn yes_variant_guess(
-    stream: &mut TcpStream,
+    stream: &mut Conn,
     player_name: String,
     the_game: &mut davincicode::Game,
     dialog_status: &mut i32,
+    room: &Room<Stream>,
 ) -> i32 {
     loop {
         // println!("Continuing with guess");
@@ -132,18 +235,38 @@ async fn yes_variant_guess(
 
         let mut continue_guess = false;
 
-        let returned_val =
-            guess_opponent_card_loop(stream, the_game, opponents_names.clone(), &player_name).await;
+        let returned_val = guess_opponent_card_loop(
+            stream,
+            the_game,
+            opponents_names.clone(),
+            &player_name,
+            room,
+        )
+        .await;
+
+        if returned_val == PLAYER_GONE_CODE || returned_val == PLAYER_IDLE_CODE {
+            *dialog_status = returned_val;
+            break;
+        }
 
         if returned_val == 1 {
             // good guess, either continue or break and save hidden card
-            let response = loop_read_str(
+            offer_hint(stream, the_game, &player_name, room);
+            let response = match loop_read_str(
                 stream,
+                the_game,
                 "It's your turn: Would you like to make another guess?".to_string(),
                 vec!["yes".to_string(), "no".to_string()],
+                room.decision_timeout,
             )
             .await
-            .expect("Got no response");
+            {
+                Ok(response) => response,
+                Err(code) => {
+                    *dialog_status = code;
+                    break;
+                }
+            };
 
             match response.as_str() {
                 "yes" => {
@@ -173,9 +296,10 @@ async fn yes_variant_guess(
 
 async fn player_move(
     player_name: String,
-    player_tcp_name: &mut HashMap<String, TcpStream>,
+    player_tcp_name: &mut HashMap<String, Conn>,
     the_game: &mut davincicode::Game,
-) {
+    room: &Room<Stream>,
+) -> i32 {
     let mut dialog_status: i32 = -1;
     let stream = player_tcp_name.get_mut(&(player_name.clone())).unwrap();
 
@@ -184,10 +308,6 @@ async fn player_move(
     let max_card_avail_value = the_game.card_avail.len() as u32;
     let mut can_t_draw_any: bool = false;
 
-    if max_card_avail_value < 1 {
-        can_t_draw_any = true;
-    }
-
     the_game.shuffle_avail_card();
 
     let current_player = the_game
@@ -196,6 +316,13 @@ async fn player_move(
         .find(|player| player.name == player_name)
         .expect("No player found\n");
 
+    // a player resuming their turn after a reconnection already holds the card they drew
+    let mut current_player_side_card: Option<davincicode::Card> = current_player.side_card;
+
+    if max_card_avail_value < 1 && current_player_side_card.is_none() {
+        can_t_draw_any = true;
+    }
+
     if can_t_draw_any {
         // no more cards on the set to draw, so take turn guessing op card
         game_context.push_str(
@@ -220,7 +347,7 @@ async fn player_move(
     }
     game_context.push_str(format!("\n{}", "Your opponents deck: ".yellow()).as_str());
     for opponent in the_game.players.iter() {
-        if opponent.name == *opponents_names.first().unwrap() {
+        if opponents_names.contains(&opponent.name) {
             game_context.push_str(format!("\n{}", "Player: ".blue()).as_str());
             game_context.push_str(&opponent.name);
             game_context.push_str(format!("{}", " ".blue()).as_str());
@@ -236,35 +363,34 @@ async fn player_move(
 
     // read user input (card to pick)
     let picked_card_number: usize;
-    let mut current_player_side_card: Option<davincicode::Card> = None;
 
-    if !can_t_draw_any {
+    if !can_t_draw_any && current_player_side_card.is_none() {
         let to_send = format!(
             "{} {}{}\n",
             "It's your turn".blue(),
             player_name,
             game_context,
         );
-        if send_something(stream, &to_send).await {
-            return;
+        if send_in_game(stream, the_game, &to_send).await {
+            return PLAYER_GONE_CODE;
         }
 
-        let value = loop_read_uint(
+        let value = match loop_read_uint(
             stream,
-            "Enter card number to draw it".to_string(),
+            the_game,
+            prompt::DRAW_QUESTION.to_string(),
             vec![0, max_card_avail_value - 1],
+            room.decision_timeout,
         )
         .await
-        .expect("Got no response");
+        {
+            Ok(value) => value,
+            Err(code) => return code,
+        };
 
         picked_card_number = value as usize;
         // draw the card here
-        for p in the_game.players.iter_mut() {
-            if p.name == player_name.clone() {
-                current_player_side_card =
-                    Some(p.draw_specific_card(&mut the_game.card_avail, picked_card_number));
-            }
-        }
+        current_player_side_card = the_game.draw(&player_name, picked_card_number);
     }
 
     // continue to ask if they want to keep it hidden now or guess opponent card
@@ -289,18 +415,23 @@ async fn player_move(
                 }
             }
             to_send.push_str(format!("\n{}\n", "Saving it as side card.".blue()).as_str());
-            if send_something(stream, &to_send).await {
-                return;
+            if send_in_game(stream, the_game, &to_send).await {
+                return PLAYER_GONE_CODE;
             }
 
             // player drawn a card, so they can decide not to make a guess
-            let response = loop_read_str(
+            let response = match loop_read_str(
                 stream,
+                the_game,
                 "It's your turn: Would you like to make a guess?".to_string(),
                 vec!["yes".to_string(), "no".to_string()],
+                room.decision_timeout,
             )
             .await
-            .expect("Got no response");
+            {
+                Ok(response) => response,
+                Err(code) => return code,
+            };
 
             match response.as_str() {
                 "yes" => {
@@ -310,6 +441,7 @@ async fn player_move(
                         player_name.to_string(),
                         the_game,
                         &mut dialog_status,
+                        room,
                     )
                     .await;
                 }
@@ -327,20 +459,21 @@ async fn player_move(
                 player_name.to_string(),
                 the_game,
                 &mut dialog_status,
+                room,
             )
             .await;
         }
     }
 
     match dialog_status {
+        PLAYER_GONE_CODE | PLAYER_IDLE_CODE => {
+            // lost the connection or ran out of time mid-turn, the side card (if any) stays with the player
+            return dialog_status;
+        }
         1 => {
             // player picked a card, and decided to keep it
             // or picked a card, guessed and won too and refused to keep guessing
-            for p in the_game.players.iter_mut() {
-                if p.name == player_name.clone() {
-                    p.save_side_card(true);
-                }
-            }
+            the_game.save_side_card(&player_name, true);
 
             let mut to_send: String = "Okay, saving your side card as hidden.\n".to_owned();
 
@@ -357,15 +490,11 @@ async fn player_move(
                     to_send.push_str(&p.show_hand(true, true));
                 }
             }
-            if send_something(stream, &to_send).await {}
+            send_in_game(stream, the_game, &to_send).await;
         }
         2 => {
             // player picked a card, guess and lost, revealing their card
-            for p in the_game.players.iter_mut() {
-                if p.name == player_name.clone() {
-                    p.save_side_card(false);
-                }
-            }
+            the_game.save_side_card(&player_name, false);
 
             let mut to_send = String::new();
 
@@ -392,27 +521,30 @@ async fn player_move(
                 }
             }
 
-            if send_something(stream, &to_send).await {}
+            send_in_game(stream, the_game, &to_send).await;
         }
         GAME_END_CODE => {
             println!("We got a winner: {:?}\n", the_game.winner);
             // announce this to the remaining player
             let to_send = format!("{}", "You won! Congrats!".green(),);
-            if send_something(stream, &to_send).await {}
+            send_in_game(stream, the_game, &to_send).await;
+
+            return 0;
         }
         _ => {
             // what??
         }
     }
 
-    //
+    2
 }
 
 async fn guess_opponent_card_loop(
-    stream: &mut TcpStream,
+    stream: &mut Conn,
     the_game: &mut davincicode::Game,
     opponents_names: Vec<String>,
     player_name: &str,
+    room: &Room<Stream>,
 ) -> i32 {
     let mut picked_card_number: usize;
     let mut golden_value: u32 = u32::MAX;
@@ -422,6 +554,8 @@ async fn guess_opponent_card_loop(
     let mut skip_chose_op = false;
 
     loop {
+        offer_hint(stream, the_game, player_name, room);
+
         if opponents_names.len() == 1 {
             skip_chose_op = true;
         }
@@ -445,20 +579,25 @@ async fn guess_opponent_card_loop(
             );
         }
 
-        if send_something(stream, &to_send).await {
-            return -2;
+        if send_in_game(stream, the_game, &to_send).await {
+            return PLAYER_GONE_CODE;
         }
 
         let mut op_idx = 0;
         if !skip_chose_op {
             // pick the opponent
-            op_idx = loop_read_uint(
+            op_idx = match loop_read_uint(
                 stream,
+                the_game,
                 "It's your turn! Pick current opponent for this guess: \n".to_string(),
                 vec![0, opponents_names.len() as u32 - 1],
+                room.decision_timeout,
             )
             .await
-            .expect("Got no response");
+            {
+                Ok(value) => value,
+                Err(code) => return code,
+            };
         } // else, skip, and the op_idx will be 0
 
         let opponent_name_ = String::from(opponents_names.get(op_idx as usize).unwrap()); //.unwrap());
@@ -484,32 +623,37 @@ async fn guess_opponent_card_loop(
         }
         to_send.push_str(format!("\n{}", "Your current deck: ".green()).as_str());
         for p in the_game.players.iter() {
-            if p.name != opponent_name_ {
-                // so it gotta be the current player
+            // with more than one opponent, not every other player is the current one
+            if p.name == player_name {
                 to_send.push_str(&p.show_hand(false, true));
             }
         }
         to_send.push_str(format!("{}", "What your opponents see: ".yellow()).as_str());
         to_send.push_str("");
         for p in the_game.players.iter() {
-            if p.name != opponent_name_ {
-                // so it gotta be the current player
+            // with more than one opponent, not every other player is the current one
+            if p.name == player_name {
                 to_send.push_str(&p.show_hand(true, true));
             }
         }
 
-        if send_something(stream, &to_send).await {
-            return -2;
+        if send_in_game(stream, the_game, &to_send).await {
+            return PLAYER_GONE_CODE;
         }
 
         // request the player which opponent card they want to guess the value
-        let value = loop_read_uint(
+        let value = match loop_read_uint(
             stream,
+            the_game,
             "It's your turn: Which card would you like to guess".to_string(),
             vec![0, opponent_deck_len - 1],
+            room.decision_timeout,
         )
         .await
-        .expect("Got no response");
+        {
+            Ok(value) => value,
+            Err(code) => return code,
+        };
 
         picked_card_number = value as usize;
         println!("{} {}\n", "Picked card number:".blue(), picked_card_number);
@@ -517,10 +661,9 @@ async fn guess_opponent_card_loop(
         // only 1 opponent, read their specific card value
         for opponent in the_game.players.iter() {
             if opponent.name == opponent_name_ {
-                golden_value = match opponent.get_specific_card_value(picked_card_number) {
-                    None => u32::MAX,
-                    Some(val) => val,
-                };
+                golden_value = opponent
+                    .get_specific_card_value(picked_card_number)
+                    .unwrap_or(u32::MAX);
             }
         }
         // valid pick?
@@ -529,16 +672,39 @@ async fn guess_opponent_card_loop(
             continue;
         }
         // request the player to give their guessed value of the card
-        let guessed_value = loop_read_uint(
+        let guessed_value = match loop_read_uint(
             stream,
+            the_game,
             "It's your turn: Enter your guess: value between".to_string(),
             vec![0, CARD_MAX_VAL],
+            room.decision_timeout,
         )
         .await
-        .expect("Got no response");
+        {
+            Ok(value) => value,
+            Err(code) => return code,
+        };
 
-        // evaluate the guess
-        if golden_value == guessed_value {
+        // evaluate the guess, the opponent card gets revealed when right
+        let right = the_game.guess(
+            player_name,
+            &opponent_name_,
+            picked_card_number,
+            guessed_value,
+        ) == Some(true);
+
+        room.spectators
+            .public(&format!(
+                "{} guessed {}'s card {} is {}: {}\n",
+                player_name,
+                opponent_name_,
+                picked_card_number,
+                guessed_value,
+                if right { "right" } else { "wrong" }
+            ))
+            .await;
+
+        if right {
             correct_guess = true;
             println!("{}\n", "Good guess!".green());
             // ack correct guess
@@ -552,22 +718,14 @@ async fn guess_opponent_card_loop(
                 .as_str(),
             );
 
-            // mutate the opponent card to revealed
-            for opponent in the_game.players.iter_mut() {
-                if opponent.name == opponent_name_ {
-                    // the opponent card get's revealed
-                    opponent.reveal_card(picked_card_number);
-                }
-            }
-
             // show the player the new opponent deck
             for opponent in the_game.players.iter() {
                 if opponent.name == opponent_name_ {
                     to_send.push_str(&opponent.show_hand(true, true));
                 }
             }
-            if send_something(stream, &to_send).await {
-                return -2;
+            if send_in_game(stream, the_game, &to_send).await {
+                return PLAYER_GONE_CODE;
             }
             break;
         } else {
@@ -583,10 +741,276 @@ async fn guess_opponent_card_loop(
     0
 }
 
+async fn play_turn(
+    player_name: String,
+    player_tcp_name: &mut HashMap<String, Conn>,
+    the_game: &mut davincicode::Game,
+    room: &Room<Stream>,
+) -> i32 {
+    show_table(the_game, room).await;
+    room.spectators
+        .public(&format!("\nIt's {}'s turn\n", player_name))
+        .await;
+
+    loop {
+        absorb_rejoins(player_tcp_name, the_game, room).await;
+
+        if room.access.is_kicked(&player_name).await {
+            return kick_out(player_tcp_name, the_game, room, &player_name).await;
+        }
+
+        // the seat of a player who went idle is played by a bot until they come back
+        if room.is_bot(&player_name).await {
+            let summary = bot::play_turn(the_game, &player_name);
+            return announce_turn(player_tcp_name, the_game, &summary, room).await;
+        }
+
+        let ret = player_move(player_name.clone(), player_tcp_name, the_game, room).await;
+        // the hints were about that turn
+        if let Some(stream) = player_tcp_name.get(&player_name) {
+            stream.clear_hint();
+        }
+        match ret {
+            PLAYER_IDLE_CODE => {
+                let reason = format!("didn't answer in {}s", decision_secs(room));
+                return idle_out(player_tcp_name, the_game, room, &player_name, &reason).await;
+            }
+            PLAYER_GONE_CODE => {}
+            _ => return ret,
+        }
+
+        // pause the turn until the player comes back or the grace period runs out
+        println!(
+            "{} {} {}s",
+            player_name,
+            "lost their connection, waiting".yellow(),
+            room.sessions.grace.as_secs()
+        );
+        broadcast_msg(
+            player_tcp_name,
+            the_game,
+            &format!(
+                "\n{} {}\n",
+                player_name,
+                "lost their connection, waiting for them.".yellow()
+            ),
+            room,
+        )
+        .await;
+
+        match room.sessions.wait_for(&player_name).await {
+            Some(stream) => {
+                rejoin(player_tcp_name, the_game, room, &player_name, stream).await;
+            }
+            None => {
+                // they can still come back later and take their seat back
+                let reason = "didn't come back";
+                return idle_out(player_tcp_name, the_game, room, &player_name, reason).await;
+            }
+        }
+    }
+}
+
+async fn absorb_rejoins(
+    player_tcp_name: &mut HashMap<String, Conn>,
+    the_game: &mut davincicode::Game,
+    room: &Room<Stream>,
+) {
+    let names: Vec<String> = player_tcp_name.keys().cloned().collect();
+
+    for name in names {
+        if let Some(stream) = room.sessions.take(&name).await {
+            rejoin(player_tcp_name, the_game, room, &name, stream).await;
+        }
+    }
+}
+
+async fn rejoin(
+    player_tcp_name: &mut HashMap<String, Conn>,
+    the_game: &davincicode::Game,
+    room: &Room<Stream>,
+    player_name: &str,
+    stream: Stream,
+) {
+    // the old connection is dropped, the player gets a full view of the game on the new one
+    let mut conn = Conn::spawn(
+        stream,
+        player_name,
+        Arc::clone(&room.chat),
+        room.access.door(player_name).await,
+        room.slow_policy,
+    )
+    .await;
+    let _ = send_in_game(&mut conn, the_game, &resync_view(the_game, player_name)).await;
+    player_tcp_name.insert(player_name.to_string(), conn);
+
+    println!("{} {}", player_name, "reconnected".green());
+    if room.give_back(player_name).await {
+        println!(
+            "{} {}",
+            player_name,
+            "takes their seat back from the bot".green()
+        );
+    }
+}
+
+/// Applies the idle policy of the room to a player who went silent.
+async fn idle_out(
+    player_tcp_name: &mut HashMap<String, Conn>,
+    the_game: &mut davincicode::Game,
+    room: &Room<Stream>,
+    player_name: &str,
+    reason: &str,
+) -> i32 {
+    let mut summary = format!(
+        "\n{} {}, {}.\n",
+        player_name,
+        reason.yellow(),
+        room.idle_policy.describe().yellow()
+    );
+
+    match room.idle_policy {
+        IdlePolicy::AUTO => summary.push_str(&bot::safe_move(the_game, player_name)),
+        IdlePolicy::BOT => {
+            room.hand_to_bot(player_name).await;
+            summary.push_str(&bot::play_turn(the_game, player_name));
+        }
+        IdlePolicy::FORFEIT => {
+            the_game.forfeit(player_name);
+        }
+    }
+
+    announce_turn(player_tcp_name, the_game, &summary, room).await
+}
+
+/// Takes a player the host sent away out of the game.
+async fn kick_out(
+    player_tcp_name: &mut HashMap<String, Conn>,
+    the_game: &mut davincicode::Game,
+    room: &Room<Stream>,
+    player_name: &str,
+) -> i32 {
+    the_game.forfeit(player_name);
+
+    let summary = format!(
+        "\n{} {}\n",
+        player_name,
+        "was sent away by the host, all their cards get revealed.".yellow()
+    );
+    announce_turn(player_tcp_name, the_game, &summary, room).await
+}
+
+/// Tells everyone what happened during a turn nobody answered prompts for.
+async fn announce_turn(
+    player_tcp_name: &mut HashMap<String, Conn>,
+    the_game: &mut davincicode::Game,
+    summary: &str,
+    room: &Room<Stream>,
+) -> i32 {
+    print!("{}", summary);
+    broadcast_msg(player_tcp_name, the_game, summary, room).await;
+
+    // same as player_move: 0 once the game is over
+    if the_game.game_status() {
+        // the winner may not have played that turn, or not themselves
+        if let Some(winner) = the_game.players.first() {
+            if let Some(stream) = player_tcp_name.get_mut(&winner.name) {
+                let to_send = format!("{}", "You won! Congrats!".green());
+                send_in_game(stream, the_game, &to_send).await;
+            }
+        }
+        return 0;
+    }
+
+    2
+}
+
+/// Sends the spectators the table, masked or revealed depending on what they may see.
+async fn show_table(the_game: &davincicode::Game, room: &Room<Stream>) {
+    room.spectators
+        .table(
+            format!(
+                "{}\n{}",
+                "Table:".blue(),
+                spectate::table_view(the_game, false)
+            ),
+            format!(
+                "{}\n{}",
+                "Table:".blue(),
+                spectate::table_view(the_game, true)
+            ),
+        )
+        .await;
+}
+
+fn decision_secs(room: &Room<Stream>) -> u64 {
+    room.decision_timeout
+        .map(|decision_timeout| decision_timeout.as_secs())
+        .unwrap_or(0)
+}
+
+fn resync_view(the_game: &davincicode::Game, player_name: &str) -> String {
+    let mut to_send = format!(
+        "\n{} {}{}\n",
+        "Welcome back".green(),
+        player_name,
+        ", resuming the game.".green()
+    );
+
+    let player = the_game
+        .players
+        .iter()
+        .chain(the_game.lost_players.iter())
+        .find(|player| player.name == player_name);
+
+    if let Some(player) = player {
+        to_send.push_str(format!("{}", "Your deck: ".green()).as_str());
+        to_send.push_str(&player.show_hand(false, true));
+
+        if let Some(card) = player.side_card {
+            to_send.push_str(format!("\n{}", "Your side card: ".blue()).as_str());
+            match card.color {
+                davincicode::Color::BLACK => {
+                    to_send.push_str(
+                        format!("{}{}", "B".blue(), card.value.to_string().blue()).as_str(),
+                    );
+                }
+                davincicode::Color::WHITE => {
+                    to_send.push_str(
+                        format!("{}{}", "W".yellow(), card.value.to_string().yellow()).as_str(),
+                    );
+                }
+            }
+        }
+
+        to_send.push_str(format!("\n{}", "What your opponents see: ".yellow()).as_str());
+        to_send.push_str(&player.show_hand(true, true));
+    }
+
+    if !the_game.card_avail.is_empty() {
+        to_send.push_str(format!("\n{}\n", "All avail cards: ".green()).as_str());
+        to_send.push_str(&the_game.show_avail_cards(true, true));
+    }
+
+    for opponent in the_game.players.iter() {
+        if opponent.name == player_name {
+            continue;
+        }
+        to_send.push_str(format!("\n{}", "Player: ".blue()).as_str());
+        to_send.push_str(&opponent.name);
+        to_send.push(' ');
+        to_send.push_str(&opponent.show_hand(true, true));
+    }
+    to_send.push('\n');
+
+    to_send
+}
+
 async fn game_process(
     the_game: &mut davincicode::Game,
     selected_player_name: String,
-    player_tcp_name: &mut HashMap<String, TcpStream>,
+    player_tcp_name: &mut HashMap<String, Conn>,
+    room: &Room<Stream>,
 ) -> i32 {
     let mut player_order: Vec<String> = Vec::new();
     let mut current_player = selected_player_name.clone();
@@ -604,7 +1028,7 @@ async fn game_process(
 
             if player_tcp_name.contains_key(&lost_player.name.clone()) {
                 let stream = player_tcp_name.get_mut(&lost_player.name.clone()).unwrap();
-                send_something(stream, &to_send).await;
+                send_in_game(stream, the_game, &to_send).await;
             }
         }
 
@@ -635,7 +1059,10 @@ async fn game_process(
             if cnt == 0 {
                 continue;
             }
-            player_move(player.to_string(), player_tcp_name, the_game).await;
+            let turn = play_turn(player.to_string(), player_tcp_name, the_game, room).await;
+            if turn == 0 {
+                break;
+            }
         }
     }
 
@@ -647,8 +1074,10 @@ async fn game_process(
 async fn game_run(
     player_names: HashMap<u32, String>,
     selected_player_name: String,
-    player_tcp_name: &mut HashMap<String, TcpStream>,
+    player_tcp_name: &mut HashMap<String, Conn>,
+    room: &Room<Stream>,
 ) {
+    let started = archive::now();
     let mut the_game = davincicode::Game::new(START_CARD_N);
 
     for player in player_names.iter() {
@@ -657,6 +1086,10 @@ async fn game_run(
     }
     the_game.init_set();
 
+    // commit to the seed now, it is revealed once the game is over
+    let commit = format!("{}\n", fairness::commit_marker(&the_game));
+    broadcast_msg(player_tcp_name, &the_game, &commit, room).await;
+
     // send each player their own view of their deck
     for (name, client_stream) in player_tcp_name.iter_mut() {
         let mut to_send = String::new();
@@ -670,14 +1103,14 @@ async fn game_run(
 
         to_send.push_str(&current_player.show_hand(false, true));
 
-        if send_something(client_stream, &to_send).await {
+        if send_in_game(client_stream, &the_game, &to_send).await {
             return;
         }
     }
 
     // at each turn, show each others card
     let mut ret = String::new();
-    broadcast_msg(player_tcp_name, "\n").await;
+    broadcast_msg(player_tcp_name, &the_game, "\n", room).await;
 
     ret.push('\n');
     for player in the_game.players.iter() {
@@ -686,40 +1119,111 @@ async fn game_run(
         ret.push_str(&player.show_hand(true, true));
     }
 
-    broadcast_msg(player_tcp_name, &ret).await;
+    broadcast_msg(player_tcp_name, &the_game, &ret, room).await;
 
     // process cmd of all clients
-    game_process(&mut the_game, selected_player_name.clone(), player_tcp_name).await;
+    game_process(
+        &mut the_game,
+        selected_player_name.clone(),
+        player_tcp_name,
+        room,
+    )
+    .await;
     println!("{}", "Game over".green());
+
+    show_table(&the_game, room).await;
+    if let Some(winner) = the_game.players.first() {
+        let to_send = format!("\n{} {}\n", winner.name, "won the game.".green());
+        broadcast_msg(player_tcp_name, &the_game, &to_send, room).await;
+    }
+    send_analysis(player_tcp_name, &the_game, room).await;
+    send_reveals(player_tcp_name, &the_game).await;
+    archive_game(&the_game, started, room);
+}
+
+/// Appends the game to the room's archive, once it's over.
+fn archive_game(the_game: &davincicode::Game, started: u64, room: &Room<Stream>) {
+    let path = match &room.archive {
+        Some(path) if the_game.state == davincicode::GameState::END => path,
+        _ => return,
+    };
+
+    match archive::append(path, &GameRecord::of(the_game, started)) {
+        Ok(()) => println!("{} {}", "Game saved to".green(), path.display()),
+        Err(error) => eprintln!("{} {}", "Can't archive the game:".red(), error),
+    }
 }
 
-async fn init_players(client_streams_vec: Arc<Mutex<Vec<TcpStream>>>) {
-    let mut streams = client_streams_vec.lock().await;
-    let num_clients = streams.len();
+/// Lets the player ask for hints on the guess they're about to make.
+fn offer_hint(stream: &Conn, the_game: &davincicode::Game, player_name: &str, room: &Room<Stream>) {
+    let hint = match room.ranked {
+        true => String::from(hint::RANKED),
+        false => hint::advice(the_game, player_name),
+    };
+    stream.set_hint(the_game, hint);
+}
 
-    if num_clients == 0 {
+/// Tells everybody how each decision of the game compared with the odds.
+async fn send_analysis(
+    player_tcp_name: &mut HashMap<String, Conn>,
+    the_game: &davincicode::Game,
+    room: &Room<Stream>,
+) {
+    match analysis::Report::of(&GameRecord::of(the_game, 0)) {
+        Ok(report) => {
+            let report = format!("\n{}", report);
+            room.spectators.public(&report).await;
+            for client_stream in player_tcp_name.values_mut() {
+                send_something(client_stream, &report).await;
+            }
+        }
+        Err(error) => eprintln!("{} {}", "Can't analyze the game:".red(), error),
+    }
+}
+
+/// Sends each player the seed and the log of the pile, so they can check the shuffles.
+async fn send_reveals(player_tcp_name: &mut HashMap<String, Conn>, the_game: &davincicode::Game) {
+    for (name, client_stream) in player_tcp_name.iter_mut() {
+        if let Some(reveal) = fairness::Reveal::of(the_game, name) {
+            send_something(client_stream, &format!("{}\n", reveal.to_marker())).await;
+        }
+    }
+}
+
+async fn init_players(players: Vec<(String, Stream)>, room: Arc<Room<Stream>>) {
+    if players.is_empty() {
         return;
     }
 
     let mut player_names: HashMap<u32, String> = HashMap::new();
-    let mut player_tcp_name: HashMap<String, TcpStream> = HashMap::new();
-
-    // rq to clients to identify themselves
-    for (index, client_stream) in streams.iter_mut().enumerate() {
-        let request = recv_something(client_stream).await;
+    let mut player_tcp_name: HashMap<String, Conn> = HashMap::new();
 
-        let name = request.trim().to_string();
+    for (index, (name, stream)) in players.into_iter().enumerate() {
         player_names.insert(index as u32, name.clone());
-    }
 
-    let indices: Vec<usize> = (0..player_names.len()).rev().collect();
-    for idx in indices {
-        let name = player_names.get(&(idx as u32)).unwrap();
-        let tcp_stream = streams.remove(idx);
-        player_tcp_name.insert(name.to_string(), tcp_stream);
+        let door = room.access.door(&name).await;
+        let conn = Conn::spawn(
+            stream,
+            &name,
+            Arc::clone(&room.chat),
+            door,
+            room.slow_policy,
+        )
+        .await;
+        player_tcp_name.insert(name, conn);
     }
 
-    println!("Players {:?}", player_tcp_name);
+    println!("Players {:?}", player_tcp_name.keys());
+
+    // the token lets a player take their seat back if their connection drops
+    for (name, client_stream) in player_tcp_name.iter_mut() {
+        let token = room.sessions.issue(name).await;
+        send_something(
+            client_stream,
+            &format!("{} $${}$$\n", "Your session token:".green(), token),
+        )
+        .await;
+    }
 
     let mut rng = rand::rng();
     let selected_player_index = rng.random_range(0..player_tcp_name.len());
@@ -732,7 +1236,7 @@ async fn init_players(client_streams_vec: Arc<Mutex<Vec<TcpStream>>>) {
     let some_player_stream = player_tcp_name.get_mut(&some_player_name).unwrap();
     let selected_player_name = some_player_name.clone();
 
-    println!("Picked {:?} as the first to move.\n", some_player_stream);
+    println!("Picked {} as the first to move.\n", some_player_name);
 
     let mut to_send = String::new();
     to_send.push_str(format!("{}\n", "It's your turn, move".green()).as_str());
@@ -758,81 +1262,65 @@ async fn init_players(client_streams_vec: Arc<Mutex<Vec<TcpStream>>>) {
         player_names.clone(),
         selected_player_name,
         &mut player_tcp_name,
+        &room,
     )
     .await;
+    // let the last messages through before hanging up
+    for (_, conn) in player_tcp_name.drain() {
+        conn.finish().await;
+    }
 }
 
-async fn handle_client(client_id: &mut u32, client_streams_vec: Arc<Mutex<Vec<TcpStream>>>) {
-    let mut buffer = [0u8; 1024];
-
-    let mut streams = client_streams_vec.lock().await;
-    let stream = &mut streams[*client_id as usize];
-    let mut init_player: bool = false;
-
-    loop {
-        buffer.fill(0);
-        let bytes_read = match stream.read(&mut buffer).await {
-            Ok(bytes_read) => bytes_read,
-            Err(error) => {
-                eprintln!("Error reading from client {}: {}", client_id, error);
-                break;
-            }
-        };
-
-        if bytes_read == 0 {
-            println!(
-                "{} {} {}",
-                "Client".red(),
-                client_id,
-                "has disconnected".red()
-            );
-            break;
-        }
-
-        let request = String::from_utf8_lossy(&buffer[..bytes_read]);
-        println!(
-            "{} {} {}",
-            "Received from client".blue(),
-            client_id,
-            request
-        );
-
-        if request == "init" {
-            if let Err(error) = stream
-                .write_all(String::from("Init successfull").as_bytes())
-                .await
-            {
-                eprintln!("Error writing response to client {}: {}", client_id, error);
-                break;
-            }
+async fn broadcast_msg(
+    player_tcp_name: &mut HashMap<String, Conn>,
+    the_game: &davincicode::Game,
+    cmd: &str,
+    room: &Room<Stream>,
+) {
+    room.spectators.public(cmd).await;
 
-            init_player = true;
-            break;
+    for client_stream in player_tcp_name.values_mut() {
+        if send_in_game(client_stream, the_game, cmd).await {
+            continue;
         }
     }
+}
 
-    if !init_player {
-        streams.remove(*client_id as usize);
-        *client_id -= 1;
-    }
+async fn send_something(some_player: &mut Conn, cmd: &str) -> bool {
+    // true when the player's connection is gone
+    !some_player.send(cmd)
 }
 
-async fn broadcast_msg(player_tcp_name: &mut HashMap<String, TcpStream>, cmd: &str) {
-    for client_stream in player_tcp_name.values_mut() {
-        if send_something(client_stream, cmd).await {
+/// Same as `send_something`, once the message is checked against what the player may see.
+async fn send_in_game(some_player: &mut Conn, the_game: &davincicode::Game, cmd: &str) -> bool {
+    !some_player.send_in(the_game, cmd)
+}
+
+/// Hangs up on the players waiting for the game the host sent away.
+async fn drop_kicked(players: &mut Vec<(String, Stream)>, room: &Room<Stream>) {
+    let mut kept = Vec::new();
+
+    for (name, mut stream) in players.drain(..) {
+        if room.access.is_kicked(&name).await {
+            println!("{} {}", name, "was sent away by the host".yellow());
+            send_raw(&mut stream, conn::KICKED_MSG).await;
             continue;
         }
+        kept.push((name, stream));
     }
+
+    *players = kept;
 }
 
-async fn send_something(some_player: &mut TcpStream, cmd: &str) -> bool {
+/// Writes to a connection that isn't a player yet.
+async fn send_raw(stream: &mut Stream, cmd: &str) -> bool {
     let mut ret = false;
 
-    if let Err(error) = some_player.write_all(cmd.as_bytes()).await {
+    if let Err(error) = stream.write_all(cmd.as_bytes()).await {
         eprintln!("Error writing response to client: {}", error);
         ret = true;
     }
-    if let Err(error) = some_player.flush().await {
+    if let Err(error) = stream.flush().await {
         eprintln!("Error flushing response to client {}", error);
         ret = true;
     }
@@ -840,63 +1328,665 @@ async fn send_something(some_player: &mut TcpStream, cmd: &str) -> bool {
     ret
 }
 
-async fn recv_something(stream: &mut TcpStream) -> String {
-    let mut buffer = [0; 1024];
+/// Reads an answer that has to come before the deadline of the current decision.
+async fn recv_before(stream: &mut Conn, deadline: Option<Instant>) -> Result<String, i32> {
+    // None means the connection is gone, the player may come back with their token
+    let response = match deadline {
+        Some(deadline) => match timeout_at(deadline, stream.recv()).await {
+            Ok(response) => response,
+            Err(_) => return Err(PLAYER_IDLE_CODE),
+        },
+        None => stream.recv().await,
+    };
+
+    response.ok_or(PLAYER_GONE_CODE)
+}
 
-    let bytes_read = stream
-        .read(&mut buffer)
-        .await
-        .expect("Cannot read from client\n");
+fn keep_alive(stream: &TcpStream) {
+    // probe idle connections, so a vanished peer errors out instead of hanging the game
+    let keepalive = TcpKeepalive::new()
+        .with_time(Duration::from_secs(KEEPALIVE_SECS))
+        .with_interval(Duration::from_secs(KEEPALIVE_SECS));
+
+    if let Err(error) = SockRef::from(stream).set_tcp_keepalive(&keepalive) {
+        eprintln!("Cannot enable keepalive: {}", error);
+    }
+}
+
+/// Keeps accepting connections once the game started, so that players who lost theirs
+/// can take their seat back with their session token.
+async fn accept_connections(
+    listener: TcpListener,
+    room: Arc<Room<Stream>>,
+    joining: mpsc::Sender<(String, Stream)>,
+    tls: Option<TlsAcceptor>,
+) {
+    while let Ok((stream, _)) = listener.accept().await {
+        keep_alive(&stream);
+
+        let room = Arc::clone(&room);
+        let joining = joining.clone();
+        let tls = tls.clone();
+        tokio::spawn(async move {
+            if let Some(stream) = secure(stream, tls).await {
+                handshake(stream, room, joining).await;
+            }
+        });
+    }
+}
+
+/// Same as accept_connections, for a Unix domain socket or clients in this process.
+async fn accept_streams(
+    mut listener: Listener,
+    room: Arc<Room<Stream>>,
+    joining: mpsc::Sender<(String, Stream)>,
+) {
+    while let Some(stream) = listener.accept().await {
+        tokio::spawn(handshake(stream, Arc::clone(&room), joining.clone()));
+    }
+}
+
+/// Same as accept_connections, for browsers: the page of the web client, then its WebSocket.
+async fn accept_web(
+    listener: TcpListener,
+    room: Arc<Room<Stream>>,
+    joining: mpsc::Sender<(String, Stream)>,
+    tls: Option<TlsAcceptor>,
+) {
+    while let Ok((stream, _)) = listener.accept().await {
+        keep_alive(&stream);
+
+        let room = Arc::clone(&room);
+        let joining = joining.clone();
+        let tls = tls.clone();
+        tokio::spawn(async move {
+            let stream = match secure(stream, tls).await {
+                Some(stream) => stream,
+                None => return,
+            };
+            if let Some(stream) = web::accept(stream).await {
+                handshake(Box::new(stream), room, joining).await;
+            }
+        });
+    }
+}
+
+/// The TLS handshake, when the server has a certificate.
+async fn secure(stream: TcpStream, tls: Option<TlsAcceptor>) -> Option<Stream> {
+    let secured = timeout(
+        Duration::from_secs(HANDSHAKE_TIMEOUT_SECS),
+        tls::accept(stream, tls.as_ref()),
+    )
+    .await;
+
+    match secured {
+        Ok(Ok(stream)) => Some(stream),
+        Ok(Err(error)) => {
+            eprintln!("TLS handshake failed: {}", error);
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+/// Writes a self-signed pair for LAN games, to pass to the clients with --tls-ca or
+/// --tls-fingerprint.
+fn gen_cert(dir: &std::path::Path, addr: &str) {
+    let mut names = vec![String::from("localhost"), String::from("127.0.0.1")];
+    if !names.iter().any(|name| name == addr) {
+        names.push(addr.to_string());
+    }
+
+    match tls::write_self_signed(dir, &names) {
+        Ok(fingerprint) => {
+            println!(
+                "{} {} {}",
+                "Wrote".green(),
+                dir.join(tls::CERT_FILE).display(),
+                dir.join(tls::KEY_FILE).display()
+            );
+            println!("{} {}", "Certificate fingerprint:".blue(), fingerprint);
+        }
+        Err(error) => {
+            eprintln!("Cannot write the certificate: {}", error);
+            process::exit(1);
+        }
+    }
+}
+
+fn load_tls(args: &Args) -> Option<TlsAcceptor> {
+    let (cert, key) = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => (cert, key),
+        _ => return None,
+    };
+
+    let acceptor = tls::acceptor(cert, key).unwrap_or_else(|error| {
+        eprintln!("Cannot load the TLS certificate: {}", error);
+        process::exit(1);
+    });
+    if let Ok(fingerprint) = tls::file_fingerprint(cert) {
+        println!("{} {}", "TLS certificate fingerprint:".blue(), fingerprint);
+    }
+
+    Some(acceptor)
+}
+
+/// "init", then the name of a new player, resume:<token> to take a seat back, or
+/// spectate[:<host key>] to watch the game.
+async fn handshake(
+    mut stream: Stream,
+    room: Arc<Room<Stream>>,
+    joining: mpsc::Sender<(String, Stream)>,
+) {
+    if read_handshake(&mut stream).await.as_deref() != Some("init") {
+        return;
+    }
+    if send_raw(&mut stream, "Init successfull").await {
+        return;
+    }
+
+    let request = match read_handshake(&mut stream).await {
+        Some(request) => request,
+        None => return,
+    };
+
+    if let Some(key) = spectate::parse_spectate(&request) {
+        let view = room.spectators.view_for(key);
+        println!("{} {:?}", "A spectator joined:".blue(), view);
+        let mut said = room.spectators.join(stream, view).await;
+
+        // the host can send players away, for the whole game
+        if view == View::HOST {
+            while let Some(msg) = said.recv().await {
+                if let Some(command) = access::parse_host_command(&msg) {
+                    room.host_command(command).await;
+                }
+            }
+        }
+        return;
+    }
 
-    let request = String::from_utf8_lossy(&buffer[..bytes_read]);
+    if let Some(token) = session::parse_resume(&request) {
+        // nobody the host sent away takes their seat back
+        if let Some(name) = room.sessions.owner(token).await {
+            if room.access.is_kicked(&name).await {
+                let error = access::AccessError::KICKED;
+                println!("{} {}: {}", "Refused".red(), name, error);
+                send_raw(&mut stream, &format!("{}\n", error)).await;
+                return;
+            }
+        }
+        match room.sessions.resume(token, stream).await {
+            Ok(name) => println!("{} {}", name, "is reconnecting".blue()),
+            Err(mut stream) => {
+                send_raw(
+                    &mut stream,
+                    &format!("{}", "Unknown session token.\n".red()),
+                )
+                .await;
+            }
+        }
+        return;
+    }
+
+    // a new player, until the game starts
+    let (name, secret) = access::parse_join(&request);
+    if name.is_empty() {
+        return;
+    }
+    if let Err(error) = room.access.admit(name, secret).await {
+        println!("{} {}: {}", "Refused".red(), name, error);
+        send_raw(&mut stream, &format!("{}\n", error)).await;
+        return;
+    }
+    let name = name.to_string();
+    if let Err(mpsc::error::SendError((_, mut stream))) = joining.send((name, stream)).await {
+        send_raw(
+            &mut stream,
+            &format!("{}", "The game already started.\n".red()),
+        )
+        .await;
+    }
+}
+
+async fn read_handshake(stream: &mut Stream) -> Option<String> {
+    let mut buffer = [0u8; 1024];
 
-    request.to_string()
+    match timeout(
+        Duration::from_secs(HANDSHAKE_TIMEOUT_SECS),
+        stream.read(&mut buffer),
+    )
+    .await
+    {
+        Ok(Ok(bytes_read)) if bytes_read > 0 => {
+            Some(String::from_utf8_lossy(&buffer[..bytes_read]).to_string())
+        }
+        _ => None,
+    }
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
 
-    let address = format!("{}:{}", args.addr, args.port);
+    if let Some(ServerCommand::ARCHIVE { command }) = &args.command {
+        if let Err(error) = archive::run(&args.archive, command) {
+            eprintln!("{} {}", "Archive error:".red(), error);
+        }
+        return;
+    }
+    if let Some(dir) = &args.gen_cert {
+        gen_cert(dir, &args.addr);
+        return;
+    }
+    let tls = load_tls(&args);
+
+    let address = match &args.unix {
+        Some(path) => path.display().to_string(),
+        None => format!("{}:{}", args.addr, args.port),
+    };
     let required_clients = args.nplayers;
 
-    let listener = TcpListener::bind(address.clone()).await.unwrap();
+    // a Unix domain socket replaces the TCP port, for games on one machine
+    let listener = match &args.unix {
+        Some(_) => None,
+        None => Some(TcpListener::bind(address.clone()).await.unwrap()),
+    };
+    let unix_listener = args
+        .unix
+        .as_deref()
+        .map(|path| Listener::bind_unix(path).unwrap());
     println!("{} {}", "Server listening on".green(), address);
 
-    let client_streams_vec = Arc::new(Mutex::new(Vec::new()));
-    let client_counter = Arc::new(Semaphore::new(0));
-    let mut client_handles = Vec::new();
+    // the port stays open during the game, for spectators and players who need to reconnect
+    let mut room = Room::new(
+        Sessions::new(Duration::from_secs(args.grace)),
+        room::secs_or_none(args.decision_timeout),
+        args.idle_policy,
+        args.slow_clients,
+        Spectators::new(room::secs_or_none(args.spectator_delay)),
+        Access::new(Secret::from_args(args.password, args.invite)),
+        (!args.no_archive).then_some(args.archive),
+    );
+    room.ranked = args.ranked;
+    let room = Arc::new(room);
+    println!(
+        "{} {}",
+        "Host key to watch the whole game:".blue(),
+        room.spectators.host_key()
+    );
+    match room.access.secret() {
+        Some(Secret::PASSWORD(_)) => {
+            println!("{}", "Players need the room password to join".blue())
+        }
+        Some(Secret::INVITE(code)) => println!("{} {}", "Invite code to join:".blue(), code),
+        None => {}
+    }
 
-    let mut client_id = 0;
+    let (joining, mut joined) = mpsc::channel(1);
+    if let Some(web_port) = args.web_port {
+        let web_address = format!("{}:{}", args.addr, web_port);
+        let web_listener = TcpListener::bind(web_address.clone()).await.unwrap();
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        println!(
+            "{} {}://{}",
+            "Browser client served at".green(),
+            scheme,
+            web_address
+        );
 
-    while let Ok((stream, _)) = listener.accept().await {
-        client_streams_vec.lock().await.push(stream);
+        tokio::spawn(accept_web(
+            web_listener,
+            Arc::clone(&room),
+            joining.clone(),
+            tls.clone(),
+        ));
+    }
+    if let Some(listener) = listener {
+        tokio::spawn(accept_connections(
+            listener,
+            Arc::clone(&room),
+            joining.clone(),
+            tls,
+        ));
+    }
+    if let Some(listener) = unix_listener {
+        tokio::spawn(accept_streams(listener, Arc::clone(&room), joining));
+    }
 
-        client_counter.add_permits(1);
+    let mut players = Vec::new();
+    while players.len() < required_clients as usize {
+        tokio::select! {
+            player = joined.recv() => match player {
+                Some(player) => {
+                    room.access.seated(&player.0).await;
+                    players.push(player);
+                }
+                None => break,
+            },
+            _ = room.access.changed.notified() => {}
+        }
 
-        // spawn a task to handle new client
-        let cloned_streams_vec = Arc::clone(&client_streams_vec);
-        let handle = tokio::spawn(async move {
-            handle_client(&mut client_id, cloned_streams_vec).await;
-        });
-        client_handles.push(handle);
+        // the host may have sent someone away before the game starts
+        drop_kicked(&mut players, &room).await;
+
+        println!("{} {}", "current clients:".blue(), players.len());
+    }
+    // late comers are told the game already started
+    drop(joined);
+
+    println!(
+        "{} {} {}",
+        "Starting the game with".green(),
+        required_clients,
+        "clients!".green()
+    );
+
+    init_players(players, Arc::clone(&room)).await;
+    room.spectators.finish().await;
+    if let Some(path) = &args.unix {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use davincicode::leak;
+    use davincicode::replay::Replay;
+    use davincicode::transport;
+    use tokio::io::DuplexStream;
+
+    fn test_room(secret: Option<Secret>) -> Arc<Room<Stream>> {
+        Arc::new(Room::new(
+            Sessions::default(),
+            Some(Duration::from_millis(20)),
+            IdlePolicy::BOT,
+            SlowPolicy::DISCONNECT,
+            Spectators::default(),
+            Access::new(secret),
+            None,
+        ))
+    }
 
-        client_id += 1;
-        println!("{} {}", "current clients:".blue(), client_id);
+    async fn say_hello(client: &mut DuplexStream, hello: &str) -> String {
+        let mut buffer = [0u8; 1024];
+
+        client.write_all(b"init").await.unwrap();
+        let bytes_read = client.read(&mut buffer).await.unwrap();
+        client.write_all(hello.as_bytes()).await.unwrap();
+
+        String::from_utf8_lossy(&buffer[..bytes_read]).to_string()
+    }
 
-        if client_id >= required_clients as u32 {
-            for handle in client_handles {
-                handle.await.unwrap();
+    #[tokio::test]
+    async fn test_handshake_over_pipes() {
+        let room = test_room(Some(Secret::PASSWORD(String::from("pw"))));
+        let (joining, mut joined) = mpsc::channel(1);
+
+        let (server, mut client) = transport::pipe();
+        tokio::spawn(handshake(server, Arc::clone(&room), joining.clone()));
+        assert_eq!(
+            say_hello(&mut client, "alice\npw").await,
+            "Init successfull"
+        );
+        assert_eq!(joined.recv().await.unwrap().0, "alice");
+
+        let (server, mut client) = transport::pipe();
+        tokio::spawn(handshake(server, room, joining));
+        say_hello(&mut client, "bob\nnope").await;
+
+        let mut refused = String::new();
+        client.read_to_string(&mut refused).await.unwrap();
+        assert_eq!(refused, format!("{}\n", access::AccessError::PASSWORD));
+    }
+
+    #[tokio::test]
+    async fn test_kicked_players_stay_out() {
+        let room = test_room(None);
+        let token = room.sessions.issue("alice").await;
+        room.access.kick("alice").await;
+        // the game started, nobody takes new seats
+        let (joining, joined) = mpsc::channel(1);
+        drop(joined);
+
+        let (server, mut client) = transport::pipe();
+        tokio::spawn(handshake(server, Arc::clone(&room), joining.clone()));
+        say_hello(&mut client, "alice").await;
+        let mut refused = String::new();
+        client.read_to_string(&mut refused).await.unwrap();
+        assert!(refused.contains("The game already started."));
+        assert!(room.access.is_kicked("alice").await);
+
+        let (server, mut client) = transport::pipe();
+        tokio::spawn(handshake(server, Arc::clone(&room), joining));
+        say_hello(&mut client, &format!("{}{}", session::RESUME_PREFIX, token)).await;
+        let mut refused = String::new();
+        client.read_to_string(&mut refused).await.unwrap();
+        assert_eq!(refused, format!("{}\n", access::AccessError::KICKED));
+        assert!(room.sessions.take("alice").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_local_clients() {
+        let room = test_room(None);
+        let (joining, mut joined) = mpsc::channel(1);
+        let (listener, connector) = transport::local();
+        tokio::spawn(accept_streams(listener, room, joining));
+
+        let mut client = connector.connect().unwrap();
+        assert_eq!(say_hello(&mut client, "alice").await, "Init successfull");
+        assert_eq!(joined.recv().await.unwrap().0, "alice");
+    }
+
+    #[tokio::test]
+    async fn test_game_over_pipes() {
+        // nobody answers, bots play every seat to the end
+        let room = test_room(None);
+        let mut players = Vec::new();
+        let mut clients = Vec::new();
+
+        for name in ["alice", "bob"] {
+            let (server, mut client) = transport::pipe();
+            players.push((name.to_string(), server));
+
+            clients.push(tokio::spawn(async move {
+                let mut received = String::new();
+
+                client.read_to_string(&mut received).await.unwrap();
+
+                received
+            }));
+        }
+
+        timeout(Duration::from_secs(60), init_players(players, room))
+            .await
+            .expect("The game never ended");
+
+        // both got the whole game and its end, and the pipes were closed
+        let mut winners = 0;
+        for client in clients {
+            let received = client.await.unwrap();
+            assert!(received.contains("Your session token"));
+            assert!(received.contains("a bot takes over their seat"));
+            assert!(received.contains("won the game."));
+            if received.contains("You won! Congrats!") {
+                winners += 1;
             }
+        }
+        assert_eq!(winners, 1);
+    }
 
-            println!(
-                "{} {} {}",
-                "Starting the game with".green(),
-                required_clients,
-                "clients!".green()
-            );
-            init_players(Arc::clone(&client_streams_vec)).await;
-            break;
+    /// Reads until the next question, what came before it included.
+    async fn read_question(client: &mut DuplexStream) -> String {
+        let mut buffer = [0u8; 1024];
+        let mut received = String::new();
+
+        loop {
+            let bytes_read = client.read(&mut buffer).await.unwrap();
+            assert!(bytes_read > 0, "The server hung up");
+            received.push_str(&String::from_utf8_lossy(&buffer[..bytes_read]));
+            if prompt::split_prompt(&received).0 {
+                return received;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_asked_again_after_a_bad_draw() {
+        let room = Arc::new(Room::new(
+            Sessions::default(),
+            Some(Duration::from_secs(5)),
+            IdlePolicy::BOT,
+            SlowPolicy::DISCONNECT,
+            Spectators::default(),
+            Access::default(),
+            None,
+        ));
+        let (drawn, mut first_draw) = mpsc::channel(2);
+        let mut players = Vec::new();
+
+        for name in ["alice", "bob"] {
+            let (server, mut client) = transport::pipe();
+            players.push((name.to_string(), server));
+            let drawn = drawn.clone();
+
+            // whoever moves first answers out of range, then right
+            tokio::spawn(async move {
+                let question = read_question(&mut client).await;
+                assert!(question.contains("Enter card number to draw it"));
+
+                client.write_all(b"99").await.unwrap();
+                let question = read_question(&mut client).await;
+                assert!(question.contains("Enter card number to draw it"));
+
+                client.write_all(b"0").await.unwrap();
+                let received = read_question(&mut client).await;
+                let _ = drawn.send(received).await;
+            });
+        }
+        let received = timeout(Duration::from_secs(10), async {
+            tokio::select! {
+                _ = init_players(players, room) => None,
+                received = first_draw.recv() => received,
+            }
+        })
+        .await
+        .expect("Never asked again");
+        assert!(received.unwrap().contains("You picked a"));
+    }
+
+    /// Answers at random until the game is over, then checks the shuffles like a client.
+    /// Everything received comes back too.
+    async fn play_at_random(mut client: DuplexStream) -> (String, Result<(), fairness::Mismatch>) {
+        let mut buffer = [0u8; 4096];
+        let range = regex::Regex::new(r"\(0-(\d+)\)").unwrap();
+        let mut commit = None;
+        let mut my_cards = Vec::new();
+        let mut picks = fairness::Picks::default();
+        let mut all = String::new();
+
+        loop {
+            let bytes_read = client.read(&mut buffer).await.unwrap_or(0);
+            if bytes_read == 0 {
+                panic!("The game ended without a reveal");
+            }
+            let received = String::from_utf8_lossy(&buffer[..bytes_read]).to_string();
+
+            commit = fairness::parse_commit(&received).or(commit);
+            my_cards = fairness::own_cards(&received).unwrap_or(my_cards);
+            all.push_str(&received);
+            if let Some(reveal) = fairness::Reveal::parse(&all) {
+                // the analysis comes before the reveal
+                assert!(all.contains("Analysis, with what each player could see:"));
+                let verified =
+                    fairness::verify(&commit.unwrap(), &reveal, &my_cards, &picks.picked());
+                return (all, verified);
+            }
+
+            let answer = if let Some(caps) = range.captures_iter(&received).last() {
+                let max: u32 = caps[1].parse().unwrap();
+                rand::rng().random_range(0..=max).to_string()
+            } else if received.contains("(yes/no)") {
+                String::from(if rand::rng().random_bool(0.5) {
+                    "yes"
+                } else {
+                    "no"
+                })
+            } else {
+                continue;
+            };
+            picks.asked(&received);
+            picks.answered(&answer);
+
+            if client.write_all(answer.as_bytes()).await.is_err() {
+                panic!("The game ended without a reveal");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_no_leaks_in_a_whole_game() {
+        let archive_file =
+            std::env::temp_dir().join(format!("davincicode-games-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&archive_file);
+        // the server checks every message against the game as it sends it, and panics
+        let room = Arc::new(Room::new(
+            Sessions::default(),
+            Some(Duration::from_secs(5)),
+            IdlePolicy::BOT,
+            SlowPolicy::DROP,
+            Spectators::default(),
+            Access::default(),
+            Some(archive_file.clone()),
+        ));
+        let mut players = Vec::new();
+        let mut clients = Vec::new();
+
+        for name in ["alice", "bob", "carol"] {
+            let (server, client) = transport::pipe();
+            players.push((name.to_string(), server));
+            clients.push(tokio::spawn(play_at_random(client)));
+        }
+
+        timeout(Duration::from_secs(60), init_players(players, room))
+            .await
+            .expect("The game never ended");
+        let mut games = archive::load(&archive_file).unwrap();
+        std::fs::remove_file(&archive_file).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].seats.len(), 3);
+        assert!(games[0].winner.is_some());
+        let replay = Replay::new(games.remove(0)).unwrap();
+        let end = replay.players(replay.moves());
+
+        for (name, client) in ["alice", "bob", "carol"].into_iter().zip(clients) {
+            // every player can check the shuffles
+            let (received, verified) = client.await.unwrap();
+            assert_eq!(verified, Ok(()));
+
+            // and was never shown a card they still couldn't see once the game was over
+            let known: Vec<(davincicode::Color, u32)> = end
+                .iter()
+                .flat_map(|player| {
+                    player.deck.iter().filter(move |card| {
+                        player.name == name || card.status == davincicode::CardStatus::REVEALED
+                    })
+                })
+                .map(|card| (card.color, card.value))
+                .collect();
+            let during_the_game = received.split("won the game.").next().unwrap();
+            for card in leak::cards_in(during_the_game) {
+                assert!(
+                    known.contains(&(card.color, card.value)),
+                    "{} was sent {:?}",
+                    name,
+                    card
+                );
+            }
         }
     }
 }
@@ -0,0 +1,440 @@
+//! The connection of a player, run by a reader task and a writer task.
+//!
+//! The reader keeps reading even when the player isn't being asked anything, so chat
+//! goes through at any time. Whatever else the player sends is an answer, kept until the
+//! game asks for it, but for hint requests, answered right away with the hint the game
+//! left for the player. The game never writes to the stream itself: it queues messages for
+//! the writer, and a player whose queue is full is dealt with by the `SlowPolicy`, so
+//! nobody waits on them. Answers the game hasn't asked for yet are bounded too, what
+//! doesn't fit is dropped. Once the cards are dealt, every message is checked for cards
+//! the player can't see as it is queued, and every hint as it is set, and one that gives
+//! any away is never sent.
+
+use crate::chat::{self, Chat};
+use crate::hint;
+use crate::leak;
+use crate::Game;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+
+/// The last thing a kicked player gets.
+pub const KICKED_MSG: &str = "\nYou were sent away from the room by the host.\n";
+/// Messages waiting for a player before they count as falling behind.
+pub const OUTGOING_QUEUE: usize = 64;
+/// Answers kept until the game asks for them, a player typing more is only flooding.
+const ANSWERS_QUEUE: usize = 16;
+
+/// What happens to a player whose outgoing queue is full.
+#[derive(Debug, PartialEq, Clone, Copy, clap::ValueEnum)]
+pub enum SlowPolicy {
+    /// Hang up on them, they can take their seat back and catch up
+    DISCONNECT,
+    /// Drop what doesn't fit, their cards are sent again with the next prompt
+    DROP,
+}
+
+#[derive(Debug)]
+pub struct Conn {
+    name: String,
+    chat: Arc<Chat>,
+    policy: SlowPolicy,
+    outgoing: mpsc::Sender<String>,
+    answers: mpsc::Receiver<String>,
+    writer: JoinHandle<()>,
+    reader: JoinHandle<()>,
+    /// The answer to a hint request, if there is a decision to hint about
+    hint: Arc<Mutex<Option<String>>>,
+}
+
+impl Conn {
+    /// Starts the tasks of the connection of `name`, and adds it to the chat. Setting
+    /// `door` hangs up on the player.
+    pub async fn spawn<S>(
+        stream: S,
+        name: &str,
+        chat: Arc<Chat>,
+        door: watch::Receiver<bool>,
+        policy: SlowPolicy,
+    ) -> Conn
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (reader, writer) = tokio::io::split(stream);
+        let (outgoing, outgoing_rx) = mpsc::channel(OUTGOING_QUEUE);
+        let (answers_tx, answers) = mpsc::channel(ANSWERS_QUEUE);
+        let hint = Arc::new(Mutex::new(None));
+
+        let writer = tokio::spawn(write_messages(writer, outgoing_rx, door.clone()));
+        let reader = tokio::spawn(read_messages(
+            reader,
+            name.to_string(),
+            Arc::clone(&chat),
+            answers_tx,
+            Arc::clone(&hint),
+            outgoing.downgrade(),
+            door,
+        ));
+        chat.join(name, outgoing.clone()).await;
+
+        Conn {
+            name: name.to_string(),
+            chat,
+            policy,
+            outgoing,
+            answers,
+            writer,
+            reader,
+            hint,
+        }
+    }
+
+    /// Queues a message for the player, without waiting. Returns false once the connection
+    /// is gone. Nothing is checked, for before the deal and once the game is over.
+    pub fn send(&self, msg: &str) -> bool {
+        match self.outgoing.try_send(msg.to_string()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => match self.policy {
+                SlowPolicy::DROP => true,
+                SlowPolicy::DISCONNECT => {
+                    eprintln!("{} is falling behind, hanging up", self.name);
+                    self.hang_up();
+                    false
+                }
+            },
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+
+    /// Like `send`, unless the message shows the player cards of `game` they can't see
+    /// right now: it is left out then, and debug builds stop there.
+    pub fn send_in(&self, game: &Game, msg: &str) -> bool {
+        if self.shows_hidden(game, msg) {
+            return true;
+        }
+
+        self.send(msg)
+    }
+
+    /// What the player gets when they ask for a hint, until `clear_hint`. Checked like
+    /// `send_in` when it is set, which holds until it is cleared since the player only
+    /// ever sees more of `game` in the meantime.
+    pub fn set_hint(&self, game: &Game, hint: String) {
+        if !self.shows_hidden(game, &hint) {
+            self.store_hint(Some(hint));
+        }
+    }
+
+    /// Back to having no hint for the player.
+    pub fn clear_hint(&self) {
+        self.store_hint(None);
+    }
+
+    fn store_hint(&self, hint: Option<String>) {
+        if let Ok(mut current) = self.hint.lock() {
+            *current = hint;
+        }
+    }
+
+    /// Whether `msg` gives away cards of `game` the player can't see, which debug builds
+    /// stop at.
+    fn shows_hidden(&self, game: &Game, msg: &str) -> bool {
+        let leaked = leak::leaks(game, &self.name, msg);
+        debug_assert!(
+            leaked.is_empty(),
+            "{} was sent hidden cards {:?}: {}",
+            self.name,
+            leaked,
+            msg
+        );
+        if !leaked.is_empty() {
+            eprintln!("Not sending {} hidden cards {:?}", self.name, leaked);
+        }
+        !leaked.is_empty()
+    }
+
+    /// Stops both tasks, which closes the stream. The game sees the player as gone.
+    fn hang_up(&self) {
+        self.writer.abort();
+        self.reader.abort();
+    }
+
+    /// The next answer of the player, None once the connection is gone.
+    pub async fn recv(&mut self) -> Option<String> {
+        self.answers.recv().await
+    }
+
+    /// Drops what the player sent while nobody was asking them anything.
+    pub fn discard_pending(&mut self) {
+        while self.answers.try_recv().is_ok() {}
+    }
+
+    /// Sends what is still queued, then hangs up.
+    pub async fn finish(self) {
+        self.chat.leave(&self.name).await;
+        drop(self.outgoing);
+
+        let _ = self.writer.await;
+        // the stream closes once both halves are gone
+        self.reader.abort();
+    }
+}
+
+async fn write_messages<W>(
+    mut writer: W,
+    mut outgoing: mpsc::Receiver<String>,
+    mut door: watch::Receiver<bool>,
+) where
+    W: AsyncWrite + Unpin,
+{
+    loop {
+        let msg = tokio::select! {
+            msg = outgoing.recv() => msg,
+            Ok(_) = door.wait_for(|kicked| *kicked) => Some(String::from(KICKED_MSG)),
+        };
+        let msg = match msg {
+            Some(msg) => msg,
+            None => return,
+        };
+
+        if let Err(error) = writer.write_all(msg.as_bytes()).await {
+            eprintln!("Error writing response to client: {}", error);
+            return;
+        }
+        if let Err(error) = writer.flush().await {
+            eprintln!("Error flushing response to client {}", error);
+            return;
+        }
+
+        if msg == KICKED_MSG {
+            let _ = writer.shutdown().await;
+            return;
+        }
+    }
+}
+
+async fn read_messages<R>(
+    mut reader: R,
+    name: String,
+    chat: Arc<Chat>,
+    answers: mpsc::Sender<String>,
+    hint: Arc<Mutex<Option<String>>>,
+    // weak, the writer stops once the game is done with the player
+    outgoing: mpsc::WeakSender<String>,
+    mut door: watch::Receiver<bool>,
+) where
+    R: AsyncRead + Unpin,
+{
+    let mut buffer = [0u8; 1024];
+
+    loop {
+        // dropping `answers` tells the game the connection is gone
+        let read = tokio::select! {
+            read = reader.read(&mut buffer) => read,
+            Ok(_) = door.wait_for(|kicked| *kicked) => return,
+        };
+        let bytes_read = match read {
+            Ok(0) | Err(_) => return,
+            Ok(bytes_read) => bytes_read,
+        };
+        let msg = String::from_utf8_lossy(&buffer[..bytes_read]).to_string();
+
+        if hint::is_request(&msg) {
+            let current = match hint.lock() {
+                Ok(current) => current.clone(),
+                Err(_) => None,
+            };
+            let current = current.unwrap_or_else(|| String::from(hint::NO_HINT));
+            // like chat, a hint nobody has room for is dropped
+            if let Some(outgoing) = outgoing.upgrade() {
+                let _ = outgoing.try_send(hint::hint_line(&current));
+            }
+            continue;
+        }
+
+        match chat::parse_say(&msg) {
+            Some(message) => chat.say(&name, message).await,
+            None => match answers.try_send(msg) {
+                Ok(()) | Err(TrySendError::Full(_)) => (),
+                Err(TrySendError::Closed(_)) => return,
+            },
+        }
+    }
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectate::Spectators;
+    use crate::tests::dealt;
+    use tokio::io::duplex;
+
+    fn door() -> watch::Receiver<bool> {
+        // never kicked
+        watch::Sender::new(false).subscribe()
+    }
+
+    #[tokio::test]
+    async fn test_answers_and_chat_are_apart() {
+        let chat = Arc::new(Chat::new(Arc::new(Spectators::default())));
+        let (server, mut client) = duplex(1024);
+        let mut conn = Conn::spawn(
+            server,
+            "alice",
+            Arc::clone(&chat),
+            door(),
+            SlowPolicy::DISCONNECT,
+        )
+        .await;
+
+        client.write_all(b"/say hi").await.unwrap();
+        let mut buffer = [0u8; 1024];
+        let bytes_read = client.read(&mut buffer).await.unwrap();
+        assert_eq!(&buffer[..bytes_read], b"~~alice: hi~~\n");
+
+        client.write_all(b"3").await.unwrap();
+        assert_eq!(conn.recv().await.as_deref(), Some("3"));
+
+        assert!(conn.send("It's your turn"));
+        let bytes_read = client.read(&mut buffer).await.unwrap();
+        assert_eq!(&buffer[..bytes_read], b"It's your turn");
+
+        drop(client);
+        assert_eq!(conn.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_hints_are_answered_at_once() {
+        let chat = Arc::new(Chat::new(Arc::new(Spectators::default())));
+        let (server, mut client) = duplex(1024);
+        let mut conn = Conn::spawn(server, "alice", chat, door(), SlowPolicy::DISCONNECT).await;
+        let mut buffer = [0u8; 1024];
+
+        client.write_all(b"/hint").await.unwrap();
+        let bytes_read = client.read(&mut buffer).await.unwrap();
+        assert_eq!(
+            &buffer[..bytes_read],
+            hint::hint_line(hint::NO_HINT).as_bytes()
+        );
+
+        conn.set_hint(&dealt(), String::from("bob's card 1 can only be 5"));
+        client.write_all(b"/hint").await.unwrap();
+        let bytes_read = client.read(&mut buffer).await.unwrap();
+        assert_eq!(&buffer[..bytes_read], b"^^bob's card 1 can only be 5^^\n");
+
+        // never an answer
+        client.write_all(b"1").await.unwrap();
+        assert_eq!(conn.recv().await.as_deref(), Some("1"));
+    }
+
+    #[tokio::test]
+    async fn test_discard_pending() {
+        let chat = Arc::new(Chat::new(Arc::new(Spectators::default())));
+        let (server, mut client) = duplex(1024);
+        let mut conn = Conn::spawn(server, "alice", chat, door(), SlowPolicy::DISCONNECT).await;
+
+        client.write_all(b"out of turn").await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        conn.discard_pending();
+
+        client.write_all(b"2").await.unwrap();
+        assert_eq!(conn.recv().await.as_deref(), Some("2"));
+    }
+
+    #[tokio::test]
+    async fn test_flooding_is_dropped() {
+        let chat = Arc::new(Chat::new(Arc::new(Spectators::default())));
+        let (server, mut client) = duplex(1024);
+        let mut conn = Conn::spawn(server, "alice", chat, door(), SlowPolicy::DISCONNECT).await;
+
+        for idx in 0..ANSWERS_QUEUE * 2 {
+            client.write_all(idx.to_string().as_bytes()).await.unwrap();
+            // one answer per read
+            tokio::time::sleep(tokio::time::Duration::from_millis(2)).await;
+        }
+
+        let mut kept = Vec::new();
+        while let Ok(answer) = conn.answers.try_recv() {
+            kept.push(answer);
+        }
+        assert_eq!(kept.len(), ANSWERS_QUEUE);
+        assert_eq!(kept[0], "0");
+
+        // still connected
+        client.write_all(b"2").await.unwrap();
+        assert_eq!(conn.recv().await.as_deref(), Some("2"));
+    }
+
+    #[tokio::test]
+    async fn test_own_cards_are_sent() {
+        let chat = Arc::new(Chat::new(Arc::new(Spectators::default())));
+        let (server, mut client) = duplex(1024);
+        let conn = Conn::spawn(server, "alice", chat, door(), SlowPolicy::DISCONNECT).await;
+        let game = dealt();
+
+        let hand = game.players[0].show_hand(false, false);
+        assert!(conn.send_in(&game, &hand));
+        let mut buffer = [0u8; 1024];
+        let bytes_read = client.read(&mut buffer).await.unwrap();
+        assert_eq!(&buffer[..bytes_read], hand.as_bytes());
+    }
+
+    #[cfg(debug_assertions)]
+    #[tokio::test]
+    #[should_panic(expected = "alice was sent hidden cards")]
+    async fn test_hidden_cards_are_never_sent() {
+        let chat = Arc::new(Chat::new(Arc::new(Spectators::default())));
+        let (server, _client) = duplex(1024);
+        let conn = Conn::spawn(server, "alice", chat, door(), SlowPolicy::DISCONNECT).await;
+        let game = dealt();
+
+        conn.send_in(&game, &game.players[1].show_hand(false, false));
+    }
+
+    #[cfg(debug_assertions)]
+    #[tokio::test]
+    #[should_panic(expected = "alice was sent hidden cards")]
+    async fn test_hidden_cards_are_never_hinted() {
+        let chat = Arc::new(Chat::new(Arc::new(Spectators::default())));
+        let (server, _client) = duplex(1024);
+        let conn = Conn::spawn(server, "alice", chat, door(), SlowPolicy::DISCONNECT).await;
+        let game = dealt();
+
+        conn.set_hint(&game, game.players[1].show_hand(false, false));
+    }
+
+    #[tokio::test]
+    async fn test_door_hangs_up() {
+        let chat = Arc::new(Chat::new(Arc::new(Spectators::default())));
+        let (door, rx) = watch::channel(false);
+        let (server, mut client) = duplex(1024);
+        let mut conn = Conn::spawn(server, "alice", chat, rx, SlowPolicy::DISCONNECT).await;
+
+        door.send_replace(true);
+        assert_eq!(conn.recv().await, None);
+
+        let mut kicked = String::new();
+        client.read_to_string(&mut kicked).await.unwrap();
+        assert_eq!(kicked, KICKED_MSG);
+    }
+
+    #[tokio::test]
+    async fn test_slow_client_policies() {
+        let chat = Arc::new(Chat::new(Arc::new(Spectators::default())));
+        // the client never reads, so the writer gets stuck on a full pipe
+        let (server, _client) = duplex(16);
+        let mut conn =
+            Conn::spawn(server, "slow", Arc::clone(&chat), door(), SlowPolicy::DROP).await;
+        assert!((0..OUTGOING_QUEUE * 2).all(|_| conn.send("It's your turn")));
+
+        let (server, _client) = duplex(16);
+        conn = Conn::spawn(server, "slow", chat, door(), SlowPolicy::DISCONNECT).await;
+        assert!(!(0..OUTGOING_QUEUE * 2).all(|_| conn.send("It's your turn")));
+        assert_eq!(conn.recv().await, None);
+    }
+}
@@ -0,0 +1,437 @@
+//! Lets players check that the server didn't stack the pile.
+//!
+//! Every shuffle of a game comes from one seed. The server sends its SHA-256 before the
+//! first turn, `%%commit:<hex>%%`, and the seed itself once the game is over, along with the
+//! log of the deal and of every shuffle and draw: `%%reveal:<seed>:<set>:<seat>:<log>%%`,
+//! the log being like `h0.4,h1.4,s,d0.7,b1.2`: hands dealt, shuffles, draws by seat and
+//! draws a bot made for a seat. A client then shuffles the pile again from the seed, replays
+//! the log and checks that its own draws are where it picked them, and that it ends up with
+//! the cards it was really dealt and drew.
+
+use crate::{leak, prompt, Card, Game};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::sync::LazyLock;
+
+static COMMIT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"%%commit:([0-9a-f]{64})%%").unwrap());
+static REVEAL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"%%reveal:([0-9a-f]{64}):(\d+):(\d+):([^%]*)%%").unwrap());
+static ESCAPES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\x1b\[[0-9;]*m").unwrap());
+
+/// What happened to the pile, in order.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PileEvent {
+    /// The seat got that many cards from the top of the pile
+    DEAL(usize, usize),
+    SHUFFLE,
+    /// The seat drew the card at that position
+    DRAW(usize, usize),
+    /// A bot playing the seat drew the card at that position
+    BOT(usize, usize),
+}
+
+impl fmt::Display for PileEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PileEvent::DEAL(seat, ncards) => write!(f, "h{}.{}", seat, ncards),
+            PileEvent::SHUFFLE => write!(f, "s"),
+            PileEvent::DRAW(seat, card_number) => write!(f, "d{}.{}", seat, card_number),
+            PileEvent::BOT(seat, card_number) => write!(f, "b{}.{}", seat, card_number),
+        }
+    }
+}
+
+impl PileEvent {
+    fn parse(event: &str) -> Option<PileEvent> {
+        if event == "s" {
+            return Some(PileEvent::SHUFFLE);
+        }
+
+        let (seat, n) = event.get(1..)?.split_once('.')?;
+        let (seat, n) = (seat.parse().ok()?, n.parse().ok()?);
+
+        match event.get(..1)? {
+            "h" => Some(PileEvent::DEAL(seat, n)),
+            "d" => Some(PileEvent::DRAW(seat, n)),
+            "b" => Some(PileEvent::BOT(seat, n)),
+            _ => None,
+        }
+    }
+}
+
+/// Why a game couldn't be verified.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Mismatch {
+    /// The seed isn't the one committed to
+    COMMITMENT,
+    /// The log can't be played on the pile
+    LOG,
+    /// The log has the player draw elsewhere than they picked
+    DRAWS,
+    /// The player held cards the replay didn't give them
+    HAND,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::COMMITMENT => write!(f, "the seed doesn't match the commitment"),
+            Mismatch::LOG => write!(f, "the draws don't fit the pile"),
+            Mismatch::DRAWS => write!(f, "your draws aren't where you picked them"),
+            Mismatch::HAND => write!(f, "your cards don't match the shuffles"),
+        }
+    }
+}
+
+/// What a player needs to replay the pile of a game.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Reveal {
+    pub seed: [u8; 32],
+    pub set_cards: u32,
+    pub my_seat: usize,
+    pub log: Vec<PileEvent>,
+}
+
+impl Reveal {
+    /// The reveal of `game` for the player `me`, None if they weren't dealt in.
+    pub fn of(game: &Game, me: &str) -> Option<Reveal> {
+        Some(Reveal {
+            seed: game.seed,
+            set_cards: game.set_cards,
+            my_seat: game.seats.iter().position(|seat| seat == me)?,
+            log: game.pile_log.clone(),
+        })
+    }
+
+    pub fn to_marker(&self) -> String {
+        let log: Vec<String> = self.log.iter().map(|event| event.to_string()).collect();
+
+        format!(
+            "%%reveal:{}:{}:{}:{}%%",
+            hex(&self.seed),
+            self.set_cards,
+            self.my_seat,
+            log.join(",")
+        )
+    }
+
+    /// The last reveal in a response.
+    pub fn parse(response: &str) -> Option<Reveal> {
+        let caps = REVEAL.captures_iter(response).last()?;
+
+        let mut seed = [0u8; 32];
+        for (idx, byte) in seed.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&caps[1][idx * 2..idx * 2 + 2], 16).ok()?;
+        }
+        let log = match &caps[4] {
+            "" => Vec::new(),
+            log => log
+                .split(',')
+                .map(PileEvent::parse)
+                .collect::<Option<Vec<PileEvent>>>()?,
+        };
+
+        Some(Reveal {
+            seed,
+            set_cards: caps[2].parse().ok()?,
+            my_seat: caps[3].parse().ok()?,
+            log,
+        })
+    }
+}
+
+/// The commitment to a seed, in lowercase hex.
+pub fn commitment(seed: &[u8; 32]) -> String {
+    hex(&Sha256::digest(seed))
+}
+
+pub fn commit_marker(game: &Game) -> String {
+    format!("%%commit:{}%%", commitment(&game.seed))
+}
+
+/// The commitment in a response, if any.
+pub fn parse_commit(response: &str) -> Option<String> {
+    COMMIT.captures(response).map(|caps| caps[1].to_string())
+}
+
+/// The cards each seat got from the pile, following the log from the seed.
+pub fn replay(reveal: &Reveal) -> Result<Vec<Vec<Card>>, Mismatch> {
+    // shuffled from the seed in the same way, nobody to deal to yet
+    let mut game = Game::with_seed(reveal.set_cards, reveal.seed);
+    game.init_set();
+
+    let mut hands: Vec<Vec<Card>> = Vec::new();
+    for event in reveal.log.iter() {
+        let (seat, cards) = match *event {
+            PileEvent::SHUFFLE => {
+                game.shuffle_avail_card();
+                continue;
+            }
+            PileEvent::DEAL(seat, ncards) if ncards <= game.card_avail.len() => {
+                (seat, game.card_avail.drain(..ncards).collect())
+            }
+            PileEvent::DRAW(seat, card_number) | PileEvent::BOT(seat, card_number)
+                if card_number < game.card_avail.len() =>
+            {
+                (seat, vec![game.card_avail.remove(card_number)])
+            }
+            _ => return Err(Mismatch::LOG),
+        };
+
+        if hands.len() <= seat {
+            hands.resize(seat + 1, Vec::new());
+        }
+        hands[seat].extend(cards);
+    }
+
+    Ok(hands)
+}
+
+/// Checks the seed against the commitment, that the log has the player draw at `my_picks`
+/// and nowhere else, and that `my_cards` all came out of the pile the way the log says.
+pub fn verify(
+    commit: &str,
+    reveal: &Reveal,
+    my_cards: &[Card],
+    my_picks: &[usize],
+) -> Result<(), Mismatch> {
+    if commitment(&reveal.seed) != commit {
+        return Err(Mismatch::COMMITMENT);
+    }
+
+    let hands = replay(reveal)?;
+    let my_draws: Vec<usize> = reveal
+        .log
+        .iter()
+        .filter_map(|event| match *event {
+            PileEvent::DRAW(seat, card_number) if seat == reveal.my_seat => Some(card_number),
+            _ => None,
+        })
+        .collect();
+    if my_draws != my_picks {
+        return Err(Mismatch::DRAWS);
+    }
+
+    let my_hand = hands.get(reveal.my_seat).ok_or(Mismatch::HAND)?;
+    let dealt = |card: &Card| {
+        my_hand
+            .iter()
+            .any(|other| other.color == card.color && other.value == card.value)
+    };
+
+    match !my_hand.is_empty() && my_cards.iter().all(dealt) {
+        true => Ok(()),
+        false => Err(Mismatch::HAND),
+    }
+}
+
+/// What to tell the player about the shuffles of their game.
+pub fn verdict(
+    commit: Option<&str>,
+    reveal: &Reveal,
+    my_cards: &[Card],
+    my_picks: &[usize],
+) -> String {
+    match verify(commit.unwrap_or_default(), reveal, my_cards, my_picks) {
+        Ok(()) => String::from("Shuffle verified: the pile was dealt from the committed seed."),
+        Err(mismatch) => format!("Shuffle mismatch: {}!", mismatch),
+    }
+}
+
+/// Where a player picked the cards they drew, from the questions they answered.
+#[derive(Debug, Default, Clone)]
+pub struct Picks {
+    picked: Vec<usize>,
+    /// Whether the last question was which card to draw
+    drawing: bool,
+    /// The answer to it, taken once the server asks something else
+    answer: Option<usize>,
+}
+
+impl Picks {
+    /// The server asks `question`. The draw question again means the last answer didn't
+    /// fit, or never got there.
+    pub fn asked(&mut self, question: &str) {
+        let drawing = question.contains(prompt::DRAW_QUESTION);
+        if let Some(answer) = self.answer.take() {
+            if !drawing {
+                self.picked.push(answer);
+            }
+        }
+        self.drawing = drawing;
+    }
+
+    /// The player answers the last question.
+    pub fn answered(&mut self, answer: &str) {
+        if self.drawing {
+            self.answer = answer.trim().parse().ok();
+        }
+    }
+
+    /// The positions picked, in order, the last answer counting once the game is over.
+    pub fn picked(&self) -> Vec<usize> {
+        self.picked.iter().copied().chain(self.answer).collect()
+    }
+}
+
+/// The cards of the last "Your deck" line of a response, as the CLI server sends it.
+pub fn own_cards(response: &str) -> Option<Vec<Card>> {
+    let response = ESCAPES.replace_all(response, "");
+
+    response.lines().rev().find_map(|line| {
+        ["Your deck", "Your new deck", "Your current deck"]
+            .iter()
+            .find_map(|label| line.find(label).map(|start| &line[start + label.len()..]))
+            .map(leak::cards_in)
+    })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::dealt;
+
+    fn cards_of(game: &Game, name: &str) -> Vec<Card> {
+        let player = game.players.iter().find(|p| p.name == name).unwrap();
+        player
+            .deck
+            .iter()
+            .chain(player.side_card.iter())
+            .copied()
+            .collect()
+    }
+
+    #[test]
+    fn test_reveal_round_trip() {
+        let mut game = dealt();
+        game.shuffle_avail_card();
+        game.draw("bob", 5);
+
+        let reveal = Reveal::of(&game, "bob").unwrap();
+        let marker = format!("bob won{}\n", reveal.to_marker());
+        assert_eq!(Reveal::parse(&marker), Some(reveal));
+        assert!(Reveal::of(&game, "carol").is_none());
+
+        let commit = parse_commit(&format!("{}\n", commit_marker(&game)));
+        assert_eq!(commit, Some(commitment(&game.seed)));
+    }
+
+    #[test]
+    fn test_verify() {
+        let mut game = dealt();
+        let commit = commitment(&game.seed);
+        for _ in 0..3 {
+            game.shuffle_avail_card();
+            game.draw("alice", 2);
+            game.players[0].save_side_card(true);
+            game.shuffle_avail_card();
+            game.draw("bob", 0);
+        }
+
+        let reveal = Reveal::of(&game, "bob").unwrap();
+        let bob = cards_of(&game, "bob");
+        assert_eq!(verify(&commit, &reveal, &bob, &[0, 0, 0]), Ok(()));
+        let alice = Reveal::of(&game, "alice").unwrap();
+        assert_eq!(
+            verify(&commit, &alice, &cards_of(&game, "alice"), &[2, 2, 2]),
+            Ok(())
+        );
+
+        // a card bob never drew
+        assert_eq!(
+            verify(&commit, &reveal, &cards_of(&game, "alice"), &[0, 0, 0]),
+            Err(Mismatch::HAND)
+        );
+        let mut stacked = reveal.clone();
+        stacked.seed[0] ^= 1;
+        assert_eq!(
+            verify(&commit, &stacked, &bob, &[0, 0, 0]),
+            Err(Mismatch::COMMITMENT)
+        );
+        let mut stacked = reveal.clone();
+        stacked.log.push(PileEvent::DRAW(1, 99));
+        assert_eq!(
+            verify(&commit, &stacked, &bob, &[0, 0, 0]),
+            Err(Mismatch::LOG)
+        );
+    }
+
+    #[test]
+    fn test_draws_are_where_they_were_picked() {
+        let mut game = dealt();
+        let commit = commitment(&game.seed);
+        game.shuffle_avail_card();
+        game.draw("alice", 3);
+        game.players[0].save_side_card(true);
+        game.bot_draw("bob", 1);
+        game.players[1].save_side_card(true);
+        let alice = cards_of(&game, "alice");
+
+        let reveal = Reveal::of(&game, "alice").unwrap();
+        assert_eq!(verify(&commit, &reveal, &alice, &[3]), Ok(()));
+        // what the bot drew for bob isn't his to check
+        let bob = Reveal::of(&game, "bob").unwrap();
+        assert_eq!(verify(&commit, &bob, &cards_of(&game, "bob"), &[]), Ok(()));
+
+        // alice gets the card she picked from another place in the pile
+        let mut stacked = reveal.clone();
+        let at = stacked
+            .log
+            .iter()
+            .position(|event| *event == PileEvent::DRAW(0, 3))
+            .unwrap();
+        stacked.log[at] = PileEvent::DRAW(0, 5);
+        assert_eq!(
+            verify(&commit, &stacked, &alice, &[3]),
+            Err(Mismatch::DRAWS)
+        );
+        // or a bot drew it for her
+        stacked.log[at] = PileEvent::BOT(0, 3);
+        assert_eq!(
+            verify(&commit, &stacked, &alice, &[3]),
+            Err(Mismatch::DRAWS)
+        );
+    }
+
+    #[test]
+    fn test_picks() {
+        let mut picks = Picks::default();
+        picks.asked("Keep it hidden? (yes/no)");
+        picks.answered("yes");
+        picks.asked("Enter card number to draw it (0-15)");
+        picks.answered("99");
+        // asked again: 99 didn't fit
+        picks.asked("Enter card number to draw it (0-15)");
+        picks.answered("4");
+        picks.asked("Which card would you like to guess (0-3)");
+        picks.answered("2");
+        assert_eq!(picks.picked(), vec![4]);
+
+        // the game ended before the next question
+        picks.asked("Enter card number to draw it (0-14)");
+        picks.answered("0");
+        assert_eq!(picks.picked(), vec![4, 0]);
+    }
+
+    #[test]
+    fn test_own_cards() {
+        let game = dealt();
+        let response = format!(
+            "Your deck: {}What your opponents see: {}",
+            game.players[0].show_hand(false, true),
+            game.players[0].show_hand(true, true)
+        );
+
+        assert_eq!(own_cards(&response).unwrap().len(), 4);
+        assert_eq!(own_cards("It's your turn"), None);
+    }
+}
@@ -0,0 +1,211 @@
+//! Hints for beginners: which opponent cards the sort order gives away.
+//!
+//! A player sends `/hint` at any time; it is never taken as the answer to a prompt. While
+//! they have a guess to make, the server answers with their likeliest guesses and why,
+//! worked out from what they can see (see `bot::candidates`), as `^^<hint>^^`, which
+//! clients show apart from the game. Ranked rooms give no hints.
+
+use crate::bot;
+use crate::{card_cmp, card_name, Card, CardStatus, Game, Player};
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// What a player types (and the client sends as is) to get a hint.
+pub const HINT_COMMAND: &str = "/hint";
+pub const NO_HINT: &str = "No hint now, ask when you have a guess to make.";
+pub const RANKED: &str = "No hints in a ranked room.";
+/// Guesses in a hint, likeliest first
+const SHOWN: usize = 3;
+
+static HINT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\^\^([^^]*)\^\^\n?").unwrap());
+
+pub fn is_request(msg: &str) -> bool {
+    msg.trim() == HINT_COMMAND
+}
+
+pub fn hint_line(hint: &str) -> String {
+    format!("^^{}^^\n", hint)
+}
+
+/// Splits what the server sent into the hints and the rest.
+pub fn split_hints(response: &str) -> (Vec<String>, String) {
+    let hints = HINT
+        .captures_iter(response)
+        .map(|caps| caps[1].to_string())
+        .collect();
+
+    (hints, HINT.replace_all(response, "").to_string())
+}
+
+/// The likeliest guesses of `me`, and why.
+pub fn advice(game: &Game, me: &str) -> String {
+    let mut guesses = Vec::new();
+    for opponent in game.players.iter().filter(|player| player.name != me) {
+        for (card_idx, card) in opponent.deck.iter().enumerate() {
+            if card.status == CardStatus::HIDDEN {
+                let values = bot::candidates(game, me, opponent, card_idx);
+                if !values.is_empty() {
+                    guesses.push((opponent, card_idx, values));
+                }
+            }
+        }
+    }
+    if guesses.is_empty() {
+        return String::from("Nothing left to guess.");
+    }
+    guesses.sort_by_key(|(_, _, values)| values.len());
+
+    let mut text = String::from("Likeliest guesses:");
+    for (opponent, card_idx, values) in guesses.iter().take(SHOWN) {
+        text.push_str(&format!(
+            "\n{}",
+            reason(game, me, opponent, *card_idx, values)
+        ));
+    }
+
+    let drew = game
+        .players
+        .iter()
+        .any(|player| player.name == me && player.side_card.is_some());
+    if drew && guesses[0].2.len() > 1 {
+        text.push_str("\nA wrong guess reveals the card you drew.");
+    }
+
+    text
+}
+
+/// "bob's card 2 can only be 4: between their B3 and W5, and you can see B4".
+fn reason(game: &Game, me: &str, opponent: &Player, card_idx: usize, values: &[u32]) -> String {
+    let card = opponent.deck[card_idx];
+    let revealed = |idx: &usize| opponent.deck[*idx].status == CardStatus::REVEALED;
+    let before = (0..card_idx)
+        .rev()
+        .find(revealed)
+        .map(|idx| opponent.deck[idx]);
+    let after = (card_idx + 1..opponent.deck.len())
+        .find(revealed)
+        .map(|idx| opponent.deck[idx]);
+
+    let mut text = match values {
+        [value] => format!(
+            "{}'s card {} can only be {}",
+            opponent.name, card_idx, value
+        ),
+        _ => {
+            let values: Vec<String> = values.iter().map(u32::to_string).collect();
+            format!(
+                "{}'s card {} is {} (1 in {})",
+                opponent.name,
+                card_idx,
+                values.join(", "),
+                values.len()
+            )
+        }
+    };
+    match (before, after) {
+        (Some(before), Some(after)) => text.push_str(&format!(
+            ": between their {} and {}",
+            card_name(&before),
+            card_name(&after)
+        )),
+        (Some(before), None) => text.push_str(&format!(": after their {}", card_name(&before))),
+        (None, Some(after)) => text.push_str(&format!(": before their {}", card_name(&after))),
+        (None, None) => text.push_str(": any value not in sight"),
+    }
+
+    // the cards of that color in range that rule values out
+    let in_range = |other: &Card| {
+        other.color == card.color
+            && before.is_none_or(|before| other.value >= before.value)
+            && after.is_none_or(|after| other.value <= after.value)
+    };
+    let mut seen: Vec<Card> = Vec::new();
+    for player in game.players.iter().chain(game.lost_players.iter()) {
+        for (idx, other) in player
+            .deck
+            .iter()
+            .chain(player.side_card.iter())
+            .enumerate()
+        {
+            let own = player.name == me;
+            let bound = player.name == opponent.name
+                && (before.is_some_and(|before| before == *other)
+                    || after.is_some_and(|after| after == *other));
+            if (own || other.status == CardStatus::REVEALED)
+                && in_range(other)
+                && !bound
+                && !(player.name == opponent.name && idx == card_idx)
+            {
+                seen.push(*other);
+            }
+        }
+    }
+    if !seen.is_empty() {
+        seen.sort_by(card_cmp);
+        let seen: Vec<String> = seen.iter().map(card_name).collect();
+        text.push_str(&format!(", and you can see {}", seen.join(" ")));
+    }
+    let ruled_out: Vec<String> = opponent
+        .ruled_out(card_idx)
+        .iter()
+        .map(u32::to_string)
+        .collect();
+    if !ruled_out.is_empty() {
+        text.push_str(&format!(", and it isn't {}", ruled_out.join(" ")));
+    }
+
+    text
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::card;
+    use crate::Color;
+
+    fn game() -> Game {
+        let mut game = Game::new(24);
+
+        let mut me = Player::new(String::from("me"), 2);
+        me.deck = vec![
+            card(4, Color::BLACK, CardStatus::HIDDEN),
+            card(9, Color::WHITE, CardStatus::HIDDEN),
+        ];
+        let mut bob = Player::new(String::from("bob"), 4);
+        bob.deck = vec![
+            card(3, Color::BLACK, CardStatus::REVEALED),
+            card(5, Color::BLACK, CardStatus::HIDDEN),
+            card(5, Color::WHITE, CardStatus::REVEALED),
+            card(10, Color::BLACK, CardStatus::HIDDEN),
+        ];
+
+        game.players = vec![me, bob];
+        game
+    }
+
+    #[test]
+    fn test_advice() {
+        let advice = advice(&game(), "me");
+
+        assert_eq!(
+            advice,
+            "Likeliest guesses:\n\
+             bob's card 1 can only be 5: between their B3 and W5, and you can see B4\n\
+             bob's card 3 is 6, 7, 8, 9, 10, 11 (1 in 6): after their W5"
+        );
+    }
+
+    #[test]
+    fn test_hints_stand_apart() {
+        assert!(is_request(" /hint\n"));
+        assert!(!is_request("/hints"));
+
+        let response = format!("It's your turn{}Pick a card", hint_line("a\nb"));
+        let (hints, rest) = split_hints(&response);
+        assert_eq!(hints, vec!["a\nb"]);
+        assert_eq!(rest, "It's your turnPick a card");
+    }
+}
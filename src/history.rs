@@ -0,0 +1,79 @@
+//! What happened in a game, move by move.
+//!
+//! The `Game` keeps one event per move of a player, from the deal to the last guess, so a
+//! finished game can be archived, written down and played again.
+
+use crate::{card_name, Card};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum GameEvent {
+    /// The player was dealt these cards
+    DEAL { player: String, cards: Vec<Card> },
+    /// The player drew this card from the pile
+    DRAW { player: String, card: Card },
+    /// The player guessed that the card `card_idx` of `opponent` is `value`
+    GUESS {
+        player: String,
+        opponent: String,
+        card_idx: usize,
+        value: u32,
+        right: bool,
+    },
+    /// The player put the card they drew in their deck, hidden or revealed
+    SAVE { player: String, hidden: bool },
+    /// The player gave up, or was made to, revealing all their cards
+    FORFEIT { player: String },
+}
+
+impl GameEvent {
+    /// Who made the move.
+    pub fn player(&self) -> &str {
+        match self {
+            GameEvent::DEAL { player, .. }
+            | GameEvent::DRAW { player, .. }
+            | GameEvent::GUESS { player, .. }
+            | GameEvent::SAVE { player, .. }
+            | GameEvent::FORFEIT { player } => player,
+        }
+    }
+}
+
+impl fmt::Display for GameEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameEvent::DEAL { player, cards } => {
+                let cards: Vec<String> = cards.iter().map(card_name).collect();
+                write!(f, "{} was dealt {}", player, cards.join(", "))
+            }
+            GameEvent::DRAW { player, card } => write!(f, "{} drew {}", player, card_name(card)),
+            GameEvent::GUESS {
+                player,
+                opponent,
+                card_idx,
+                value,
+                right,
+            } => write!(
+                f,
+                "{} guessed {}'s card {} is {}: {}",
+                player,
+                opponent,
+                card_idx,
+                value,
+                if *right { "right" } else { "wrong" }
+            ),
+            GameEvent::SAVE {
+                player,
+                hidden: true,
+            } => write!(f, "{} kept their card hidden", player),
+            GameEvent::SAVE {
+                player,
+                hidden: false,
+            } => write!(f, "{} revealed their drawn card", player),
+            GameEvent::FORFEIT { player } => {
+                write!(f, "{} gave up, all their cards revealed", player)
+            }
+        }
+    }
+}
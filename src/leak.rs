@@ -0,0 +1,101 @@
+//! Checks that what a player is sent doesn't give away cards they can't see.
+//!
+//! A player may know the value of their own cards (the one they just drew included) and of
+//! every revealed card. Any other card of the game written out in full (`B5`, `W11`) in a
+//! message to them is a leak. Every message to a player is checked as it is sent (see
+//! `Conn::send_in`), and the tests run it over whole games.
+
+use crate::{Card, CardStatus, Color, Game};
+use regex::Regex;
+use std::sync::LazyLock;
+
+// built once, every message to a player goes through them
+static ESCAPES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\x1b\[[0-9;]*m").unwrap());
+static CARD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b([BW])(\d+)\b").unwrap());
+
+/// Every card whose value `me` may know.
+pub fn known_cards(game: &Game, me: &str) -> Vec<Card> {
+    let mut known = Vec::new();
+
+    for player in game.players.iter().chain(game.lost_players.iter()) {
+        let own = player.name == me;
+
+        for card in player.deck.iter().chain(player.side_card.iter()) {
+            if own || card.status == CardStatus::REVEALED {
+                known.push(*card);
+            }
+        }
+    }
+
+    known
+}
+
+/// The cards written out in `msg`, colors (terminal escapes) left out.
+pub fn cards_in(msg: &str) -> Vec<Card> {
+    CARD.captures_iter(&ESCAPES.replace_all(msg, ""))
+        .filter_map(|caps| {
+            let color = match &caps[1] {
+                "B" => Color::BLACK,
+                _ => Color::WHITE,
+            };
+            let value = caps[2].parse::<u32>().ok()?;
+
+            Some(Card::new(value, color))
+        })
+        .collect()
+}
+
+/// The cards of `game` that `msg` shows to `me` while they shouldn't know them.
+pub fn leaks(game: &Game, me: &str, msg: &str) -> Vec<Card> {
+    let known = known_cards(game, me);
+    let same = |a: &Card, b: &Card| a.color == b.color && a.value == b.value;
+
+    cards_in(msg)
+        .into_iter()
+        .filter(|card| game_cards(game).any(|other| same(card, other)))
+        .filter(|card| !known.iter().any(|other| same(card, other)))
+        .collect()
+}
+
+/// Every card of the game, wherever it is.
+fn game_cards(game: &Game) -> impl Iterator<Item = &Card> {
+    game.players
+        .iter()
+        .chain(game.lost_players.iter())
+        .flat_map(|player| player.deck.iter().chain(player.side_card.iter()))
+        .chain(game.card_avail.iter())
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::dealt;
+    use colored::Colorize;
+
+    #[test]
+    fn test_cards_in() {
+        assert_eq!(
+            cards_in("0: B3, 1: W?, 2: W11"),
+            vec![Card::new(3, Color::BLACK), Card::new(11, Color::WHITE)]
+        );
+        assert_eq!(cards_in(&format!("{}{}", "B".blue(), "7".blue())).len(), 1);
+        assert!(cards_in("Bob picked card 3").is_empty());
+    }
+
+    #[test]
+    fn test_own_and_opponent_hands() {
+        let mut game = dealt();
+
+        let alice_view = game.players[0].show_hand(false, false);
+        assert!(leaks(&game, "alice", &alice_view).is_empty());
+        assert_eq!(leaks(&game, "bob", &alice_view).len(), 4);
+        assert!(leaks(&game, "bob", &game.players[0].show_hand(true, true)).is_empty());
+
+        game.players[0].reveal_card(1);
+        assert_eq!(leaks(&game, "bob", &alice_view).len(), 3);
+        assert!(leaks(&game, "bob", &game.show_avail_cards(false, false)).len() > 1);
+    }
+}
@@ -1,7 +1,36 @@
 use colored::Colorize;
-use rand::{seq::SliceRandom, Rng};
+use fairness::PileEvent;
+use history::GameEvent;
+use rand::rngs::StdRng;
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 
+pub mod access;
+pub mod analysis;
+pub mod archive;
+pub mod bot;
+pub mod chat;
+pub mod conn;
+pub mod fairness;
+pub mod hint;
+pub mod history;
+pub mod leak;
+pub mod local;
+pub mod logs;
+pub mod notation;
+pub mod notes;
+pub mod opponents;
+pub mod prompt;
+pub mod puzzle;
+pub mod replay;
+pub mod room;
+pub mod session;
+pub mod spectate;
+pub mod tls;
+pub mod transport;
+pub mod web;
+
 #[derive(Debug)]
 pub struct Game {
     pub state: GameState,
@@ -12,6 +41,15 @@ pub struct Game {
     pub set_cards: u32,
     pub logs: String,
     pub err: String,
+    /// Every shuffle of the pile comes from it, see `fairness`
+    pub seed: [u8; 32],
+    rng: StdRng,
+    /// The names of the players when the cards were dealt
+    pub seats: Vec<String>,
+    /// The deal, then every shuffle and draw of the pile
+    pub pile_log: Vec<PileEvent>,
+    /// Every move of the players, from the deal on
+    pub history: Vec<GameEvent>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -21,9 +59,14 @@ pub struct Player {
     pub deck: Vec<Card>,
     pub status: PlayerStatus,
     pub side_card: Option<Card>,
+    /// The values wrong guesses ruled out for each hidden card, known to everybody. Kept
+    /// by card rather than by position, which changes as cards get added.
+    pub ruled_out: Vec<(Card, Vec<u32>)>,
+    /// Where each card saved after the deal went in the deck, as the table saw it
+    pub inserted: Vec<usize>,
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub struct Card {
     pub color: Color,
     pub value: u32,
@@ -38,12 +81,12 @@ pub enum PlayerStatus {
     WON,
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum Color {
     BLACK,
     WHITE,
 }
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum CardStatus {
     HIDDEN,
     REVEALED,
@@ -61,6 +104,10 @@ pub enum GameState {
 ///
 impl Game {
     pub fn new(set_number: u32) -> Game {
+        Game::with_seed(set_number, rand::rng().random())
+    }
+
+    pub fn with_seed(set_number: u32, seed: [u8; 32]) -> Game {
         let empty_players: Vec<Player> = Vec::new();
         let empty_cards: Vec<Card> = Vec::new();
 
@@ -73,6 +120,11 @@ impl Game {
             set_cards: set_number,
             logs: String::from(""),
             err: String::from(""),
+            seed,
+            rng: StdRng::from_seed(seed),
+            seats: Vec::new(),
+            pile_log: Vec::new(),
+            history: Vec::new(),
         }
     }
 
@@ -106,17 +158,115 @@ impl Game {
         }
 
         // shuffle the deck here
-        self.shuffle_avail_card();
-
-        // init the game for the players internally
-        for player in self.players.iter_mut() {
-            player.init_game(&mut self.card_avail);
+        self.card_avail.shuffle(&mut self.rng);
+
+        // init the game for the players internally, each from the top of the pile so the
+        // deal can be replayed from the seed
+        for (seat, player) in self.players.iter_mut().enumerate() {
+            let ncards = (player.ncards as usize).min(self.card_avail.len());
+            let mut hand: Vec<Card> = self.card_avail.drain(..ncards).collect();
+            player.init_game(&mut hand);
+            self.pile_log.push(PileEvent::DEAL(seat, ncards));
+            self.history.push(GameEvent::DEAL {
+                player: player.name.clone(),
+                cards: player.deck.clone(),
+            });
         }
+        self.seats = players_name;
     }
 
     pub fn shuffle_avail_card(&mut self) {
-        let mut rng = rand::rng();
-        self.card_avail.shuffle(&mut rng);
+        // fewer than two cards take nothing from the rng, no need to replay it
+        if self.card_avail.len() > 1 {
+            self.card_avail.shuffle(&mut self.rng);
+            self.pile_log.push(PileEvent::SHUFFLE);
+        }
+    }
+
+    /// `name` draws the card `card_number` of the pile as their side card.
+    pub fn draw(&mut self, name: &str, card_number: usize) -> Option<Card> {
+        self.draw_logged(name, card_number, PileEvent::DRAW)
+    }
+
+    /// Like `draw`, picked by a bot playing for `name`, which the pile log tells apart.
+    pub fn bot_draw(&mut self, name: &str, card_number: usize) -> Option<Card> {
+        self.draw_logged(name, card_number, PileEvent::BOT)
+    }
+
+    fn draw_logged(
+        &mut self,
+        name: &str,
+        card_number: usize,
+        event: fn(usize, usize) -> PileEvent,
+    ) -> Option<Card> {
+        let player = self.players.iter_mut().find(|player| player.name == name)?;
+
+        let card = player.draw_specific_card(&mut self.card_avail, card_number);
+        if let Some(seat) = self.seats.iter().position(|seat| seat == name) {
+            self.pile_log.push(event(seat, card_number));
+        }
+        self.history.push(GameEvent::DRAW {
+            player: name.to_string(),
+            card,
+        });
+
+        Some(card)
+    }
+
+    /// `name` guesses that the card `card_idx` of `opponent` is `value`, which reveals it
+    /// when they're right. None if there is no such hidden card.
+    pub fn guess(
+        &mut self,
+        name: &str,
+        opponent: &str,
+        card_idx: usize,
+        value: u32,
+    ) -> Option<bool> {
+        let target = self
+            .players
+            .iter_mut()
+            .find(|player| player.name == opponent)?;
+        target.deck.get(card_idx)?;
+
+        let right = target.get_specific_card_value(card_idx)? == value;
+        if right {
+            target.reveal_card(card_idx);
+        } else {
+            target.rule_out(card_idx, value);
+        }
+        self.history.push(GameEvent::GUESS {
+            player: name.to_string(),
+            opponent: opponent.to_string(),
+            card_idx,
+            value,
+            right,
+        });
+
+        Some(right)
+    }
+
+    /// `name` puts the card they drew in their deck, if they drew one.
+    pub fn save_side_card(&mut self, name: &str, hide_it: bool) {
+        let player = match self.players.iter_mut().find(|player| player.name == name) {
+            Some(player) if player.side_card.is_some() => player,
+            _ => return,
+        };
+
+        player.save_side_card(hide_it);
+        self.history.push(GameEvent::SAVE {
+            player: name.to_string(),
+            hidden: hide_it,
+        });
+    }
+
+    /// `name` gives up, see `Player::forfeit`.
+    pub fn forfeit(&mut self, name: &str) {
+        if let Some(player) = self.players.iter_mut().find(|player| player.name == name) {
+            player.forfeit();
+            self.history.push(GameEvent::FORFEIT {
+                player: name.to_string(),
+            });
+        }
     }
 
     pub fn show_avail_cards(&self, hide_values: bool, colorize: bool) -> String {
@@ -272,6 +422,14 @@ impl Card {
     }
 }
 
+/// "B5" or "W5".
+pub fn card_name(card: &Card) -> String {
+    match card.color {
+        Color::BLACK => format!("B{}", card.value),
+        Color::WHITE => format!("W{}", card.value),
+    }
+}
+
 ///
 ///# Implementation of the Player struct
 ///
@@ -284,6 +442,8 @@ impl Player {
             deck: empty_vect,
             status: PlayerStatus::INIT,
             side_card: None,
+            ruled_out: Vec::new(),
+            inserted: Vec::new(),
         }
     }
 
@@ -330,7 +490,7 @@ impl Player {
         // remove the card from the set now
         let idx = avail_card
             .iter()
-            .position(|card| (card.value == picked_card.value && card.color == picked_card.color))
+            .position(|card| card.value == picked_card.value && card.color == picked_card.color)
             .unwrap();
         avail_card.remove(idx);
 
@@ -353,7 +513,7 @@ impl Player {
 
         let idx = avail_card
             .iter()
-            .position(|card| (card.value == picked_card.value && card.color == picked_card.color))
+            .position(|card| card.value == picked_card.value && card.color == picked_card.color)
             .unwrap();
         avail_card.remove(idx);
 
@@ -372,7 +532,7 @@ impl Player {
 
         let idx = avail_card
             .iter()
-            .position(|card| (card.value == picked_card.value && card.color == picked_card.color))
+            .position(|card| card.value == picked_card.value && card.color == picked_card.color)
             .unwrap();
         avail_card.remove(idx);
 
@@ -382,19 +542,24 @@ impl Player {
     }
 
     pub fn save_side_card(&mut self, hide_it: bool) {
-        if hide_it {
-            self.deck.push(self.side_card.unwrap());
-        } else {
+        // nothing drawn this turn (empty set), so nothing to save
+        let mut the_card = match self.side_card.take() {
+            Some(card) => card,
+            None => return,
+        };
+
+        if !hide_it {
             // reveal and save
-            let mut the_card = self.side_card.unwrap();
             the_card.status = CardStatus::REVEALED;
-
-            self.deck.push(the_card);
         }
+        self.deck.push(the_card);
         self.ncards += 1;
 
         // sort the deck
         self.sort_deck();
+        if let Some(idx) = self.deck.iter().position(|card| *card == the_card) {
+            self.inserted.push(idx);
+        }
     }
 
     pub fn show_hand(&self, opponent_view: bool, colorize: bool) -> String {
@@ -470,6 +635,11 @@ impl Player {
                     }
                 }
             };
+            let ruled_out = self.ruled_out(elm_number);
+            if opponent_view && !ruled_out.is_empty() {
+                let values: Vec<String> = ruled_out.iter().map(u32::to_string).collect();
+                hand.push_str(&format!("(not {})", values.join(" ")));
+            }
             hand.push_str(", ");
         }
         // hand.push('\n');
@@ -477,21 +647,40 @@ impl Player {
         hand
     }
 
-    fn sort_deck(&mut self) {
-        // sort here by value and color
-        self.deck.sort_by(|a, b| {
-            if a.value != b.value {
-                // sort by value in ascending order
-                return a.value.cmp(&b.value);
+    /// A wrong guess says the card `card_idx` isn't `value`.
+    pub fn rule_out(&mut self, card_idx: usize, value: u32) {
+        let card = match self.deck.get(card_idx) {
+            Some(card) if card.status == CardStatus::HIDDEN => *card,
+            _ => return,
+        };
+
+        let same = |other: &Card| other.value == card.value && other.color == card.color;
+        match self.ruled_out.iter_mut().find(|(other, _)| same(other)) {
+            Some((_, values)) if values.contains(&value) => {}
+            Some((_, values)) => {
+                values.push(value);
+                values.sort();
             }
+            None => self.ruled_out.push((card, vec![value])),
+        }
+    }
 
-            // sort by color (Black first, then White)
-            match (a.color, b.color) {
-                (Color::BLACK, Color::WHITE) => Ordering::Less,
-                (Color::WHITE, Color::BLACK) => Ordering::Greater,
-                _ => Ordering::Equal,
-            }
-        });
+    /// The values ruled out for the card `card_idx`, none once it's revealed.
+    pub fn ruled_out(&self, card_idx: usize) -> &[u32] {
+        let card = match self.deck.get(card_idx) {
+            Some(card) if card.status == CardStatus::HIDDEN => card,
+            _ => return &[],
+        };
+
+        self.ruled_out
+            .iter()
+            .find(|(other, _)| other.value == card.value && other.color == card.color)
+            .map_or(&[], |(_, values)| values.as_slice())
+    }
+
+    fn sort_deck(&mut self) {
+        // sort here by value and color
+        self.deck.sort_by(card_cmp);
     }
 
     pub fn init_game(&mut self, avail_card: &mut Vec<Card>) {
@@ -519,6 +708,15 @@ impl Player {
         ret
     }
 
+    /// Gives up: the side card and every hidden card get revealed, so the player is out.
+    pub fn forfeit(&mut self) {
+        self.save_side_card(false);
+
+        for card in self.deck.iter_mut() {
+            card.status = CardStatus::REVEALED;
+        }
+    }
+
     pub fn reveal_card_2(&mut self, card_to_reveal: &Card) {
         // reveal card
         let idx = self
@@ -533,6 +731,38 @@ impl Player {
     }
 }
 
+/// The values ruled out for each card of a hand sent by `Player::show_hand`, "0: B?(not 3
+/// 7), 1: W5" giving `[[3, 7], []]`.
+pub fn ruled_out_from_str(deck_str: &str) -> Vec<Vec<u32>> {
+    deck_str
+        .split(",")
+        .filter(|card| card.contains(":"))
+        .map(|card| match card.split_once("(not ") {
+            Some((_, values)) => values
+                .trim_end_matches(|c: char| c == ')' || c.is_whitespace())
+                .split_whitespace()
+                .filter_map(|value| value.parse().ok())
+                .collect(),
+            None => Vec::new(),
+        })
+        .collect()
+}
+
+/// Order of the cards in a deck: by value, black first on equal values.
+pub fn card_cmp(a: &Card, b: &Card) -> Ordering {
+    if a.value != b.value {
+        // sort by value in ascending order
+        return a.value.cmp(&b.value);
+    }
+
+    // sort by color (Black first, then White)
+    match (a.color, b.color) {
+        (Color::BLACK, Color::WHITE) => Ordering::Less,
+        (Color::WHITE, Color::BLACK) => Ordering::Greater,
+        _ => Ordering::Equal,
+    }
+}
+
 ///
 ///# Testing
 ///
@@ -540,6 +770,41 @@ impl Player {
 mod tests {
     use super::*;
 
+    /// A card as `status` left it.
+    pub(crate) fn card(value: u32, color: Color, status: CardStatus) -> Card {
+        let mut card = Card::new(value, color);
+        card.status = status;
+        card
+    }
+
+    /// alice and bob, dealt 4 cards each from a set of 24.
+    pub(crate) fn dealt() -> Game {
+        let mut game = Game::new(24);
+        game.players = vec![
+            Player::new(String::from("alice"), 4),
+            Player::new(String::from("bob"), 4),
+        ];
+        game.init_set();
+        game
+    }
+
+    /// alice, bob and carol, played by bots to the end.
+    pub(crate) fn bot_game() -> Game {
+        let mut game = Game::new(24);
+        for name in ["alice", "bob", "carol"] {
+            game.players.push(Player::new(String::from(name), 4));
+        }
+        game.init_set();
+
+        let mut turn = 0;
+        while !game.game_status() {
+            let name = game.players[turn % game.players.len()].name.clone();
+            crate::bot::play_turn(&mut game, &name);
+            turn += 1;
+        }
+        game
+    }
+
     #[test]
     #[should_panic]
     fn test_uniq_unames() {
@@ -786,4 +1051,66 @@ mod tests {
         // avail card reduced
         assert_eq!(START_CARD_N, game.card_avail.len() as u32 + 4);
     }
+
+    #[test]
+    fn test_save_side_card_once() {
+        let mut p1 = Player::new(String::from("me"), 0);
+        p1.side_card = Some(Card::new(5, crate::Color::BLACK));
+
+        p1.save_side_card(false);
+        assert_eq!(p1.side_card, None);
+        assert_eq!(p1.deck.len(), 1);
+        assert_eq!(p1.deck[0].status, CardStatus::REVEALED);
+
+        // nothing left on the side, so a second save is a no-op
+        p1.save_side_card(true);
+        assert_eq!(p1.ncards, 1);
+        assert_eq!(p1.deck.len(), 1);
+    }
+
+    #[test]
+    fn test_forfeit() {
+        let mut p1 = Player::new(String::from("me"), 2);
+        p1.deck.push(Card::new(1, crate::Color::WHITE));
+        p1.deck.push(Card::new(3, crate::Color::BLACK));
+        p1.side_card = Some(Card::new(2, crate::Color::BLACK));
+
+        p1.forfeit();
+
+        assert_eq!(p1.ncards, 3);
+        assert!(p1
+            .deck
+            .iter()
+            .all(|card| card.status == CardStatus::REVEALED));
+    }
+
+    #[test]
+    fn test_ruled_out_follows_the_card() {
+        let mut game = Game::new(24);
+        let mut bob = Player::new(String::from("bob"), 2);
+        bob.deck = vec![
+            Card::new(4, crate::Color::BLACK),
+            Card::new(9, crate::Color::WHITE),
+        ];
+        game.players.push(bob);
+
+        assert_eq!(game.guess("alice", "bob", 1, 7), Some(false));
+        assert_eq!(game.guess("alice", "bob", 1, 3), Some(false));
+        assert_eq!(game.guess("alice", "bob", 1, 7), Some(false));
+        assert_eq!(game.players[0].ruled_out(1), &[3, 7]);
+
+        // a card drawn below W9 moves it along
+        let bob = &mut game.players[0];
+        bob.side_card = Some(Card::new(2, crate::Color::WHITE));
+        bob.save_side_card(true);
+        assert_eq!(bob.ruled_out(1), &[] as &[u32]);
+        assert_eq!(bob.ruled_out(2), &[3, 7]);
+
+        let hand = bob.show_hand(true, false);
+        assert_eq!(hand, "0: W?, 1: B?, 2: W?(not 3 7), ");
+        assert_eq!(ruled_out_from_str(&hand), vec![vec![], vec![], vec![3, 7]]);
+
+        bob.reveal_card(2);
+        assert!(bob.ruled_out(2).is_empty());
+    }
 }
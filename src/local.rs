@@ -0,0 +1,501 @@
+//! A game played on one terminal, without any server.
+//!
+//! The players share the keyboard and answer the questions the servers would ask, one line
+//! at a time. Between turns the game waits on `Step::PASS`, so the screen can hide the hand
+//! of the previous player until the next one sits down. Seats can also go to bots, which
+//! play their turn as soon as it comes.
+
+use crate::bot::{self, Difficulty};
+use crate::{card_name, Game, Player};
+use rand::Rng;
+use std::collections::HashSet;
+use std::fmt;
+
+pub const CARD_PER_PLAYER: u32 = 4;
+pub const START_CARD_N: u32 = 24;
+pub const MAX_PLAYERS: usize = (START_CARD_N / CARD_PER_PLAYER) as usize;
+
+/// What the game waits for.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Step {
+    /// The next player to sit down and press Enter
+    PASS,
+    /// Which card of the set to draw
+    DRAW,
+    /// Whether to guess, or to keep the drawn card hidden
+    GUESS,
+    /// Which opponent to guess
+    OPPONENT,
+    /// Which card of this opponent to guess
+    CARD(String),
+    /// The value of this card of this opponent
+    VALUE(String, usize),
+    /// Whether to guess again after a right guess
+    AGAIN,
+    /// Nothing, the game is over
+    END,
+}
+
+/// Why a local game can't start.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SetupError {
+    /// Too few or too many players
+    PLAYERS,
+    /// Two players with the same name
+    NAMES,
+}
+
+impl fmt::Display for SetupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SetupError::PLAYERS => write!(f, "A local game needs 2 to {} players.", MAX_PLAYERS),
+            SetupError::NAMES => write!(f, "Players should have different names."),
+        }
+    }
+}
+
+impl std::error::Error for SetupError {}
+
+///
+///# A game between players sharing one terminal
+///
+#[derive(Debug)]
+pub struct LocalGame {
+    pub game: Game,
+    pub step: Step,
+    /// Who plays after whom, players who lost included
+    order: Vec<String>,
+    bots: HashSet<String>,
+    difficulty: Difficulty,
+    current: String,
+    /// What every player may know, oldest first
+    pub events: Vec<String>,
+}
+
+impl LocalGame {
+    pub fn new(names: &[String]) -> Result<LocalGame, SetupError> {
+        LocalGame::with_bots(names, 0, Difficulty::NORMAL)
+    }
+
+    /// A game between `names` and `bots` bots, named bot1, bot2...
+    pub fn with_bots(
+        names: &[String],
+        bots: usize,
+        difficulty: Difficulty,
+    ) -> Result<LocalGame, SetupError> {
+        let bots: Vec<String> = (1..=bots).map(|n| format!("bot{}", n)).collect();
+        let names = [names, &bots].concat();
+
+        if names.len() < 2 || names.len() > MAX_PLAYERS {
+            return Err(SetupError::PLAYERS);
+        }
+        if (1..names.len()).any(|i| names[i..].contains(&names[i - 1])) {
+            return Err(SetupError::NAMES);
+        }
+
+        let mut game = Game::new(START_CARD_N);
+        for name in names.iter() {
+            game.players
+                .push(Player::new(name.clone(), CARD_PER_PLAYER));
+        }
+        game.init_set();
+
+        let first = names[rand::rng().random_range(0..names.len())].clone();
+
+        let mut local = LocalGame {
+            game,
+            step: Step::PASS,
+            events: vec![format!("{} plays first.", first)],
+            current: first.clone(),
+            order: names,
+            bots: bots.into_iter().collect(),
+            difficulty,
+        };
+        local.give_turn(first);
+
+        Ok(local)
+    }
+
+    /// Whose turn it is, or who is about to play on `Step::PASS`.
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    pub fn player(&self) -> Option<&Player> {
+        self.game
+            .players
+            .iter()
+            .find(|player| player.name == self.current)
+    }
+
+    /// The players still in the game, apart from the current one.
+    pub fn opponents(&self) -> Vec<&Player> {
+        self.game
+            .players
+            .iter()
+            .filter(|player| player.name != self.current)
+            .collect()
+    }
+
+    /// The question for the current player.
+    pub fn prompt(&self) -> String {
+        match &self.step {
+            Step::PASS => format!("Pass the terminal to {}, then press Enter.", self.current),
+            Step::DRAW => format!(
+                "It's your turn {}: Enter card number to draw it (0 to {})",
+                self.current,
+                self.game.card_avail.len() - 1
+            ),
+            Step::GUESS => {
+                let drawn = match self.player().and_then(|player| player.side_card) {
+                    Some(card) => format!("You picked a {}. ", card_name(&card)),
+                    None => String::new(),
+                };
+                format!("{}Would you like to make a guess? (yes/no)", drawn)
+            }
+            Step::OPPONENT => {
+                let mut prompt = String::from("Pick the opponent for this guess:\n");
+                for (idx, opponent) in self.opponents().iter().enumerate() {
+                    prompt.push_str(
+                        format!(
+                            "{}: {} => {}\n",
+                            idx,
+                            opponent.name,
+                            opponent.show_hand(true, false)
+                        )
+                        .as_str(),
+                    );
+                }
+                prompt
+            }
+            Step::CARD(opponent) => format!(
+                "Which card of {} would you like to guess? (0 to {})",
+                opponent,
+                self.deck_len(opponent).saturating_sub(1)
+            ),
+            Step::VALUE(opponent, card_idx) => format!(
+                "Enter your guess for {}'s card {}: value between 0 and {}",
+                opponent,
+                card_idx,
+                self.game.set_cards / 2 - 1
+            ),
+            Step::AGAIN => {
+                String::from("You got it right! Would you like to make another guess? (yes/no)")
+            }
+            Step::END => match &self.game.winner {
+                Some(winner) => format!("{} won the game.", winner.name),
+                None => String::from("The game is over."),
+            },
+        }
+    }
+
+    /// Plays the answer of the current player. An Err tells them why it was refused, and the
+    /// same question stands.
+    pub fn answer(&mut self, input: &str) -> Result<(), String> {
+        let input = input.trim();
+
+        match self.step.clone() {
+            Step::PASS => self.start_turn(),
+            Step::DRAW => {
+                let card_number = read_number(input, self.game.card_avail.len())?;
+                self.game.draw(&self.current, card_number);
+                self.events.push(format!("{} drew a card.", self.current));
+                self.step = Step::GUESS;
+            }
+            Step::GUESS | Step::AGAIN => match read_yes_no(input)? {
+                true => self.step = self.guess_step(),
+                false => {
+                    if self.player().is_some_and(|p| p.side_card.is_some()) {
+                        self.events
+                            .push(format!("{} kept their card hidden.", self.current));
+                    }
+                    self.end_turn(true);
+                }
+            },
+            Step::OPPONENT => {
+                let opponents = self.opponents();
+                let idx = read_number(input, opponents.len())?;
+                self.step = Step::CARD(opponents[idx].name.clone());
+            }
+            Step::CARD(opponent) => {
+                let card_idx = read_number(input, self.deck_len(&opponent))?;
+                let hidden = self
+                    .find(&opponent)
+                    .and_then(|player| player.get_specific_card_value(card_idx))
+                    .is_some();
+                if !hidden {
+                    return Err(String::from("This card is already revealed."));
+                }
+                self.step = Step::VALUE(opponent, card_idx);
+            }
+            Step::VALUE(opponent, card_idx) => {
+                let value = read_number(input, (self.game.set_cards / 2) as usize)? as u32;
+                self.guess(&opponent, card_idx, value);
+            }
+            Step::END => {}
+        }
+
+        Ok(())
+    }
+
+    fn guess(&mut self, opponent: &str, card_idx: usize, value: u32) {
+        let right = self.game.guess(&self.current, opponent, card_idx, value) == Some(true);
+
+        self.events.push(format!(
+            "{} guessed {}'s card {} is {}: {}",
+            self.current,
+            opponent,
+            card_idx,
+            value,
+            if right { "right" } else { "wrong" }
+        ));
+
+        if !right {
+            if self
+                .player()
+                .is_some_and(|player| player.side_card.is_some())
+            {
+                self.events
+                    .push(format!("{} revealed their drawn card.", self.current));
+            }
+            self.end_turn(false);
+        } else if self.game.game_status() {
+            self.end_turn(true);
+        } else {
+            self.step = Step::AGAIN;
+        }
+    }
+
+    fn start_turn(&mut self) {
+        self.game.shuffle_avail_card();
+
+        self.step = if self.game.card_avail.is_empty() {
+            // nothing left to draw, the only move is a guess
+            self.guess_step()
+        } else {
+            Step::DRAW
+        };
+    }
+
+    /// Straight to the cards when there's only one opponent to pick.
+    fn guess_step(&self) -> Step {
+        match self.opponents().as_slice() {
+            [opponent] => Step::CARD(opponent.name.clone()),
+            _ => Step::OPPONENT,
+        }
+    }
+
+    /// Keeps the drawn card, hidden or not, and hands over to the next player.
+    fn end_turn(&mut self, hide: bool) {
+        let current = self.current.clone();
+        self.game.save_side_card(&current, hide);
+
+        if self.game.game_status() {
+            if let Some(winner) = &self.game.winner {
+                self.events.push(format!("{} won the game.", winner.name));
+            }
+            self.step = Step::END;
+            return;
+        }
+
+        let next = self.next_after(&current);
+        self.give_turn(next);
+    }
+
+    /// Plays the turns of the bots up to the next person, who gets the keyboard.
+    fn give_turn(&mut self, mut name: String) {
+        while self.bots.contains(&name) {
+            self.current = name.clone();
+            let summary = bot::play_turn_as(&mut self.game, &name, self.difficulty);
+            self.events.extend(summary.lines().map(String::from));
+
+            if self.game.game_status() {
+                if let Some(winner) = &self.game.winner {
+                    self.events.push(format!("{} won the game.", winner.name));
+                }
+                self.step = Step::END;
+                return;
+            }
+            name = self.next_after(&name);
+        }
+
+        self.current = name;
+        if self.order.len() - self.bots.len() > 1 {
+            self.step = Step::PASS;
+        } else {
+            // nobody to hide the screen from
+            self.start_turn();
+        }
+    }
+
+    /// The seat after `name` that is still in the game.
+    fn next_after(&self, name: &str) -> String {
+        let seat = self.order.iter().position(|seat| seat == name).unwrap_or(0);
+
+        (1..=self.order.len())
+            .map(|offset| &self.order[(seat + offset) % self.order.len()])
+            .find(|seat| self.find(seat).is_some())
+            .unwrap_or(&self.order[seat])
+            .clone()
+    }
+
+    fn find(&self, name: &str) -> Option<&Player> {
+        self.game.players.iter().find(|player| player.name == name)
+    }
+
+    fn deck_len(&self, name: &str) -> usize {
+        self.find(name).map_or(0, |player| player.deck.len())
+    }
+}
+
+fn read_number(input: &str, count: usize) -> Result<usize, String> {
+    match input.parse::<usize>() {
+        Ok(number) if number < count => Ok(number),
+        _ => Err(format!(
+            "Please enter a number between 0 and {}.",
+            count.saturating_sub(1)
+        )),
+    }
+}
+
+fn read_yes_no(input: &str) -> Result<bool, String> {
+    match input {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err(String::from("Please answer yes or no.")),
+    }
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CardStatus, GameState};
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    /// The first hidden card of `opponent` and its value.
+    fn hidden_card(local: &LocalGame, opponent: &str) -> (usize, u32) {
+        let player = local.find(opponent).unwrap();
+        let idx = player
+            .deck
+            .iter()
+            .position(|card| card.status == CardStatus::HIDDEN)
+            .unwrap();
+
+        (idx, player.deck[idx].value)
+    }
+
+    #[test]
+    fn test_setup_errors() {
+        assert_eq!(
+            LocalGame::new(&names(&["alice"])).unwrap_err(),
+            SetupError::PLAYERS
+        );
+        assert_eq!(
+            LocalGame::new(&names(&["alice", "bob", "alice"])).unwrap_err(),
+            SetupError::NAMES
+        );
+    }
+
+    #[test]
+    fn test_turn_passes_on() {
+        let mut local = LocalGame::new(&names(&["alice", "bob", "carol"])).unwrap();
+        assert_eq!(local.step, Step::PASS);
+        let first = local.current().to_string();
+
+        local.answer("").unwrap();
+        assert_eq!(local.step, Step::DRAW);
+        assert!(local.answer("99").is_err());
+        local.answer("0").unwrap();
+        assert_eq!(local.step, Step::GUESS);
+        assert!(local.prompt().starts_with("You picked a "));
+
+        local.answer("no").unwrap();
+        assert_eq!(local.step, Step::PASS);
+        assert_ne!(local.current(), first);
+        assert_eq!(local.find(&first).unwrap().deck.len(), 5);
+    }
+
+    #[test]
+    fn test_wrong_guess_reveals_drawn_card() {
+        let mut local = LocalGame::new(&names(&["alice", "bob"])).unwrap();
+        let me = local.current().to_string();
+        let opponent = local.opponents()[0].name.clone();
+
+        local.answer("").unwrap();
+        local.answer("0").unwrap();
+        local.answer("yes").unwrap();
+        assert_eq!(local.step, Step::CARD(opponent.clone()));
+
+        let (idx, value) = hidden_card(&local, &opponent);
+        local.answer(&idx.to_string()).unwrap();
+        local.answer(&((value + 1) % 12).to_string()).unwrap();
+
+        assert_eq!(local.step, Step::PASS);
+        assert_eq!(local.current(), opponent);
+        let revealed = local
+            .find(&me)
+            .unwrap()
+            .deck
+            .iter()
+            .filter(|card| card.status == CardStatus::REVEALED)
+            .count();
+        assert_eq!(revealed, 1);
+    }
+
+    #[test]
+    fn test_practice_against_bots() {
+        let mut local = LocalGame::with_bots(&names(&["me"]), 2, Difficulty::HARD).unwrap();
+        assert_eq!(
+            LocalGame::with_bots(&names(&["bot1"]), 1, Difficulty::EASY).unwrap_err(),
+            SetupError::NAMES
+        );
+
+        // the bots play on their own, and the keyboard is never passed
+        for _ in 0..200 {
+            match local.step {
+                Step::END => break,
+                Step::PASS => panic!("Nobody to pass the terminal to"),
+                _ => assert_eq!(local.current(), "me"),
+            }
+            let answer = match &local.step {
+                Step::CARD(opponent) => hidden_card(&local, opponent).0.to_string(),
+                Step::GUESS | Step::AGAIN => String::from("yes"),
+                _ => String::from("0"),
+            };
+            local.answer(&answer).unwrap();
+        }
+
+        assert_eq!(local.step, Step::END);
+        assert!(local.game.winner.is_some());
+    }
+
+    #[test]
+    fn test_right_guesses_win() {
+        let mut local = LocalGame::new(&names(&["alice", "bob"])).unwrap();
+        let me = local.current().to_string();
+        let opponent = local.opponents()[0].name.clone();
+
+        local.answer("").unwrap();
+        local.answer("0").unwrap();
+        local.answer("yes").unwrap();
+
+        for _ in 0..CARD_PER_PLAYER {
+            let (idx, value) = hidden_card(&local, &opponent);
+            local.answer(&idx.to_string()).unwrap();
+            local.answer(&value.to_string()).unwrap();
+            if local.step == Step::AGAIN {
+                local.answer("yes").unwrap();
+            }
+        }
+
+        assert_eq!(local.step, Step::END);
+        assert_eq!(local.game.state, GameState::END);
+        assert_eq!(local.prompt(), format!("{} won the game.", me));
+    }
+}
@@ -0,0 +1,226 @@
+//! The log of the TUI client: what happened, newest first, in a bounded ring buffer.
+//!
+//! Every entry has the time it came in, since the client started, and a category, so the
+//! log can show one category only, or the entries with some text in them.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Entries kept, the oldest ones go first
+pub const MAX_ENTRIES: usize = 500;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Category {
+    /// The client telling about itself: connections, notes, hints
+    SYSTEM,
+    /// The game, as the server or the local game tells it
+    MOVE,
+    CHAT,
+    ERROR,
+}
+
+impl Category {
+    pub const ALL: [Category; 4] = [
+        Category::SYSTEM,
+        Category::MOVE,
+        Category::CHAT,
+        Category::ERROR,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Category::SYSTEM => "system",
+            Category::MOVE => "move",
+            Category::CHAT => "chat",
+            Category::ERROR => "error",
+        }
+    }
+
+    /// The filter after `filter`: every category, then each one in turn.
+    pub fn next_filter(filter: Option<Category>) -> Option<Category> {
+        match filter {
+            None => Some(Category::ALL[0]),
+            Some(category) => {
+                let idx = Category::ALL.iter().position(|other| *other == category);
+                idx.and_then(|idx| Category::ALL.get(idx + 1).copied())
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Entry {
+    /// Since the client started
+    pub at: Duration,
+    pub category: Category,
+    pub text: String,
+}
+
+impl Entry {
+    /// "04:07", or "1:04:07" past the first hour.
+    pub fn stamp(&self) -> String {
+        let secs = self.at.as_secs();
+        match secs / 3600 {
+            0 => format!("{:02}:{:02}", secs / 60, secs % 60),
+            hours => format!("{}:{:02}:{:02}", hours, secs / 60 % 60, secs % 60),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Logs {
+    /// Oldest first
+    entries: VecDeque<Entry>,
+    started: Instant,
+    /// Shown above the entries and never filtered: the prompt of a local game, a replay
+    pub header: String,
+    /// Show only this category, none for all of them
+    pub filter: Option<Category>,
+    /// Show only the entries with this in them, whatever the case
+    pub search: String,
+}
+
+impl Default for Logs {
+    fn default() -> Self {
+        Logs::new()
+    }
+}
+
+impl Logs {
+    pub fn new() -> Logs {
+        Logs {
+            entries: VecDeque::new(),
+            started: Instant::now(),
+            header: String::new(),
+            filter: None,
+            search: String::new(),
+        }
+    }
+
+    /// Adds `text` as of now, with no blank lines around it.
+    pub fn push(&mut self, category: Category, text: &str) {
+        let at = self.started.elapsed();
+        self.push_at(at, category, text);
+    }
+
+    fn push_at(&mut self, at: Duration, category: Category, text: &str) {
+        let text = text.trim_matches('\n');
+        if text.trim().is_empty() {
+            return;
+        }
+
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Entry {
+            at,
+            category,
+            text: text.to_string(),
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entries the filter and the search let through, newest first.
+    pub fn shown(&self) -> impl Iterator<Item = &Entry> {
+        let search = self.search.to_lowercase();
+
+        self.entries.iter().rev().filter(move |entry| {
+            self.filter
+                .is_none_or(|category| entry.category == category)
+                && (search.is_empty() || entry.text.to_lowercase().contains(&search))
+        })
+    }
+
+    /// What the log shows on top of its entries, none when it shows everything.
+    pub fn describe_view(&self) -> Option<String> {
+        let mut view = Vec::new();
+        if let Some(category) = self.filter {
+            view.push(format!("{} only", category.label()));
+        }
+        if !self.search.is_empty() {
+            view.push(format!("/{}", self.search));
+        }
+
+        match view.is_empty() {
+            true => None,
+            false => Some(view.join(", ")),
+        }
+    }
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_the_oldest_entries_go_first() {
+        let mut logs = Logs::new();
+        for idx in 0..MAX_ENTRIES + 3 {
+            logs.push(Category::MOVE, &format!("move {}", idx));
+        }
+        logs.push(Category::MOVE, "\n\n");
+
+        assert_eq!(logs.len(), MAX_ENTRIES);
+        let shown: Vec<&str> = logs.shown().map(|entry| entry.text.as_str()).collect();
+        assert_eq!(shown[0], format!("move {}", MAX_ENTRIES + 2));
+        assert_eq!(shown[MAX_ENTRIES - 1], "move 3");
+    }
+
+    #[test]
+    fn test_filter_and_search() {
+        let mut logs = Logs::new();
+        logs.push(Category::SYSTEM, "Connected to server at 127.0.0.1:8079\n");
+        logs.push(
+            Category::MOVE,
+            "\nbob guessed card 2 of alice: 5, wrong\n\n\n",
+        );
+        logs.push(Category::CHAT, "alice: nice try Bob");
+        logs.push(Category::ERROR, "Connection lost, reconnecting...");
+
+        assert_eq!(logs.shown().count(), 4);
+        assert_eq!(logs.describe_view(), None);
+
+        logs.filter = Some(Category::MOVE);
+        let shown: Vec<&Entry> = logs.shown().collect();
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown[0].text, "bob guessed card 2 of alice: 5, wrong");
+
+        logs.filter = None;
+        logs.search = String::from("BOB");
+        assert_eq!(logs.shown().count(), 2);
+        logs.filter = Some(Category::CHAT);
+        assert_eq!(logs.shown().count(), 1);
+        assert_eq!(logs.describe_view(), Some(String::from("chat only, /BOB")));
+
+        let mut filter = None;
+        let mut filters = Vec::new();
+        loop {
+            filter = Category::next_filter(filter);
+            filters.push(filter);
+            if filter.is_none() {
+                break;
+            }
+        }
+        assert_eq!(filters.len(), Category::ALL.len() + 1);
+    }
+
+    #[test]
+    fn test_stamp() {
+        let mut logs = Logs::new();
+        logs.push_at(Duration::from_secs(247), Category::SYSTEM, "a");
+        logs.push_at(Duration::from_secs(3600 + 247), Category::SYSTEM, "b");
+
+        let stamps: Vec<String> = logs.shown().map(Entry::stamp).collect();
+        assert_eq!(stamps, vec!["1:04:07", "04:07"]);
+    }
+}
@@ -0,0 +1,426 @@
+//! A text notation for whole games, like PGN for chess.
+//!
+//! A record starts with tags, then the deal (one line per seat, in seating order), then one
+//! numbered line per turn:
+//!
+//! ```text
+//! [Set "24"]
+//! [Seed "6d6f..."]
+//! [Winner "bob"]
+//!
+//! alice: W0 B3 B10 W10
+//! bob: B7 B8 W9 B11
+//!
+//! 1. alice+W3 alice>bob#2=9 ! alice>bob#0=6 x alice:show
+//! 2. bob+B1 bob:hide
+//! ```
+//!
+//! `alice+W3` is a draw, `alice>bob#2=9` a guess of bob's card 2, right (`!`) or wrong
+//! (`x`). `alice:hide` and `alice:show` put the drawn card in the deck hidden or revealed,
+//! `alice:resign` gives up. Player names can't hold spaces nor any of `:+>#=`.
+//!
+//! That is the private form, with every card. The public one, to paste anywhere, only has
+//! the color of the cards the table never saw (`bob: B B W9 B`, `bob+B`) and no seed, which
+//! would give them away; it can't be played again.
+
+use crate::archive::GameRecord;
+use crate::history::GameEvent;
+use crate::replay::Replay;
+use crate::{card_name, Card, CardStatus, Color};
+use regex::Regex;
+use std::fmt;
+use std::sync::LazyLock;
+
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"^\[(\w+) "([^"]*)"\]$"#).unwrap());
+static DEAL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^([^\s:+>#=]+):\s+(.*)$").unwrap());
+static TURN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\d+\.$").unwrap());
+static DRAW: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^([^:+>#=]+)\+(\S+)$").unwrap());
+static GUESS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([^:+>#=]+)>([^:+>#=]+)#(\d+)=(\d+)$").unwrap());
+static SAVE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([^:+>#=]+):(hide|show|resign)$").unwrap());
+
+/// Why a record couldn't be read.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    /// From 1
+    pub line: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The record of a game, in notation, as anyone may see it.
+pub fn write(record: &GameRecord) -> String {
+    write_as(record, Some(&seen(record)))
+}
+
+/// The record of a game, in notation, with every card: to play it again or analyze it.
+pub fn write_private(record: &GameRecord) -> String {
+    write_as(record, None)
+}
+
+/// With only the color of the cards that aren't `seen`, when given.
+fn write_as(record: &GameRecord, seen: Option<&[Card]>) -> String {
+    let mut text = format!("[Set \"{}\"]\n", record.set_cards);
+    if !record.seed.is_empty() && seen.is_none() {
+        text.push_str(&format!("[Seed \"{}\"]\n", record.seed));
+    }
+    if record.started > 0 {
+        text.push_str(&format!("[Started \"{}\"]\n", record.started));
+        text.push_str(&format!("[Finished \"{}\"]\n", record.finished));
+    }
+    if let Some(winner) = &record.winner {
+        text.push_str(&format!("[Winner \"{}\"]\n", winner));
+    }
+    text.push('\n');
+
+    let mut deal_done = false;
+    let mut turn = 0;
+    for (event, event_turn) in record.events.iter().zip(turns(&record.events)) {
+        if let GameEvent::DEAL { player, cards } = event {
+            let cards: Vec<String> = cards.iter().map(|card| card_token(card, seen)).collect();
+            text.push_str(&format!("{}: {}\n", player, cards.join(" ")));
+            continue;
+        }
+        if !deal_done {
+            deal_done = true;
+            text.push('\n');
+        }
+
+        if event_turn != turn {
+            turn = event_turn;
+            if turn > 1 {
+                text.push('\n');
+            }
+            text.push_str(&format!("{}.", turn));
+        }
+        text.push(' ');
+        text.push_str(&token(event, seen));
+    }
+    if deal_done {
+        text.push('\n');
+    }
+
+    text
+}
+
+/// The cards the table saw by the end of the game, none when the moves don't fit the cards.
+fn seen(record: &GameRecord) -> Vec<Card> {
+    let replay = match Replay::new(record.clone()) {
+        Ok(replay) => replay,
+        Err(_) => return Vec::new(),
+    };
+
+    replay
+        .players(replay.moves())
+        .iter()
+        .flat_map(|player| player.deck.iter())
+        .filter(|card| card.status == CardStatus::REVEALED)
+        .copied()
+        .collect()
+}
+
+/// "W3", or "W" when the card isn't `seen`.
+fn card_token(card: &Card, seen: Option<&[Card]>) -> String {
+    let shown = seen.is_none_or(|seen| {
+        seen.iter()
+            .any(|other| other.color == card.color && other.value == card.value)
+    });
+
+    match (shown, card.color) {
+        (true, _) => card_name(card),
+        (false, Color::BLACK) => String::from("B"),
+        (false, Color::WHITE) => String::from("W"),
+    }
+}
+
+/// The turn of each event, from 1, the deal being turn 0.
+pub fn turns(events: &[GameEvent]) -> Vec<usize> {
+    let mut turn = 0;
+    let mut current = "";
+    let mut turn_over = true;
+
+    events
+        .iter()
+        .map(|event| {
+            if matches!(event, GameEvent::DEAL { .. }) {
+                return 0;
+            }
+
+            // a turn ends with the drawn card saved, or when somebody else moves
+            let player = event.player();
+            if turn_over || player != current {
+                turn += 1;
+                current = player;
+            }
+            turn_over = matches!(event, GameEvent::SAVE { .. } | GameEvent::FORFEIT { .. });
+            turn
+        })
+        .collect()
+}
+
+/// Reads a record back.
+pub fn parse(text: &str) -> Result<GameRecord, ParseError> {
+    let mut record = GameRecord {
+        started: 0,
+        finished: 0,
+        set_cards: 0,
+        cards_per_player: 0,
+        seed: String::new(),
+        seats: Vec::new(),
+        events: Vec::new(),
+        winner: None,
+    };
+
+    for (idx, line) in text.lines().enumerate() {
+        let error = |reason: String| ParseError {
+            line: idx + 1,
+            reason,
+        };
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        } else if let Some(caps) = TAG.captures(line) {
+            let number = || {
+                caps[2]
+                    .parse::<u64>()
+                    .map_err(|_| error(format!("{} isn't a number", &caps[2])))
+            };
+            match &caps[1] {
+                "Set" => record.set_cards = number()? as u32,
+                "Seed" => record.seed = caps[2].to_string(),
+                "Started" => record.started = number()?,
+                "Finished" => record.finished = number()?,
+                "Winner" => record.winner = Some(caps[2].to_string()),
+                // like PGN, tags we don't know are kept out of the way
+                _ => {}
+            }
+        } else if let Some(caps) = DEAL.captures(line) {
+            let cards = caps[2]
+                .split_whitespace()
+                .map(|card| read_card(card).map_err(error))
+                .collect::<Result<Vec<Card>, ParseError>>()?;
+            record.seats.push(caps[1].to_string());
+            record.events.push(GameEvent::DEAL {
+                player: caps[1].to_string(),
+                cards,
+            });
+        } else {
+            parse_turn(line, &mut record.events).map_err(error)?;
+        }
+    }
+
+    if record.seats.is_empty() {
+        return Err(ParseError {
+            line: text.lines().count(),
+            reason: String::from("no deal"),
+        });
+    }
+    record.cards_per_player = match &record.events[0] {
+        GameEvent::DEAL { cards, .. } => cards.len() as u32,
+        _ => 0,
+    };
+
+    Ok(record)
+}
+
+fn parse_turn(line: &str, events: &mut Vec<GameEvent>) -> Result<(), String> {
+    let mut tokens = line.split_whitespace();
+
+    while let Some(token) = tokens.next() {
+        if TURN.is_match(token) {
+            continue;
+        }
+
+        let event = if let Some(caps) = GUESS.captures(token) {
+            let right = match tokens.next() {
+                Some("!") => true,
+                Some("x") => false,
+                _ => return Err(format!("{} needs ! or x after it", token)),
+            };
+            GameEvent::GUESS {
+                player: caps[1].to_string(),
+                opponent: caps[2].to_string(),
+                card_idx: caps[3]
+                    .parse()
+                    .map_err(|_| format!("bad guess {}", token))?,
+                value: caps[4]
+                    .parse()
+                    .map_err(|_| format!("bad guess {}", token))?,
+                right,
+            }
+        } else if let Some(caps) = DRAW.captures(token) {
+            GameEvent::DRAW {
+                player: caps[1].to_string(),
+                card: read_card(&caps[2])?,
+            }
+        } else if let Some(caps) = SAVE.captures(token) {
+            let player = caps[1].to_string();
+            match &caps[2] {
+                "resign" => GameEvent::FORFEIT { player },
+                hide => GameEvent::SAVE {
+                    player,
+                    hidden: hide == "hide",
+                },
+            }
+        } else {
+            return Err(format!("unknown move {}", token));
+        };
+
+        events.push(event);
+    }
+
+    Ok(())
+}
+
+fn token(event: &GameEvent, seen: Option<&[Card]>) -> String {
+    match event {
+        GameEvent::DEAL { player, .. } => format!("{}:", player),
+        GameEvent::DRAW { player, card } => format!("{}+{}", player, card_token(card, seen)),
+        GameEvent::GUESS {
+            player,
+            opponent,
+            card_idx,
+            value,
+            right,
+        } => format!(
+            "{}>{}#{}={} {}",
+            player,
+            opponent,
+            card_idx,
+            value,
+            if *right { "!" } else { "x" }
+        ),
+        GameEvent::SAVE { player, hidden } => {
+            format!("{}:{}", player, if *hidden { "hide" } else { "show" })
+        }
+        GameEvent::FORFEIT { player } => format!("{}:resign", player),
+    }
+}
+
+/// A card of the private form, a public one can't be played again.
+fn read_card(card: &str) -> Result<Card, String> {
+    match card {
+        "B" | "W" => Err(format!(
+            "{} has no value, only a private record can be read",
+            card
+        )),
+        _ => parse_card(card).ok_or_else(|| format!("bad card {}", card)),
+    }
+}
+
+/// "B5" or "W11".
+fn parse_card(card: &str) -> Option<Card> {
+    let color = match card.get(..1)? {
+        "B" => Color::BLACK,
+        "W" => Color::WHITE,
+        _ => return None,
+    };
+
+    Some(Card::new(card[1..].parse().ok()?, color))
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::bot_game;
+
+    const RECORD: &str = "[Set \"24\"]
+[Winner \"alice\"]
+[Event \"Friday night\"]
+
+alice: W0 B3 B10 W10
+bob: B7 B8 W9 B11
+
+1. alice+W3 alice>bob#2=9 ! alice>bob#0=6 x alice:show
+2. bob+B1 bob:hide
+3. alice:resign
+";
+
+    #[test]
+    fn test_parse() {
+        let record = parse(RECORD).unwrap();
+
+        assert_eq!(record.seats, vec!["alice", "bob"]);
+        assert_eq!(record.cards_per_player, 4);
+        assert_eq!(record.winner.as_deref(), Some("alice"));
+        assert_eq!(record.events.len(), 9);
+        assert_eq!(
+            record.events[3],
+            GameEvent::GUESS {
+                player: String::from("alice"),
+                opponent: String::from("bob"),
+                card_idx: 2,
+                value: 9,
+                right: true,
+            }
+        );
+        assert_eq!(
+            record.events[7],
+            GameEvent::SAVE {
+                player: String::from("bob"),
+                hidden: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = parse(&RECORD.replace("W9 ", "Y9 ")).unwrap_err();
+        assert_eq!(error.line, 6);
+
+        let error = parse(&RECORD.replace("#2=9 !", "#2=9")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 8: alice>bob#2=9 needs ! or x after it"
+        );
+        assert!(parse("1. alice+W3").is_err());
+    }
+
+    #[test]
+    fn test_written_games_read_back() {
+        for _ in 0..10 {
+            let game = bot_game();
+            let record = GameRecord::of(&game, 1_792_332_300);
+            assert_eq!(parse(&write_private(&record)), Ok(record));
+        }
+    }
+
+    #[test]
+    fn test_public_form() {
+        let mut record = parse(RECORD).unwrap();
+        record.seed = String::from("6d6f");
+        assert!(write_private(&record).contains("[Seed \"6d6f\"]"));
+
+        // alice resigned, the table only saw the W9 of bob
+        let public = write(&record);
+        assert_eq!(
+            public,
+            "[Set \"24\"]
+[Winner \"alice\"]
+
+alice: W0 B3 B10 W10
+bob: B B W9 B
+
+1. alice+W3 alice>bob#2=9 ! alice>bob#0=6 x alice:show
+2. bob+B bob:hide
+3. alice:resign
+"
+        );
+        assert_eq!(
+            parse(&public).unwrap_err().to_string(),
+            "line 5: B has no value, only a private record can be read"
+        );
+    }
+}
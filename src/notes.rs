@@ -0,0 +1,219 @@
+//! Notes on the hidden cards of the opponents, kept by the TUI client.
+//!
+//! Hidden cards change places as cards get added to a hand, so after each turn the server
+//! sends where every card a player saved went, `%%inserted:2,0:alice%%`, which everybody
+//! at the table sees anyway. Notes follow those insertions to stay on their card. A card
+//! with no note of its own can show what is public about it instead: the values it can
+//! still have.
+
+use crate::bot;
+use crate::{Game, Player};
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+/// What a player types to note something on a card, `/note 2 5 or 6`; the note stays
+/// with the client.
+pub const NOTE_COMMAND: &str = "/note";
+
+static INSERTED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"%%inserted:([\d,]*):([^%\n]*)%%\n?").unwrap());
+
+/// Where the cards saved by each player went, one marker per player.
+pub fn inserted_markers(game: &Game) -> String {
+    game.players
+        .iter()
+        .chain(game.lost_players.iter())
+        .map(|player| {
+            let positions: Vec<String> = player.inserted.iter().map(usize::to_string).collect();
+            format!("%%inserted:{}:{}%%\n", positions.join(","), player.name)
+        })
+        .collect()
+}
+
+/// Splits what the server sent into where the saved cards went, by player, and the rest.
+pub fn split_inserted(response: &str) -> (Vec<(String, Vec<usize>)>, String) {
+    let inserted = INSERTED
+        .captures_iter(response)
+        .map(|caps| {
+            let positions = caps[1]
+                .split(",")
+                .filter_map(|idx| idx.parse().ok())
+                .collect();
+            (caps[2].to_string(), positions)
+        })
+        .collect();
+
+    (inserted, INSERTED.replace_all(response, "").to_string())
+}
+
+/// The card and the note of a `/note` command, none for anything else.
+pub fn parse_note(msg: &str) -> Option<Result<(usize, String), String>> {
+    let rest = msg.trim().strip_prefix(NOTE_COMMAND)?;
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+
+    let (card_idx, note) = rest.trim().split_once(' ').unwrap_or((rest.trim(), ""));
+    Some(match card_idx.parse() {
+        Ok(card_idx) => Ok((card_idx, note.trim().to_string())),
+        Err(_) => Err(format!("Usage: {} <card> [note]", NOTE_COMMAND)),
+    })
+}
+
+#[derive(Debug, Default)]
+pub struct Notes {
+    hands: HashMap<String, Hand>,
+}
+
+#[derive(Debug, Default)]
+struct Hand {
+    /// By card, in the order of the hand
+    notes: Vec<String>,
+    /// How many saved cards the notes already made room for
+    followed: usize,
+}
+
+impl Notes {
+    /// The note on the card `card_idx` of `player`, empty when there is none.
+    pub fn get(&self, player: &str, card_idx: usize) -> &str {
+        self.hands
+            .get(player)
+            .and_then(|hand| hand.notes.get(card_idx))
+            .map_or("", String::as_str)
+    }
+
+    /// An empty `note` clears it.
+    pub fn set(&mut self, player: &str, card_idx: usize, note: &str) {
+        let hand = self.hands.entry(player.to_string()).or_default();
+        if hand.notes.len() <= card_idx {
+            hand.notes.resize(card_idx + 1, String::new());
+        }
+        hand.notes[card_idx] = note.trim().to_string();
+    }
+
+    /// Moves the notes of `player` along with the cards they saved, `inserted` being where
+    /// every card they saved went, as the server sends it.
+    pub fn follow(&mut self, player: &str, inserted: &[usize]) {
+        let hand = self.hands.entry(player.to_string()).or_default();
+
+        for card_idx in inserted.iter().skip(hand.followed) {
+            // past the last note, nothing moves
+            if *card_idx <= hand.notes.len() {
+                hand.notes.insert(*card_idx, String::new());
+            }
+        }
+        hand.followed = hand.followed.max(inserted.len());
+    }
+}
+
+/// What `me` can tell about the card `card_idx` of `opponent`: the values it can still
+/// have, those wrong guesses ruled out aside.
+pub fn public_note(
+    game: &Game,
+    me: &str,
+    opponent: &Player,
+    card_idx: usize,
+    ruled_out: &[u32],
+) -> String {
+    let values: Vec<u32> = bot::candidates(game, me, opponent, card_idx)
+        .into_iter()
+        .filter(|value| !ruled_out.contains(value))
+        .collect();
+
+    values_text(&values)
+}
+
+/// "0-3 7 9", runs of three values or more shortened.
+fn values_text(values: &[u32]) -> String {
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for value in values {
+        match runs.last_mut() {
+            Some((_, last)) if *last + 1 == *value => *last = *value,
+            _ => runs.push((*value, *value)),
+        }
+    }
+
+    let mut parts = Vec::new();
+    for (first, last) in runs {
+        match last - first {
+            0 => parts.push(first.to_string()),
+            1 => parts.push(format!("{} {}", first, last)),
+            _ => parts.push(format!("{}-{}", first, last)),
+        }
+    }
+
+    parts.join(" ")
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Card, CardStatus, Color};
+
+    #[test]
+    fn test_notes_follow_the_cards() {
+        let mut bob = Player::new(String::from("bob"), 3);
+        bob.deck = vec![
+            Card::new(2, Color::BLACK),
+            Card::new(5, Color::BLACK),
+            Card::new(8, Color::WHITE),
+        ];
+        let mut notes = Notes::default();
+        notes.set("bob", 0, "2 or 3");
+        notes.set("bob", 2, " 8? ");
+
+        // bob saves B4, then W10, the server sending all his insertions each time
+        for card in [Card::new(4, Color::BLACK), Card::new(10, Color::WHITE)] {
+            bob.side_card = Some(card);
+            bob.save_side_card(true);
+
+            let mut game = Game::new(24);
+            game.players.push(bob.clone());
+            let (inserted, rest) = split_inserted(&inserted_markers(&game));
+            assert_eq!(rest, "");
+            for (player, positions) in inserted.iter() {
+                notes.follow(player, positions);
+                // the same marker twice moves nothing
+                notes.follow(player, positions);
+            }
+        }
+
+        assert_eq!(bob.inserted, vec![1, 4]);
+        assert_eq!(notes.get("bob", 0), "2 or 3");
+        assert_eq!(notes.get("bob", 1), "");
+        assert_eq!(notes.get("bob", 3), "8?");
+        assert_eq!(notes.get("bob", 4), "");
+        assert_eq!(notes.get("alice", 0), "");
+    }
+
+    #[test]
+    fn test_parse_note() {
+        assert_eq!(
+            parse_note("/note 2 5 or 6 "),
+            Some(Ok((2, String::from("5 or 6"))))
+        );
+        assert_eq!(parse_note("/note 3"), Some(Ok((3, String::new()))));
+        assert!(matches!(parse_note("/note"), Some(Err(_))));
+        assert!(matches!(parse_note("/note x 5"), Some(Err(_))));
+        assert_eq!(parse_note("/notes 2"), None);
+        assert_eq!(parse_note("2"), None);
+    }
+
+    #[test]
+    fn test_public_note() {
+        let mut game = Game::new(24);
+        let mut me = Player::new(String::from("me"), 1);
+        me.deck = vec![Card::new(3, Color::BLACK)];
+        let mut bob = Player::new(String::from("bob"), 2);
+        bob.deck = vec![Card::new(0, Color::BLACK), Card::new(7, Color::BLACK)];
+        bob.deck[1].status = CardStatus::REVEALED;
+        game.players = vec![me, bob.clone()];
+
+        assert_eq!(public_note(&game, "me", &bob, 0, &[]), "0-2 4-6");
+        assert_eq!(public_note(&game, "me", &bob, 0, &[1, 5]), "0 2 4 6");
+    }
+}
@@ -0,0 +1,158 @@
+//! What each player sees of the others, for the TUI client's opponent panels.
+//!
+//! When a turn starts, the server sends every player one marker per opponent, the players
+//! out of the game included: `%%opponent:playing:1:0: B?, 1: W5, :bob%%`, with their
+//! status, whether it is their turn, their hand as `Player::show_hand` shows it to
+//! opponents, then their name.
+
+use crate::{ruled_out_from_str, Card, CardStatus, Color, Game, PlayerStatus};
+use regex::Regex;
+use std::sync::LazyLock;
+
+static OPPONENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"%%opponent:(playing|out):([01]):([^%]*):([^%:\n]*)%%\n?").unwrap()
+});
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Opponent {
+    pub name: String,
+    /// The hidden cards have no value
+    pub deck: Vec<Card>,
+    /// The values wrong guesses ruled out for each card
+    pub ruled_out: Vec<Vec<u32>>,
+    pub out: bool,
+    pub turn: bool,
+}
+
+impl Opponent {
+    pub fn hidden(&self) -> usize {
+        self.deck
+            .iter()
+            .filter(|card| card.status == CardStatus::HIDDEN)
+            .count()
+    }
+}
+
+/// The opponents of `me`, `turn` being the player whose turn starts.
+pub fn opponent_markers(game: &Game, me: &str, turn: &str) -> String {
+    game.players
+        .iter()
+        .chain(game.lost_players.iter())
+        .filter(|player| player.name != me)
+        .map(|player| {
+            let status = match player.status {
+                PlayerStatus::LOST => "out",
+                _ => "playing",
+            };
+            format!(
+                "%%opponent:{}:{}:{}:{}%%\n",
+                status,
+                (player.name == turn) as u8,
+                player.show_hand(true, false),
+                player.name
+            )
+        })
+        .collect()
+}
+
+/// The opponents of `me` as they would be told, for games with no server.
+pub fn of(game: &Game, me: &str, turn: &str) -> Vec<Opponent> {
+    split_opponents(&opponent_markers(game, me, turn)).0
+}
+
+/// Splits what the server sent into the opponents it tells about and the rest.
+pub fn split_opponents(response: &str) -> (Vec<Opponent>, String) {
+    let opponents = OPPONENT
+        .captures_iter(response)
+        .map(|caps| Opponent {
+            name: caps[4].to_string(),
+            deck: masked_deck(&caps[3]),
+            ruled_out: ruled_out_from_str(&caps[3]),
+            out: &caps[1] == "out",
+            turn: &caps[2] == "1",
+        })
+        .collect();
+
+    (opponents, OPPONENT.replace_all(response, "").to_string())
+}
+
+/// "0: B?, 1: W5, " as cards, the hidden ones with no value.
+fn masked_deck(hand: &str) -> Vec<Card> {
+    hand.split(",")
+        .filter_map(|card| card.split_once(":"))
+        .filter_map(|(_, card)| {
+            let card = card.trim();
+            let color = match card.get(0..1)? {
+                "B" => Color::BLACK,
+                "W" => Color::WHITE,
+                _ => return None,
+            };
+            let digits: String = card[1..].chars().take_while(char::is_ascii_digit).collect();
+
+            Some(match digits.parse() {
+                Ok(value) => Card {
+                    color,
+                    value,
+                    status: CardStatus::REVEALED,
+                },
+                Err(_) => Card::new(0, color),
+            })
+        })
+        .collect()
+}
+
+/// Puts what the server told about `opponents` in `known`, in the order they came.
+pub fn update(known: &mut Vec<Opponent>, opponents: Vec<Opponent>) {
+    for opponent in opponents {
+        match known.iter_mut().find(|other| other.name == opponent.name) {
+            Some(other) => *other = opponent,
+            None => known.push(opponent),
+        }
+    }
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Player;
+
+    #[test]
+    fn test_every_opponent_is_told() {
+        let mut game = Game::new(24);
+        for name in ["alice", "bob", "carol"] {
+            game.players.push(Player::new(String::from(name), 3));
+        }
+        game.init_set();
+        game.players[1].reveal_card(0);
+        game.players[2].status = PlayerStatus::LOST;
+        let carol = game.players.remove(2);
+        game.lost_players.push(carol);
+        game.players[1].side_card = Some(Card::new(99, Color::WHITE));
+        game.players[1].save_side_card(true);
+        game.guess("alice", "bob", 3, 98);
+
+        let (opponents, rest) = split_opponents(&opponent_markers(&game, "alice", "bob"));
+        assert_eq!(rest, "");
+        assert_eq!(opponents.len(), 2);
+
+        let bob = &opponents[0];
+        assert_eq!(bob.name, "bob");
+        assert!(bob.turn && !bob.out);
+        assert_eq!(bob.deck.len(), 4);
+        assert_eq!(bob.deck[0], game.players[1].deck[0]);
+        assert_eq!(bob.deck[3], Card::new(0, Color::WHITE));
+        assert_eq!(bob.hidden(), 3);
+        assert_eq!(bob.ruled_out[3], vec![98]);
+
+        assert_eq!(opponents[1].name, "carol");
+        assert!(opponents[1].out && !opponents[1].turn);
+
+        let mut known = vec![opponents[1].clone()];
+        update(&mut known, opponents);
+        assert_eq!(known[0].name, "carol");
+        assert_eq!(known[1].name, "bob");
+    }
+}
@@ -0,0 +1,43 @@
+//! The questions the server asks a player.
+//!
+//! Every question ends with `&&?&&`, the first one of a turn as well as the same one asked
+//! again after an answer that didn't fit, so a client knows an answer is expected now and
+//! anything else typed is only chat or hints.
+
+const MARKER: &str = "&&?&&";
+
+/// The question that asks which card of the pile to draw, by its position.
+pub const DRAW_QUESTION: &str = "Enter card number to draw it";
+
+/// A question, as sent to the player who has to answer it.
+pub fn question_line(question: &str) -> String {
+    format!("{}{}\n", question, MARKER)
+}
+
+/// Whether what the server sent asks us something, and the rest without the marker.
+pub fn split_prompt(response: &str) -> (bool, String) {
+    (response.contains(MARKER), response.replace(MARKER, ""))
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_questions_stand_apart() {
+        let response = format!(
+            "You picked a W3\n{}",
+            question_line("Keep it hidden? (y/n)")
+        );
+        let (asked, rest) = split_prompt(&response);
+        assert!(asked);
+        assert_eq!(rest, "You picked a W3\nKeep it hidden? (y/n)\n");
+
+        let (asked, rest) = split_prompt("Wait for your turn, bob");
+        assert!(!asked);
+        assert_eq!(rest, "Wait for your turn, bob");
+    }
+}
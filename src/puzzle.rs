@@ -0,0 +1,214 @@
+//! Deduction puzzles: one of the rival's hidden cards can only have one value, find it.
+//!
+//! A puzzle comes from a seed, so it can be played again or shared: the seed shuffles the
+//! pile, deals both hands and picks the rival cards that are already revealed. Deals with
+//! no forced card, or more than one, are skipped until one fits.
+
+use crate::bot;
+use crate::{card_name, Card, CardStatus, Game, Player};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+pub const ME: &str = "you";
+pub const RIVAL: &str = "rival";
+
+const SET_CARDS: u32 = 24;
+/// Chances of each rival card to be revealed
+const REVEALED: f64 = 0.4;
+
+pub struct Puzzle {
+    pub seed: u64,
+    /// The players are `ME` and `RIVAL`
+    pub game: Game,
+    /// From 1 to 5
+    pub difficulty: u32,
+    card_idx: usize,
+    value: u32,
+}
+
+impl Puzzle {
+    pub fn generate(seed: u64) -> Puzzle {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        loop {
+            let mut game = Game::with_seed(SET_CARDS, rng.random());
+            game.players = vec![
+                Player::new(String::from(ME), rng.random_range(4..=7)),
+                Player::new(String::from(RIVAL), rng.random_range(4..=7)),
+            ];
+            game.init_set();
+            for card in game.players[1].deck.iter_mut() {
+                if rng.random_bool(REVEALED) {
+                    card.status = CardStatus::REVEALED;
+                }
+            }
+
+            let rival = &game.players[1];
+            let hidden = hidden_cards(rival);
+            let forced: Vec<usize> = hidden
+                .iter()
+                .copied()
+                .filter(|idx| bot::candidates(&game, ME, rival, *idx).len() == 1)
+                .collect();
+            if hidden.len() < 2 || forced.len() != 1 {
+                continue;
+            }
+
+            let card_idx = forced[0];
+            let value = rival.deck[card_idx].value;
+            let difficulty = difficulty(&game, card_idx);
+            return Puzzle {
+                seed,
+                game,
+                difficulty,
+                card_idx,
+                value,
+            };
+        }
+    }
+
+    pub fn me(&self) -> &Player {
+        &self.game.players[0]
+    }
+
+    pub fn rival(&self) -> &Player {
+        &self.game.players[1]
+    }
+
+    /// Whether the card `card_idx` of the rival is forced to be `value`, and why not.
+    pub fn check(&self, card_idx: usize, value: u32) -> Result<(), String> {
+        let rival = self.rival();
+        match rival.deck.get(card_idx) {
+            Some(card) if card.status == CardStatus::HIDDEN => {}
+            Some(_) => return Err(format!("Card {} is already revealed.", card_idx)),
+            None => return Err(format!("The rival has no card {}.", card_idx)),
+        }
+
+        let candidates = bot::candidates(&self.game, ME, rival, card_idx);
+        if card_idx != self.card_idx {
+            let values: Vec<String> = candidates.iter().map(u32::to_string).collect();
+            return Err(format!(
+                "Card {} isn't forced, it could be {}.",
+                card_idx,
+                values.join(", ")
+            ));
+        }
+        match value == self.value {
+            true => Ok(()),
+            false => Err(format!("Card {} can't be {}.", card_idx, value)),
+        }
+    }
+
+    /// Why the forced card can only have its value.
+    pub fn explain(&self) -> String {
+        let rival = self.rival();
+        let card = rival.deck[self.card_idx];
+        let revealed = |idx: &usize| rival.deck[*idx].status == CardStatus::REVEALED;
+
+        let mut text = format!("Card {} is {}", self.card_idx, card_name(&card));
+        if let Some(before) = (0..self.card_idx).rev().find(revealed) {
+            text.push_str(&format!(", after {}", card_name(&rival.deck[before])));
+        }
+        if let Some(after) = (self.card_idx + 1..rival.deck.len()).find(revealed) {
+            text.push_str(&format!(", before {}", card_name(&rival.deck[after])));
+        }
+        let held: Vec<String> = self
+            .me()
+            .deck
+            .iter()
+            .filter(|other| other.color == card.color)
+            .map(card_name)
+            .collect();
+        if !held.is_empty() {
+            text.push_str(&format!(", and you hold {}", held.join(" ")));
+        }
+        text.push('.');
+
+        text
+    }
+}
+
+fn hidden_cards(player: &Player) -> Vec<usize> {
+    (0..player.deck.len())
+        .filter(|idx| player.deck[*idx].status == CardStatus::HIDDEN)
+        .collect()
+}
+
+/// More hidden cards to look through, values ruled out by your own hand rather than by the
+/// revealed cards, and other cards down to two values all make a puzzle harder.
+fn difficulty(game: &Game, card_idx: usize) -> u32 {
+    let rival = &game.players[1];
+    let hidden = hidden_cards(rival);
+
+    // what somebody holding no card could tell
+    let from_revealed = bot::candidates(game, "", rival, card_idx).len() as u32;
+    let close_call = hidden
+        .iter()
+        .filter(|idx| **idx != card_idx)
+        .any(|idx| bot::candidates(game, ME, rival, *idx).len() == 2);
+
+    let score = (hidden.len() as u32 - 2).min(2) + (from_revealed - 1).min(2) + close_call as u32;
+    1 + score.min(4)
+}
+
+/// A rival card as `ME` sees it: hidden, it has no value.
+pub fn masked(card: &Card) -> Card {
+    match card.status {
+        CardStatus::HIDDEN => Card::new(0, card.color),
+        CardStatus::REVEALED => *card,
+    }
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_puzzle() {
+        let puzzle = Puzzle::generate(42);
+        let again = Puzzle::generate(42);
+
+        assert_eq!(puzzle.game.players, again.game.players);
+        assert_eq!(puzzle.difficulty, again.difficulty);
+        assert_ne!(Puzzle::generate(43).game.players, puzzle.game.players);
+    }
+
+    #[test]
+    fn test_one_forced_card() {
+        for seed in 0..50 {
+            let puzzle = Puzzle::generate(seed);
+            let rival = puzzle.rival();
+
+            let forced: Vec<usize> = hidden_cards(rival)
+                .into_iter()
+                .filter(|idx| bot::candidates(&puzzle.game, ME, rival, *idx).len() == 1)
+                .collect();
+            assert_eq!(forced, vec![puzzle.card_idx]);
+            assert_eq!(rival.deck[puzzle.card_idx].value, puzzle.value);
+            assert!((1..=5).contains(&puzzle.difficulty));
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let puzzle = Puzzle::generate(7);
+        let rival = puzzle.rival();
+
+        assert_eq!(puzzle.check(puzzle.card_idx, puzzle.value), Ok(()));
+        assert!(puzzle.check(puzzle.card_idx, puzzle.value + 1).is_err());
+        assert!(puzzle.check(rival.deck.len(), 0).is_err());
+
+        let other = hidden_cards(rival)
+            .into_iter()
+            .find(|idx| *idx != puzzle.card_idx)
+            .unwrap();
+        let error = puzzle.check(other, rival.deck[other].value).unwrap_err();
+        assert!(error.contains("isn't forced"));
+        assert!(puzzle
+            .explain()
+            .starts_with(&format!("Card {} is ", puzzle.card_idx)));
+    }
+}
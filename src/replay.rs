@@ -0,0 +1,358 @@
+//! Plays a recorded game again, one move at a time.
+//!
+//! A `Replay` rebuilds the table after the deal and after every move of a `GameRecord`,
+//! checking on the way that the moves fit the cards. Each position, numbered from 0 for the
+//! deal, can then be looked at with every card showing, or as one of the players saw it.
+
+use crate::archive::GameRecord;
+use crate::history::GameEvent;
+use crate::notation;
+use crate::{card_cmp, card_name, Card, CardStatus, Color, Player, PlayerStatus};
+
+pub struct Replay {
+    pub record: GameRecord,
+    /// The players after the deal, then after each move
+    positions: Vec<Vec<Player>>,
+    /// The moves, without the deal
+    moves: Vec<GameEvent>,
+    /// The turn of each move, from 1
+    turns: Vec<usize>,
+    /// Every card of the set, sorted
+    set: Vec<Card>,
+}
+
+impl Replay {
+    pub fn new(record: GameRecord) -> Result<Replay, String> {
+        let mut players: Vec<Player> = Vec::new();
+        let mut moves: Vec<GameEvent> = Vec::new();
+        let mut turns = Vec::new();
+        for (event, turn) in record.events.iter().zip(notation::turns(&record.events)) {
+            if let GameEvent::DEAL { player, cards } = event {
+                if !moves.is_empty() {
+                    return Err(format!("{} was dealt after the first move", player));
+                }
+                let mut dealt = Player::new(player.clone(), cards.len() as u32);
+                dealt.deck = cards.clone();
+                dealt.deck.sort_by(card_cmp);
+                dealt.status = PlayerStatus::PLAYING;
+                players.push(dealt);
+            } else {
+                moves.push(event.clone());
+                turns.push(turn);
+            }
+        }
+        if players.is_empty() {
+            return Err(String::from("nobody was dealt any card"));
+        }
+
+        let mut set = Vec::new();
+        for value in 0..record.set_cards / 2 {
+            set.push(Card::new(value, Color::BLACK));
+            set.push(Card::new(value, Color::WHITE));
+        }
+
+        let mut positions = vec![players];
+        for (idx, event) in moves.iter().enumerate() {
+            let mut players = positions[idx].clone();
+            play(&mut players, &set, event)
+                .map_err(|reason| format!("move {} ({}): {}", idx + 1, event, reason))?;
+            positions.push(players);
+        }
+
+        Ok(Replay {
+            record,
+            positions,
+            moves,
+            turns,
+            set,
+        })
+    }
+
+    /// How many moves were played, the last position being this one.
+    pub fn moves(&self) -> usize {
+        self.moves.len()
+    }
+
+    /// The move that led to the position `step`, none for the deal.
+    pub fn event(&self, step: usize) -> Option<&GameEvent> {
+        self.moves.get(step.checked_sub(1)?)
+    }
+
+    /// The turn the position `step` is in, 0 for the deal.
+    pub fn turn(&self, step: usize) -> usize {
+        match step.checked_sub(1) {
+            Some(idx) => self.turns.get(idx).copied().unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    pub fn turn_count(&self) -> usize {
+        self.turns.last().copied().unwrap_or(0)
+    }
+
+    /// The position at the start of `turn`.
+    pub fn step_of_turn(&self, turn: usize) -> Option<usize> {
+        match turn {
+            0 => Some(0),
+            _ => self.turns.iter().position(|other| *other == turn),
+        }
+    }
+
+    /// The players at the position `step`, in their seats.
+    pub fn players(&self, step: usize) -> &[Player] {
+        &self.positions[step.min(self.moves())]
+    }
+
+    /// The cards of `name` as `viewer` saw them, every card when there's no viewer: the
+    /// cards they know show their value, the others are hidden.
+    pub fn hand(&self, step: usize, name: &str, viewer: Option<&str>) -> Vec<Card> {
+        let player = match self.players(step).iter().find(|p| p.name == name) {
+            Some(player) => player,
+            None => return Vec::new(),
+        };
+
+        player
+            .deck
+            .iter()
+            .map(|card| see(card, viewer.is_none() || viewer == Some(name)))
+            .collect()
+    }
+
+    /// What is left of the pile at the position `step`, sorted.
+    pub fn pile(&self, step: usize, viewer: Option<&str>) -> Vec<Card> {
+        pile(&self.set, self.players(step))
+            .iter()
+            .map(|card| see(card, viewer.is_none()))
+            .collect()
+    }
+
+    /// The move that led to the position `step`, as `viewer` saw it.
+    pub fn describe(&self, step: usize, viewer: Option<&str>) -> Option<String> {
+        let event = self.event(step)?;
+
+        Some(match (event, viewer) {
+            (GameEvent::DRAW { player, card }, Some(viewer)) if player != viewer => {
+                let color = match card.color {
+                    Color::BLACK => "black",
+                    Color::WHITE => "white",
+                };
+                format!("{} drew a {} card", player, color)
+            }
+            _ => event.to_string(),
+        })
+    }
+
+    /// Every hand and the pile at the position `step`, as `viewer` saw them.
+    pub fn table(&self, step: usize, viewer: Option<&str>) -> String {
+        let mut text = String::new();
+
+        for player in self.players(step).iter() {
+            let known = viewer.is_none() || viewer == Some(player.name.as_str());
+            let cards: Vec<String> = self
+                .hand(step, &player.name, viewer)
+                .iter()
+                .map(card_text)
+                .collect();
+            text.push_str(&format!("{}: {}", player.name, cards.join(" ")));
+
+            if let Some(card) = &player.side_card {
+                text.push_str(&format!(", drew {}", card_text(&see(card, known))));
+            }
+            if player
+                .deck
+                .iter()
+                .all(|card| card.status == CardStatus::REVEALED)
+            {
+                text.push_str(" (out)");
+            }
+            text.push('\n');
+        }
+        text.push_str(&format!("Pile: {} cards\n", self.pile(step, viewer).len()));
+
+        text
+    }
+}
+
+/// Plays one move on the players, if it fits.
+fn play(players: &mut [Player], set: &[Card], event: &GameEvent) -> Result<(), String> {
+    let pile = pile(set, players);
+    let name = match event {
+        GameEvent::GUESS { opponent, .. } => opponent,
+        _ => event.player(),
+    };
+    let player = players
+        .iter_mut()
+        .find(|player| player.name == *name)
+        .ok_or_else(|| format!("{} isn't at the table", name))?;
+
+    match event {
+        GameEvent::DEAL { .. } => return Err(String::from("dealt after the first move")),
+        GameEvent::DRAW { card, .. } => {
+            if !pile.contains(card) {
+                return Err(format!("{} isn't in the pile", card_name(card)));
+            }
+            player.side_card = Some(*card);
+        }
+        GameEvent::GUESS {
+            card_idx,
+            value,
+            right,
+            ..
+        } => {
+            let card = match player.deck.get(*card_idx) {
+                Some(card) if card.status == CardStatus::HIDDEN => card,
+                Some(_) => return Err(format!("{}'s card {} is revealed", name, card_idx)),
+                None => return Err(format!("{} has no card {}", name, card_idx)),
+            };
+            if (card.value == *value) != *right {
+                return Err(format!("the card is {}", card_name(card)));
+            }
+            if *right {
+                player.reveal_card(*card_idx);
+            } else {
+                player.rule_out(*card_idx, *value);
+            }
+        }
+        GameEvent::SAVE { hidden, .. } => {
+            if player.side_card.is_none() {
+                return Err(String::from("no card was drawn"));
+            }
+            player.save_side_card(*hidden);
+        }
+        GameEvent::FORFEIT { .. } => player.forfeit(),
+    }
+
+    Ok(())
+}
+
+/// The cards of the set nobody holds.
+fn pile(set: &[Card], players: &[Player]) -> Vec<Card> {
+    let held = |card: &&Card| {
+        players.iter().any(|player| {
+            player
+                .deck
+                .iter()
+                .chain(player.side_card.iter())
+                .any(|other| other.value == card.value && other.color == card.color)
+        })
+    };
+
+    set.iter().filter(|card| !held(card)).copied().collect()
+}
+
+/// `card` showing its value, or hidden with no value to give away.
+fn see(card: &Card, known: bool) -> Card {
+    match (known, card.status) {
+        (true, _) => Card {
+            status: CardStatus::REVEALED,
+            ..*card
+        },
+        (false, CardStatus::HIDDEN) => Card::new(0, card.color),
+        (false, CardStatus::REVEALED) => *card,
+    }
+}
+
+/// "W3", or "W?" when hidden.
+fn card_text(card: &Card) -> String {
+    match card.status {
+        CardStatus::REVEALED => card_name(card),
+        CardStatus::HIDDEN => card_name(card).replace(|c: char| c.is_ascii_digit(), "") + "?",
+    }
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::bot_game;
+
+    const RECORD: &str = "[Set \"24\"]
+
+alice: W0 B3 B10 W10
+bob: B7 B8 W9 B11
+
+1. alice+W3 alice>bob#2=9 ! alice>bob#0=6 x alice:show
+2. bob+B1 bob:hide
+3. alice:resign
+";
+
+    fn replay() -> Replay {
+        Replay::new(notation::parse(RECORD).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_positions() {
+        let replay = replay();
+
+        assert_eq!(replay.moves(), 7);
+        assert_eq!(replay.turn_count(), 3);
+        assert_eq!(replay.step_of_turn(2), Some(4));
+        assert_eq!(replay.turn(5), 2);
+        assert!(replay.event(0).is_none());
+
+        // alice guessed bob's W9
+        assert_eq!(replay.players(1)[1].deck[2].status, CardStatus::HIDDEN);
+        assert_eq!(replay.players(2)[1].deck[2].status, CardStatus::REVEALED);
+        assert_eq!(replay.pile(0, None).len(), 16);
+        assert_eq!(replay.pile(5, None).len(), 14);
+        assert!(replay
+            .table(7, None)
+            .starts_with("alice: W0 B3 W3 B10 W10 (out)\n"));
+    }
+
+    #[test]
+    fn test_perspective() {
+        let replay = replay();
+
+        // bob's hidden B1 is only known to bob
+        assert_eq!(
+            replay.table(6, Some("alice")),
+            "alice: W0 B3 W3 B10 W10\nbob: B? B? B? W9 B?\nPile: 14 cards\n"
+        );
+        assert_eq!(
+            replay.table(6, Some("bob")),
+            "alice: W? B? W3 B? W?\nbob: B1 B7 B8 W9 B11\nPile: 14 cards\n"
+        );
+        assert_eq!(
+            replay.hand(6, "bob", Some("alice"))[0],
+            Card::new(0, Color::BLACK)
+        );
+
+        assert_eq!(
+            replay.describe(5, Some("alice")).unwrap(),
+            "bob drew a black card"
+        );
+        assert_eq!(replay.describe(5, Some("bob")).unwrap(), "bob drew B1");
+        assert!(replay
+            .table(1, Some("bob"))
+            .contains("alice: W? B? B? W?, drew W?\n"));
+    }
+
+    #[test]
+    fn test_moves_that_dont_fit() {
+        for (from, to) in [
+            ("#2=9 !", "#2=9 x"),
+            ("#0=6", "#7=6"),
+            ("alice+W3", "alice+W0"),
+            ("bob:hide", "carol:hide"),
+        ] {
+            let record = notation::parse(&RECORD.replace(from, to)).unwrap();
+            assert!(Replay::new(record).is_err(), "{} accepted", to);
+        }
+    }
+
+    #[test]
+    fn test_bot_games_replay() {
+        for _ in 0..10 {
+            let game = bot_game();
+            let replay = Replay::new(GameRecord::of(&game, 0)).unwrap();
+            let last = replay.players(replay.moves());
+            for player in game.players.iter().chain(game.lost_players.iter()) {
+                let replayed = last.iter().find(|p| p.name == player.name).unwrap();
+                assert_eq!(replayed.deck, player.deck);
+            }
+        }
+    }
+}
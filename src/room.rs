@@ -0,0 +1,179 @@
+use crate::access::{Access, HostCommand};
+use crate::chat::Chat;
+use crate::conn::SlowPolicy;
+use crate::session::Sessions;
+use crate::spectate::Spectators;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+
+pub const DEFAULT_DECISION_SECS: u64 = 120;
+/// Who the host's announcements come from in the chat.
+pub const HOST_NAME: &str = "host";
+
+/// What happens to a player who doesn't answer in time or doesn't come back.
+#[derive(Debug, PartialEq, Clone, Copy, clap::ValueEnum)]
+pub enum IdlePolicy {
+    /// Draw a card and keep it hidden for them
+    AUTO,
+    /// Hand their seat to a bot until they reconnect
+    BOT,
+    /// Reveal all their cards, they're out
+    FORFEIT,
+}
+
+///
+///# The settings and shared state of the game a server is running
+///
+#[derive(Debug)]
+pub struct Room<S> {
+    pub sessions: Sessions<S>,
+    /// Time a player has for each decision, None waits forever
+    pub decision_timeout: Option<Duration>,
+    pub idle_policy: IdlePolicy,
+    pub slow_policy: SlowPolicy,
+    pub spectators: Arc<Spectators>,
+    pub chat: Arc<Chat>,
+    pub access: Access,
+    /// Where finished games go, see `archive`
+    pub archive: Option<PathBuf>,
+    /// No hints for the players, see `hint`
+    pub ranked: bool,
+    bot_seats: Mutex<HashSet<String>>,
+}
+
+impl<S> Room<S> {
+    pub fn new(
+        sessions: Sessions<S>,
+        decision_timeout: Option<Duration>,
+        idle_policy: IdlePolicy,
+        slow_policy: SlowPolicy,
+        spectators: Spectators,
+        access: Access,
+        archive: Option<PathBuf>,
+    ) -> Room<S> {
+        let spectators = Arc::new(spectators);
+
+        Room {
+            sessions,
+            decision_timeout,
+            idle_policy,
+            slow_policy,
+            chat: Arc::new(Chat::new(Arc::clone(&spectators))),
+            spectators,
+            access,
+            archive,
+            ranked: false,
+            bot_seats: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub async fn hand_to_bot(&self, name: &str) {
+        self.bot_seats.lock().await.insert(name.to_string());
+    }
+
+    pub async fn is_bot(&self, name: &str) -> bool {
+        self.bot_seats.lock().await.contains(name)
+    }
+
+    /// Takes the seat back from the bot, returns whether a bot was playing it.
+    pub async fn give_back(&self, name: &str) -> bool {
+        self.bot_seats.lock().await.remove(name)
+    }
+
+    /// Sends a player away on the host's request, and tells everyone.
+    pub async fn host_command(&self, command: HostCommand) {
+        let (name, what) = match &command {
+            HostCommand::KICK(name) => (name, "kicked"),
+            HostCommand::BAN(name) => (name, "banned"),
+        };
+
+        // their token goes too, or they could take their seat back
+        self.sessions.revoke(name).await;
+        match command {
+            HostCommand::KICK(_) => self.access.kick(name).await,
+            HostCommand::BAN(_) => self.access.ban(name).await,
+        }
+
+        self.chat
+            .say(HOST_NAME, &format!("{} was {} from the room", name, what))
+            .await;
+    }
+}
+
+impl IdlePolicy {
+    /// What the other players are told when the policy kicks in.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            IdlePolicy::AUTO => "playing a safe move for them",
+            IdlePolicy::BOT => "a bot takes over their seat",
+            IdlePolicy::FORFEIT => "they forfeit, all their cards get revealed",
+        }
+    }
+}
+
+/// A duration from a number of seconds given on the command line, 0 meaning none.
+pub fn secs_or_none(secs: u64) -> Option<Duration> {
+    if secs == 0 {
+        return None;
+    }
+
+    Some(Duration::from_secs(secs))
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bot_seats() {
+        let room: Room<u32> = Room::new(
+            Sessions::default(),
+            None,
+            IdlePolicy::BOT,
+            SlowPolicy::DISCONNECT,
+            Spectators::default(),
+            Access::default(),
+            None,
+        );
+
+        room.hand_to_bot("me").await;
+        assert!(room.is_bot("me").await);
+        assert!(!room.is_bot("other").await);
+
+        assert!(room.give_back("me").await);
+        assert!(!room.give_back("me").await);
+        assert!(!room.is_bot("me").await);
+    }
+
+    #[tokio::test]
+    async fn test_host_kicks() {
+        let room: Room<u32> = Room::new(
+            Sessions::default(),
+            None,
+            IdlePolicy::AUTO,
+            SlowPolicy::DISCONNECT,
+            Spectators::default(),
+            Access::default(),
+            None,
+        );
+        let token = room.sessions.issue("bob").await;
+
+        room.host_command(HostCommand::KICK(String::from("bob")))
+            .await;
+
+        assert!(room.access.is_kicked("bob").await);
+        assert_eq!(room.sessions.resume(&token, 1).await, Err(1));
+    }
+
+    #[test]
+    fn test_secs_or_none() {
+        assert_eq!(secs_or_none(0), None);
+        assert_eq!(secs_or_none(30), Some(Duration::from_secs(30)));
+    }
+}
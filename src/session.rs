@@ -0,0 +1,195 @@
+use rand::Rng;
+use std::collections::HashMap;
+use tokio::sync::{Mutex, Notify};
+use tokio::time::{timeout_at, Duration, Instant};
+
+/// Prefix of the message a client sends instead of its name to take back its seat.
+pub const RESUME_PREFIX: &str = "resume:";
+pub const DEFAULT_GRACE_SECS: u64 = 60;
+const TOKEN_BYTES: usize = 16;
+
+///
+///# Session tokens and pending reconnections of a running game
+///
+/// Every player gets a token when joining. A client whose connection dropped can
+/// reconnect and present that token; the new stream is parked here until the game
+/// loop picks it up and swaps it in place of the dead one.
+///
+#[derive(Debug)]
+pub struct Sessions<S> {
+    tokens: Mutex<HashMap<String, String>>,
+    pending: Mutex<HashMap<String, S>>,
+    arrived: Notify,
+    pub grace: Duration,
+}
+
+impl<S> Sessions<S> {
+    pub fn new(grace: Duration) -> Sessions<S> {
+        Sessions {
+            tokens: Mutex::new(HashMap::new()),
+            pending: Mutex::new(HashMap::new()),
+            arrived: Notify::new(),
+            grace,
+        }
+    }
+
+    /// Creates the session token of a player (replacing any previous one).
+    pub async fn issue(&self, name: &str) -> String {
+        let token = new_token();
+        let mut tokens = self.tokens.lock().await;
+
+        tokens.retain(|_, owner| owner != name);
+        tokens.insert(token.clone(), name.to_string());
+
+        token
+    }
+
+    /// Forgets the token of a player, who can't take their seat back anymore.
+    pub async fn revoke(&self, name: &str) {
+        self.tokens.lock().await.retain(|_, owner| owner != name);
+        self.pending.lock().await.remove(name);
+    }
+
+    /// The player a token was issued to.
+    pub async fn owner(&self, token: &str) -> Option<String> {
+        self.tokens.lock().await.get(token.trim()).cloned()
+    }
+
+    /// Parks the stream of a reconnecting client. Returns the player's name, or the
+    /// stream back if the token is unknown.
+    pub async fn resume(&self, token: &str, stream: S) -> Result<String, S> {
+        let name = match self.tokens.lock().await.get(token.trim()) {
+            Some(name) => name.clone(),
+            None => return Err(stream),
+        };
+
+        // a newer reconnection replaces an older one that wasn't picked up yet
+        self.pending.lock().await.insert(name.clone(), stream);
+        self.arrived.notify_waiters();
+
+        Ok(name)
+    }
+
+    /// Takes the reconnected stream of a player, if any, without waiting.
+    pub async fn take(&self, name: &str) -> Option<S> {
+        self.pending.lock().await.remove(name)
+    }
+
+    /// Waits up to the grace period for a player to reconnect.
+    pub async fn wait_for(&self, name: &str) -> Option<S> {
+        let deadline = Instant::now() + self.grace;
+
+        loop {
+            // register before checking, so a reconnection in between isn't missed
+            let arrived = self.arrived.notified();
+            if let Some(stream) = self.take(name).await {
+                return Some(stream);
+            }
+
+            if timeout_at(deadline, arrived).await.is_err() {
+                return None;
+            }
+        }
+    }
+}
+
+impl<S> Default for Sessions<S> {
+    fn default() -> Self {
+        Sessions::new(Duration::from_secs(DEFAULT_GRACE_SECS))
+    }
+}
+
+pub fn new_token() -> String {
+    let mut rng = rand::rng();
+
+    (0..TOKEN_BYTES)
+        .map(|_| format!("{:02x}", rng.random::<u8>()))
+        .collect()
+}
+
+/// Extracts the token from a `resume:<token>` message.
+pub fn parse_resume(msg: &str) -> Option<&str> {
+    let token = msg.trim().strip_prefix(RESUME_PREFIX)?.trim();
+    if token.is_empty() {
+        return None;
+    }
+
+    Some(token)
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_format() {
+        let token = new_token();
+
+        assert_eq!(token.len(), TOKEN_BYTES * 2);
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(token, new_token());
+    }
+
+    #[test]
+    fn test_parse_resume() {
+        assert_eq!(parse_resume("resume:abc12\n"), Some("abc12"));
+        assert_eq!(parse_resume("resume:"), None);
+        assert_eq!(parse_resume("alice"), None);
+    }
+
+    #[tokio::test]
+    async fn test_resume_known_token() {
+        let sessions: Sessions<u32> = Sessions::default();
+        let token = sessions.issue("me").await;
+
+        assert_eq!(sessions.resume(&token, 7).await, Ok(String::from("me")));
+        assert_eq!(sessions.take("me").await, Some(7));
+        assert_eq!(sessions.take("me").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_resume_unknown_token() {
+        let sessions: Sessions<u32> = Sessions::default();
+        let old_token = sessions.issue("me").await;
+        sessions.issue("me").await; // reissuing revokes the old token
+
+        assert_eq!(sessions.resume("nope", 7).await, Err(7));
+        assert_eq!(sessions.resume(&old_token, 8).await, Err(8));
+    }
+
+    #[tokio::test]
+    async fn test_revoked_token() {
+        let sessions: Sessions<u32> = Sessions::default();
+        let token = sessions.issue("me").await;
+        sessions.revoke("me").await;
+
+        assert_eq!(sessions.resume(&token, 7).await, Err(7));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_reconnect() {
+        let sessions: std::sync::Arc<Sessions<u32>> =
+            std::sync::Arc::new(Sessions::new(Duration::from_secs(5)));
+        let token = sessions.issue("me").await;
+
+        let waiter = {
+            let sessions = sessions.clone();
+            tokio::spawn(async move { sessions.wait_for("me").await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        sessions.resume(&token, 3).await.unwrap();
+
+        assert_eq!(waiter.await.unwrap(), Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_grace_expired() {
+        let sessions: Sessions<u32> = Sessions::new(Duration::from_millis(30));
+        sessions.issue("me").await;
+
+        assert_eq!(sessions.wait_for("me").await, None);
+    }
+}
@@ -0,0 +1,301 @@
+//! Spectators watch a game without playing: they get the public events of the game and a
+//! view of the table after every turn, and are never prompted for anything.
+//!
+//! The table has every hidden card masked. A server can instead show spectators the whole
+//! table with a delay, so that a teammate watching can't help a player. The host, who knows
+//! the key the server prints at startup, sees the whole table live.
+
+use crate::{session, Card, CardStatus, Color, Game, PlayerStatus};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep_until, Duration, Instant};
+
+/// What a client sends instead of its name to watch the game, optionally followed by
+/// `:<host key>`.
+pub const SPECTATE_HELLO: &str = "spectate";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum View {
+    /// Hidden cards masked, live
+    MASKED,
+    /// Everything revealed, late
+    DELAYED,
+    /// Everything revealed, live
+    HOST,
+}
+
+#[derive(Debug)]
+struct Seat {
+    view: View,
+    events: mpsc::UnboundedSender<(Instant, String)>,
+    writer: JoinHandle<()>,
+}
+
+///
+///# The spectators of a game
+///
+#[derive(Debug)]
+pub struct Spectators {
+    /// How late spectators get the revealed table, None keeps it masked
+    pub delay: Option<Duration>,
+    host_key: String,
+    seats: Mutex<Vec<Seat>>,
+    /// Last table sent (masked, revealed) for the ones joining mid-game
+    table: Mutex<(String, String)>,
+}
+
+impl Spectators {
+    pub fn new(delay: Option<Duration>) -> Spectators {
+        Spectators {
+            delay,
+            host_key: session::new_token(),
+            seats: Mutex::new(Vec::new()),
+            table: Mutex::new((String::new(), String::new())),
+        }
+    }
+
+    pub fn host_key(&self) -> &str {
+        &self.host_key
+    }
+
+    /// The view of a spectator who presented `key` (if any) when joining.
+    pub fn view_for(&self, key: Option<&str>) -> View {
+        if key.map(str::trim) == Some(self.host_key.as_str()) {
+            return View::HOST;
+        }
+
+        match self.delay {
+            Some(_) => View::DELAYED,
+            None => View::MASKED,
+        }
+    }
+
+    /// Starts sending the game to a new spectator, beginning with the current table.
+    /// Returns what the spectator sends, which only means something coming from the host.
+    pub async fn join<S>(&self, stream: S, view: View) -> mpsc::UnboundedReceiver<String>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let (mut reader, writer) = tokio::io::split(stream);
+        let (events, rx) = mpsc::unbounded_channel();
+
+        let delay = match view {
+            View::DELAYED => self.delay.unwrap_or_default(),
+            _ => Duration::ZERO,
+        };
+        let writer = tokio::spawn(write_events(writer, rx, delay));
+
+        // spectators are never prompted, the game only takes commands from the host
+        let (said, said_rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut buffer = [0u8; 1024];
+            while let Ok(bytes_read) = reader.read(&mut buffer).await {
+                if bytes_read == 0 {
+                    break;
+                }
+
+                let msg = String::from_utf8_lossy(&buffer[..bytes_read]).to_string();
+                let _ = said.send(msg);
+            }
+        });
+
+        let table = self.table.lock().await;
+        let current = match view {
+            View::MASKED => &table.0,
+            _ => &table.1,
+        };
+        if !current.is_empty() {
+            let _ = events.send((Instant::now(), current.clone()));
+        }
+
+        self.seats.lock().await.push(Seat {
+            view,
+            events,
+            writer,
+        });
+        said_rx
+    }
+
+    /// Sends an event every spectator may see.
+    pub async fn public(&self, event: &str) {
+        let now = Instant::now();
+
+        self.seats
+            .lock()
+            .await
+            .retain(|seat| seat.events.send((now, event.to_string())).is_ok());
+    }
+
+    /// Sends the table, `masked` or `revealed` depending on the view of each spectator.
+    pub async fn table(&self, masked: String, revealed: String) {
+        let now = Instant::now();
+
+        self.seats.lock().await.retain(|seat| {
+            let table = match seat.view {
+                View::MASKED => masked.clone(),
+                _ => revealed.clone(),
+            };
+            seat.events.send((now, table)).is_ok()
+        });
+
+        *self.table.lock().await = (masked, revealed);
+    }
+
+    /// Lets every spectator get what was sent so far (the delayed ones included), then
+    /// hangs up on them.
+    pub async fn finish(&self) {
+        let seats: Vec<Seat> = self.seats.lock().await.drain(..).collect();
+
+        for seat in seats {
+            drop(seat.events);
+            let _ = seat.writer.await;
+        }
+    }
+
+    pub async fn count(&self) -> usize {
+        self.seats.lock().await.len()
+    }
+}
+
+impl Default for Spectators {
+    fn default() -> Self {
+        Spectators::new(None)
+    }
+}
+
+/// The key of a spectate hello (`spectate` or `spectate:<key>`), None if it isn't one.
+pub fn parse_spectate(msg: &str) -> Option<Option<&str>> {
+    let rest = msg.trim().strip_prefix(SPECTATE_HELLO)?;
+
+    if rest.is_empty() {
+        return Some(None);
+    }
+
+    rest.strip_prefix(':').map(Some)
+}
+
+/// Every player's cards (the ones out too), with the hidden ones masked unless `revealed`.
+pub fn table_view(game: &Game, revealed: bool) -> String {
+    let mut view = String::new();
+
+    for player in game.players.iter().chain(game.lost_players.iter()) {
+        view.push_str(&player.name);
+        if player.status == PlayerStatus::LOST {
+            view.push_str(" (out)");
+        }
+        view.push_str(": ");
+        view.push_str(&player.show_hand(!revealed, false));
+
+        if let Some(card) = player.side_card {
+            view.push_str(" drawn: ");
+            view.push_str(&card_view(&card, revealed));
+        }
+        view.push('\n');
+    }
+    view.push_str(format!("Cards left in the pile: {}\n", game.card_avail.len()).as_str());
+
+    view
+}
+
+fn card_view(card: &Card, revealed: bool) -> String {
+    let color = match card.color {
+        Color::BLACK => "B",
+        Color::WHITE => "W",
+    };
+
+    if revealed || card.status == CardStatus::REVEALED {
+        format!("{}{}", color, card.value)
+    } else {
+        format!("{}?", color)
+    }
+}
+
+async fn write_events<W>(
+    mut writer: W,
+    mut events: mpsc::UnboundedReceiver<(Instant, String)>,
+    delay: Duration,
+) where
+    W: AsyncWrite + Unpin,
+{
+    while let Some((at, event)) = events.recv().await {
+        sleep_until(at + delay).await;
+
+        if writer.write_all(event.as_bytes()).await.is_err() || writer.flush().await.is_err() {
+            // dropping the receiver lets the next send notice the spectator is gone
+            return;
+        }
+    }
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Player;
+
+    #[test]
+    fn test_parse_spectate() {
+        assert_eq!(parse_spectate("spectate"), Some(None));
+        assert_eq!(parse_spectate("spectate:abc\n"), Some(Some("abc")));
+        assert_eq!(parse_spectate("spectator"), None);
+        assert_eq!(parse_spectate("alice"), None);
+    }
+
+    #[test]
+    fn test_view_for() {
+        let live = Spectators::new(None);
+        assert_eq!(live.view_for(None), View::MASKED);
+        assert_eq!(live.view_for(Some("wrong")), View::MASKED);
+        assert_eq!(live.view_for(Some(live.host_key())), View::HOST);
+
+        let late = Spectators::new(Some(Duration::from_secs(30)));
+        assert_eq!(late.view_for(None), View::DELAYED);
+    }
+
+    #[test]
+    fn test_table_view_masks() {
+        let mut game = Game::new(24);
+        let mut p1 = Player::new(String::from("alice"), 2);
+        p1.deck = vec![Card::new(3, Color::BLACK), Card::new(5, Color::WHITE)];
+        p1.deck[1].status = CardStatus::REVEALED;
+        p1.side_card = Some(Card::new(7, Color::BLACK));
+        game.players.push(p1);
+
+        assert_eq!(
+            table_view(&game, false),
+            "alice: 0: B?, 1: W5,  drawn: B?\nCards left in the pile: 0\n"
+        );
+        assert!(table_view(&game, true).contains("0: B3, 1: W5,  drawn: B7"));
+    }
+
+    #[tokio::test]
+    async fn test_spectator_gets_table_and_events() {
+        let spectators = Spectators::default();
+        spectators
+            .table(String::from("masked\n"), String::from("revealed\n"))
+            .await;
+
+        let (server, mut client) = tokio::io::duplex(1024);
+        spectators.join(server, View::MASKED).await;
+        spectators.public("alice guessed\n").await;
+
+        let mut received = String::new();
+        while !received.contains("alice guessed") {
+            let mut buffer = [0u8; 1024];
+            let bytes_read = client.read(&mut buffer).await.unwrap();
+            received.push_str(&String::from_utf8_lossy(&buffer[..bytes_read]));
+        }
+        assert_eq!(received, "masked\nalice guessed\n");
+        assert_eq!(spectators.count().await, 1);
+
+        // gone spectators are dropped on the next send
+        drop(client);
+        spectators.public("x\n").await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        spectators.public("y\n").await;
+        assert_eq!(spectators.count().await, 0);
+    }
+}
@@ -0,0 +1,270 @@
+//! Optional TLS between clients and servers.
+//!
+//! The server is given a certificate and its key. A client trusts the server either
+//! through a trust anchor (the certificate itself, or the CA that signed it) or by pinning
+//! the SHA-256 fingerprint of the certificate, which suits self-signed LAN setups.
+
+use crate::transport::Stream;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{self, CryptoProvider};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{
+    self, ClientConfig, DigitallySignedStruct, RootCertStore, ServerConfig, SignatureScheme,
+};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+pub const CERT_FILE: &str = "cert.pem";
+pub const KEY_FILE: &str = "key.pem";
+
+/// How a client decides to trust the server.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Trust {
+    /// PEM file of the server certificate or of its CA
+    ANCHOR(PathBuf),
+    /// SHA-256 fingerprint of the server certificate, in hex
+    PINNED(String),
+}
+
+impl Trust {
+    /// From the client's command line, None meaning plain TCP.
+    pub fn from_args(ca: Option<PathBuf>, fingerprint: Option<String>) -> Option<Trust> {
+        match (ca, fingerprint) {
+            (_, Some(fingerprint)) => Some(Trust::PINNED(fingerprint)),
+            (Some(ca), None) => Some(Trust::ANCHOR(ca)),
+            (None, None) => None,
+        }
+    }
+}
+
+/// A self-signed certificate valid for `names` (host names or IPs), and its key, in PEM.
+pub fn self_signed(names: &[String]) -> io::Result<(String, String)> {
+    let certified = rcgen::generate_simple_self_signed(names.to_vec()).map_err(invalid)?;
+
+    Ok((certified.cert.pem(), certified.key_pair.serialize_pem()))
+}
+
+/// Writes a self-signed pair as `cert.pem` and `key.pem` in `dir`, returns its fingerprint.
+pub fn write_self_signed(dir: &Path, names: &[String]) -> io::Result<String> {
+    let (cert, key) = self_signed(names)?;
+
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join(CERT_FILE), &cert)?;
+    fs::write(dir.join(KEY_FILE), key)?;
+
+    pem_fingerprint(&cert)
+}
+
+/// SHA-256 of a certificate, in lowercase hex.
+pub fn fingerprint(cert: &CertificateDer) -> String {
+    Sha256::digest(cert.as_ref())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Fingerprint of the first certificate of a PEM file's content.
+pub fn pem_fingerprint(pem: &str) -> io::Result<String> {
+    let cert = CertificateDer::from_pem_slice(pem.as_bytes()).map_err(invalid)?;
+
+    Ok(fingerprint(&cert))
+}
+
+/// Fingerprint of the first certificate of a PEM file.
+pub fn file_fingerprint(path: &Path) -> io::Result<String> {
+    pem_fingerprint(&fs::read_to_string(path)?)
+}
+
+/// TLS for a server, from the PEM files of its certificate (chain) and key.
+pub fn acceptor(cert: &Path, key: &Path) -> io::Result<TlsAcceptor> {
+    let certs = CertificateDer::pem_file_iter(cert)
+        .map_err(invalid)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(invalid)?;
+    let key = PrivateKeyDer::from_pem_file(key).map_err(invalid)?;
+
+    let config = ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(invalid)?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(invalid)?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// TLS for a client.
+pub fn connector(trust: &Trust) -> io::Result<TlsConnector> {
+    let builder = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(invalid)?;
+
+    let config = match trust {
+        Trust::ANCHOR(path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in CertificateDer::pem_file_iter(path).map_err(invalid)? {
+                roots.add(cert.map_err(invalid)?).map_err(invalid)?;
+            }
+
+            builder.with_root_certificates(roots).with_no_client_auth()
+        }
+        Trust::PINNED(fingerprint) => builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(PinnedCert::new(fingerprint)))
+            .with_no_client_auth(),
+    };
+
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+/// Runs the server side of the handshake if the server has TLS on.
+pub async fn accept(stream: TcpStream, tls: Option<&TlsAcceptor>) -> io::Result<Stream> {
+    match tls {
+        Some(tls) => Ok(Box::new(tls.accept(stream).await?)),
+        None => Ok(Box::new(stream)),
+    }
+}
+
+/// Connects to `address` (host:port), over TLS if the client has it on.
+pub async fn connect(address: &str, tls: Option<&TlsConnector>) -> io::Result<Stream> {
+    let stream = TcpStream::connect(address).await?;
+
+    let tls = match tls {
+        Some(tls) => tls,
+        None => return Ok(Box::new(stream)),
+    };
+
+    let host = match address.rsplit_once(':') {
+        Some((host, _)) => host.trim_start_matches('[').trim_end_matches(']'),
+        None => address,
+    };
+    let server_name = ServerName::try_from(host.to_string()).map_err(invalid)?;
+
+    Ok(Box::new(tls.connect(server_name, stream).await?))
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(crypto::ring::default_provider())
+}
+
+fn invalid<E: std::fmt::Display>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+/// Trusts exactly one certificate, whatever name or CA it has.
+#[derive(Debug)]
+struct PinnedCert {
+    fingerprint: String,
+    provider: Arc<CryptoProvider>,
+}
+
+impl PinnedCert {
+    fn new(fingerprint: &str) -> PinnedCert {
+        // accept fingerprints as printed by most tools (AB:CD:...)
+        let fingerprint = fingerprint.replace(':', "").trim().to_lowercase();
+
+        PinnedCert {
+            fingerprint,
+            provider: provider(),
+        }
+    }
+}
+
+impl ServerCertVerifier for PinnedCert {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if fingerprint(end_entity) == self.fingerprint {
+            return Ok(ServerCertVerified::assertion());
+        }
+
+        Err(rustls::Error::General(String::from(
+            "The server certificate doesn't match the pinned fingerprint",
+        )))
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trust_from_args() {
+        assert_eq!(Trust::from_args(None, None), None);
+        assert_eq!(
+            Trust::from_args(Some(PathBuf::from("ca.pem")), None),
+            Some(Trust::ANCHOR(PathBuf::from("ca.pem")))
+        );
+        assert_eq!(
+            Trust::from_args(None, Some(String::from("ab"))),
+            Some(Trust::PINNED(String::from("ab")))
+        );
+    }
+
+    #[test]
+    fn test_fingerprint_of_self_signed() {
+        let (cert, key) = self_signed(&[String::from("localhost")]).unwrap();
+
+        assert!(cert.starts_with("-----BEGIN CERTIFICATE-----"));
+        assert!(key.contains("PRIVATE KEY"));
+
+        let fingerprint = pem_fingerprint(&cert).unwrap();
+        assert_eq!(fingerprint.len(), 64);
+        assert_eq!(pem_fingerprint(&cert).unwrap(), fingerprint);
+    }
+
+    #[test]
+    fn test_pinned_accepts_colons() {
+        assert_eq!(PinnedCert::new("AB:cd:01").fingerprint, "abcd01");
+    }
+}
@@ -0,0 +1,175 @@
+//! What a connection to a server can run over.
+//!
+//! The game only ever talks to a `Stream`, so a server runs the same over TCP, TLS, a
+//! WebSocket bridge, a Unix domain socket or an in-memory pipe. The servers live in their
+//! binaries, so only their tests host one in-process, through `local` and `pipe`.
+
+use crate::tls;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use tokio::io::{duplex, AsyncRead, AsyncWrite, DuplexStream};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc;
+use tokio_rustls::TlsConnector;
+
+const PIPE_BUFFER_BYTES: usize = 64 * 1024;
+
+/// Any byte stream a client can be connected through (TCP, a WebSocket bridge, ...).
+pub trait Transport: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Transport for T {}
+
+/// A connection, whatever it runs over.
+pub type Stream = Box<dyn Transport>;
+
+/// An in-memory connection: the server's end, and the client's.
+pub fn pipe() -> (Stream, DuplexStream) {
+    let (server, client) = duplex(PIPE_BUFFER_BYTES);
+
+    (Box::new(server), client)
+}
+
+/// Where a client finds the server.
+#[derive(Debug, Clone)]
+pub enum Endpoint {
+    /// host:port
+    TCP(String),
+    /// Path of the socket
+    UNIX(PathBuf),
+    /// A server running in the same process, in the servers' tests
+    LOCAL(LocalConnector),
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Endpoint::TCP(address) => write!(f, "{}", address),
+            Endpoint::UNIX(path) => write!(f, "{}", path.display()),
+            Endpoint::LOCAL(_) => write!(f, "this process"),
+        }
+    }
+}
+
+/// Connects to a server, over TLS if the client has it on (TCP only).
+pub async fn connect(endpoint: &Endpoint, tls: Option<&TlsConnector>) -> io::Result<Stream> {
+    match endpoint {
+        Endpoint::TCP(address) => tls::connect(address, tls).await,
+        Endpoint::UNIX(path) => Ok(Box::new(UnixStream::connect(path).await?)),
+        Endpoint::LOCAL(connector) => Ok(Box::new(connector.connect()?)),
+    }
+}
+
+/// Where a server gets its connections from, besides TCP.
+#[derive(Debug)]
+pub enum Listener {
+    UNIX(UnixListener),
+    LOCAL(mpsc::UnboundedReceiver<Stream>),
+}
+
+impl Listener {
+    /// Listens on a Unix domain socket, replacing the socket a previous server left.
+    pub fn bind_unix(path: &Path) -> io::Result<Listener> {
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            if metadata.file_type().is_socket() {
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(Listener::UNIX(UnixListener::bind(path)?))
+    }
+
+    /// The next client, None once nobody can connect anymore.
+    pub async fn accept(&mut self) -> Option<Stream> {
+        match self {
+            Listener::UNIX(listener) => {
+                let (stream, _) = listener.accept().await.ok()?;
+                Some(Box::new(stream))
+            }
+            Listener::LOCAL(incoming) => incoming.recv().await,
+        }
+    }
+}
+
+/// Connects clients to a server running in the same process.
+#[derive(Debug, Clone)]
+pub struct LocalConnector {
+    outgoing: mpsc::UnboundedSender<Stream>,
+}
+
+impl LocalConnector {
+    /// The client's end of a new connection.
+    pub fn connect(&self) -> io::Result<DuplexStream> {
+        let (server, client) = pipe();
+
+        self.outgoing
+            .send(server)
+            .map_err(|_| io::Error::new(io::ErrorKind::ConnectionRefused, "The server is gone"))?;
+
+        Ok(client)
+    }
+}
+
+/// An in-process server's listener, and what its clients connect with. No binary hosts a
+/// server this way, the servers' tests do.
+pub fn local() -> (Listener, LocalConnector) {
+    let (outgoing, incoming) = mpsc::unbounded_channel();
+
+    (Listener::LOCAL(incoming), LocalConnector { outgoing })
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_pipe_both_ways() {
+        let (mut server, mut client) = pipe();
+        let mut buffer = [0u8; 16];
+
+        client.write_all(b"init").await.unwrap();
+        let bytes_read = server.read(&mut buffer).await.unwrap();
+        assert_eq!(&buffer[..bytes_read], b"init");
+
+        server.write_all(b"Init successfull").await.unwrap();
+        let bytes_read = client.read(&mut buffer).await.unwrap();
+        assert_eq!(&buffer[..bytes_read], b"Init successfull");
+    }
+
+    async fn roundtrip(mut listener: Listener, endpoint: Endpoint) {
+        let mut client = connect(&endpoint, None).await.unwrap();
+        let mut server = listener.accept().await.unwrap();
+        let mut buffer = [0u8; 16];
+
+        client.write_all(b"init").await.unwrap();
+        let bytes_read = server.read(&mut buffer).await.unwrap();
+        assert_eq!(&buffer[..bytes_read], b"init");
+    }
+
+    #[tokio::test]
+    async fn test_local_transport() {
+        let (listener, connector) = local();
+
+        roundtrip(listener, Endpoint::LOCAL(connector.clone())).await;
+        // the server is gone with its listener
+        assert!(connector.connect().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unix_transport() {
+        let path = std::env::temp_dir().join(format!("davincicode-{}.sock", std::process::id()));
+
+        // a socket left behind doesn't keep a new server from starting
+        drop(Listener::bind_unix(&path).unwrap());
+        let listener = Listener::bind_unix(&path).unwrap();
+
+        roundtrip(listener, Endpoint::UNIX(path.clone())).await;
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -1,33 +1,98 @@
 use regex::Regex;
 use std::error::Error;
-use std::io::{self};
+use std::io::{self, Stdout};
+use std::path::PathBuf;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
 use tokio::time::{self, sleep, Duration};
 
 use crossterm::event::poll;
-use crossterm::event::Event::Key;
+use crossterm::event::Event::{Key, Mouse};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, KeyCode},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, KeyCode, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::prelude::{
-    Alignment, Backend, Constraint, CrosstermBackend, Direction, Frame, Layout, Span,
+    Alignment, Backend, Constraint, CrosstermBackend, Direction, Frame, Layout, Line, Position,
+    Rect, Span,
 };
 use ratatui::widgets::*;
 
 use ratatui::Terminal;
 
+use davincicode::access;
+use davincicode::archive;
+use davincicode::bot::Difficulty;
+use davincicode::chat;
+use davincicode::fairness;
+use davincicode::hint;
+use davincicode::history::GameEvent;
+use davincicode::local::{LocalGame, Step};
+use davincicode::logs::{Category, Logs};
+use davincicode::notes::{self, Notes};
+use davincicode::opponents::{self, Opponent};
+use davincicode::prompt;
+use davincicode::puzzle::{self, Puzzle};
+use davincicode::replay::Replay;
+use davincicode::session::RESUME_PREFIX;
+use davincicode::spectate::SPECTATE_HELLO;
+use davincicode::tls::{self, Trust};
+use davincicode::transport::{self, Endpoint, Stream};
+use tokio_rustls::TlsConnector;
+
 use clap::Parser;
 
+const RECONNECT_ATTEMPTS: u32 = 20;
+const RECONNECT_DELAY_SECS: u64 = 3;
+/// Cards in the set the servers play with
+const SET_CARDS: u32 = 24;
+/// Opponent panels side by side down to this width, one above the other under it
+const PANEL_MIN_WIDTH: u16 = 30;
+const PANEL_HEIGHT: u16 = 5;
+/// Cells of the grid the opponent cards, the pile or the values are picked from
+const GRID_ROWS: usize = 4;
+const GRID_COLUMNS: usize = 4;
+/// Who plays against the bots when no name is given
+const PRACTICE_NAME: &str = "you";
+
 /// The client to the davinci code game
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// User name
-    #[arg(short, long)]
-    name: String,
+    /// User name ("you" when practicing)
+    #[arg(short, long,
+        required_unless_present_any = ["spectate", "local", "practice", "replay", "puzzle"])]
+    name: Option<String>,
+
+    /// Play on this terminal without a server, taking turns with these players
+    #[arg(short, long, num_args = 2.., value_name = "NAMES",
+        conflicts_with_all = ["spectate", "code", "unix", "tls_ca", "tls_fingerprint"])]
+    local: Vec<String>,
+
+    /// Practice on this terminal without a server, against bots
+    #[arg(long, conflicts_with_all = ["local", "spectate", "code", "unix", "tls_ca", "tls_fingerprint"])]
+    practice: bool,
+
+    /// Step through a saved game, in notation or JSON, instead of playing
+    #[arg(long, value_name = "FILE",
+        conflicts_with_all = ["local", "practice", "spectate", "code", "unix", "tls_ca", "tls_fingerprint"])]
+    replay: Option<PathBuf>,
+
+    /// Find the forced card of a deduction puzzle, from this seed or from a random one
+    #[arg(long, value_name = "SEED", num_args = 0..=1,
+        conflicts_with_all = ["local", "practice", "spectate", "replay", "code", "unix", "tls_ca", "tls_fingerprint"])]
+    puzzle: Option<Option<u64>>,
+
+    /// Number of bots to practice against
+    #[arg(long, default_value_t = 1, requires = "practice")]
+    bots: usize,
+
+    /// How well the bots play
+    #[arg(long, value_enum, default_value_t = Difficulty::NORMAL, requires = "practice")]
+    difficulty: Difficulty,
 
     /// Server address
     #[arg(short, long, default_value_t = String::from("127.0.0.1"))]
@@ -36,27 +101,95 @@ struct Args {
     /// Server port
     #[arg(short, long, default_value_t = String::from("8079"))]
     port: String,
+
+    /// Watch the game instead of playing
+    #[arg(short, long)]
+    spectate: bool,
+
+    /// Host key printed by the server, to watch with every card revealed
+    #[arg(short = 'k', long, requires = "spectate")]
+    host_key: Option<String>,
+
+    /// Password or invite code of the room
+    #[arg(short, long, conflicts_with = "spectate")]
+    code: Option<String>,
+
+    /// Unix domain socket of a server on this machine, instead of its address and port
+    #[arg(short, long, conflicts_with_all = ["tls_ca", "tls_fingerprint"])]
+    unix: Option<PathBuf>,
+
+    /// Connect over TLS, trusting the server certificate (or its CA) in this PEM file
+    #[arg(long, conflicts_with = "tls_fingerprint")]
+    tls_ca: Option<PathBuf>,
+
+    /// Connect over TLS, trusting the server certificate with this SHA-256 fingerprint
+    #[arg(long)]
+    tls_fingerprint: Option<String>,
 }
 
 pub enum InputMode {
     Normal,
     Message,
+    /// Picking on the grid, with the arrows or hjkl
+    Select,
+    /// Typing what the log entries should have in them, after `/`
+    Search,
+}
+
+/// What the server asks us to pick on the grid.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Picking {
+    Nothing,
+    /// A card of the pile, to draw
+    Pile,
+    /// A hidden card of the opponent, to guess
+    Card,
+    /// The value of that card, up to this one
+    Value(u32),
 }
 
 pub struct App {
-    pub logs: String,
+    pub logs: Logs,
     pub input: String,
     pub name: String,
     pub message: String,
     pub mode: InputMode,
     pub player: davincicode::Player,
     pub tmp_deck: Vec<davincicode::Card>,
+    /// The values wrong guesses ruled out for each card of `tmp_deck`
+    pub tmp_ruled_out: Vec<Vec<u32>>,
     pub opp_deck: Vec<davincicode::Card>,
     pub log_scroll: u16,
+    /// Lines of the log last drawn, a page
+    pub log_height: u16,
+    pub token: Option<String>,
+    pub spectating: bool,
+    pub table: String,
+    pub chat: Vec<String>,
+    /// Hot seat: hide everything until this player sits down
+    pub pass_to: Option<String>,
+    /// The server's commitment to the seed of the shuffles
+    pub commit: Option<String>,
+    /// A reveal that didn't fit in one read
+    pub reveal: String,
+    /// Where we drew, to check the log of the pile against
+    pub picks: fairness::Picks,
+    /// Whose hand `tmp_deck` is, none for the pile
+    pub opponent: Option<String>,
+    pub notes: Notes,
+    /// Cards with no note show the values they can still have
+    pub auto_notes: bool,
+    pub picking: Picking,
+    /// The cell of the grid picked so far
+    pub selected: usize,
+    /// The card we guess the value of
+    pub guessed_card: Option<usize>,
+    /// Where the cells of the grid were last drawn, for the mouse
+    pub grid_cells: Vec<Rect>,
+    /// Everybody else at the table, in the order the server first told about them
+    pub opponents: Vec<Opponent>,
 }
 
-const MAX_SCROLL: u16 = 65535;
-
 impl App {
     pub fn new(name: String, ncards: u32) -> App {
         let none_deck: Vec<davincicode::Card> = Vec::new();
@@ -65,104 +198,422 @@ impl App {
             input: String::new(),
             message: String::new(),
             name: String::new(),
-            logs: String::new(),
+            logs: Logs::new(),
             mode: InputMode::Normal,
             player: davincicode::Player::new(name, ncards),
             tmp_deck: none_deck.clone(),
+            tmp_ruled_out: Vec::new(),
             opp_deck: none_deck.clone(),
             log_scroll: 0,
+            log_height: 0,
+            token: None,
+            spectating: false,
+            table: String::new(),
+            chat: Vec::new(),
+            pass_to: None,
+            commit: None,
+            reveal: String::new(),
+            picks: fairness::Picks::default(),
+            opponent: None,
+            notes: Notes::default(),
+            auto_notes: false,
+            picking: Picking::Nothing,
+            selected: 0,
+            guessed_card: None,
+            grid_cells: Vec::new(),
+            opponents: Vec::new(),
         }
     }
     pub fn clear_msg_filed(&mut self) {
         self.input.clear();
     }
 
-    pub fn log_add_top(&mut self, new_log: String) {
-        let tmp = &self.logs;
-        self.logs = new_log + "\n_____________________________\nOld msg\n" + tmp;
+    /// Notes `note` on the card `card_idx` of the opponent shown.
+    pub fn take_note(&mut self, card_idx: usize, note: &str) -> String {
+        let opponent = match &self.opponent {
+            Some(opponent) => opponent.clone(),
+            None => return String::from("Pick an opponent first, notes go on their cards."),
+        };
+        match self.tmp_deck.get(card_idx) {
+            Some(card) if card.status == davincicode::CardStatus::HIDDEN => {}
+            _ => return format!("{} has no hidden card {}.", opponent, card_idx),
+        }
+
+        self.notes.set(&opponent, card_idx, note);
+        match note.is_empty() {
+            true => format!("Note on {}'s card {} cleared.", opponent, card_idx),
+            false => format!("Noted on {}'s card {}.", opponent, card_idx),
+        }
+    }
+
+    /// The note shown on the card `card_idx` of the grid, if any.
+    fn note(&self, card_idx: usize) -> Option<(String, bool)> {
+        let opponent = self.opponent.as_ref()?;
+        if self.tmp_deck.get(card_idx)?.status != davincicode::CardStatus::HIDDEN {
+            return None;
+        }
+
+        let note = self.notes.get(opponent, card_idx);
+        if !note.is_empty() {
+            return Some((note.to_string(), true));
+        }
+        if !self.auto_notes {
+            return None;
+        }
+
+        // what we know of the table: our cards and that hand
+        let mut owner = davincicode::Player::new(opponent.clone(), 0);
+        owner.deck = self.tmp_deck.clone();
+        let mut game = davincicode::Game::with_seed(SET_CARDS, [0; 32]);
+        game.players = vec![self.player.clone(), owner.clone()];
+        let ruled_out = self
+            .tmp_ruled_out
+            .get(card_idx)
+            .cloned()
+            .unwrap_or_default();
+
+        Some((
+            notes::public_note(&game, &self.player.name, &owner, card_idx, &ruled_out),
+            false,
+        ))
+    }
+
+    /// Whether the cell `idx` of the grid may be picked: no revealed card, no value a wrong
+    /// guess ruled out.
+    pub fn selectable(&self, idx: usize) -> bool {
+        match self.picking {
+            Picking::Nothing => false,
+            Picking::Pile => idx < self.tmp_deck.len(),
+            Picking::Card => self
+                .tmp_deck
+                .get(idx)
+                .is_some_and(|card| card.status == davincicode::CardStatus::HIDDEN),
+            Picking::Value(max) => idx as u32 <= max && !self.ruled_out(idx as u32),
+        }
+    }
+
+    /// Whether a wrong guess ruled `value` out for the card we guess.
+    fn ruled_out(&self, value: u32) -> bool {
+        self.guessed_card
+            .and_then(|card_idx| self.tmp_ruled_out.get(card_idx))
+            .is_some_and(|values| values.contains(&value))
+    }
+
+    /// Starts picking on the grid from the first cell that may be picked, true when there
+    /// is something new to pick.
+    pub fn start_picking(&mut self, picking: Picking) -> bool {
+        if picking == self.picking {
+            return false;
+        }
+
+        self.picking = picking;
+        self.selected = (0..GRID_ROWS * GRID_COLUMNS)
+            .find(|idx| self.selectable(*idx))
+            .unwrap_or(0);
+        picking != Picking::Nothing
+    }
+
+    /// Moves the selection by `step` cells, over those that can't be picked.
+    fn move_selection(&mut self, step: isize) {
+        let mut idx = self.selected as isize;
+        loop {
+            idx += step;
+            if idx < 0 || idx >= (GRID_ROWS * GRID_COLUMNS) as isize {
+                return;
+            }
+            if self.selectable(idx as usize) {
+                self.selected = idx as usize;
+                return;
+            }
+        }
+    }
+
+    /// Handles a key while picking, with the cell picked once it is.
+    pub fn select_key(&mut self, key: KeyCode) -> Option<usize> {
+        match key {
+            KeyCode::Left | KeyCode::Char('h') => self.move_selection(-1),
+            KeyCode::Right | KeyCode::Char('l') => self.move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-(GRID_COLUMNS as isize)),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(GRID_COLUMNS as isize),
+            KeyCode::Enter | KeyCode::Char(' ') if self.selectable(self.selected) => {
+                self.mode = InputMode::Normal;
+                return Some(self.selected);
+            }
+            KeyCode::Esc => self.mode = InputMode::Normal,
+            KeyCode::Char('i') => self.mode = InputMode::Message,
+            // typing the number still works
+            KeyCode::Char(c) if c.is_ascii_digit() => {
+                self.mode = InputMode::Message;
+                self.input.push(c);
+            }
+            _ => {}
+        }
+
+        None
+    }
+
+    /// The cell of the grid clicked on, if it may be picked.
+    pub fn clicked(&self, mouse: &MouseEvent) -> Option<usize> {
+        if mouse.kind != MouseEventKind::Down(MouseButton::Left) {
+            return None;
+        }
+
+        let position = Position::new(mouse.column, mouse.row);
+        self.grid_cells
+            .iter()
+            .position(|cell| cell.contains(position))
+            .filter(|idx| self.selectable(*idx))
+    }
+
+    /// We answered the server, which asks again if it needs to.
+    pub fn answered(&mut self, answer: &str) {
+        self.picks.answered(answer);
+        if self.picking == Picking::Card {
+            self.guessed_card = answer.trim().parse().ok();
+        }
+        self.picking = Picking::Nothing;
+    }
+
+    /// Further back in the log, as far as it goes once drawn.
+    pub fn log_scroll_next(&mut self, lines: u16) {
+        self.log_scroll = self.log_scroll.saturating_add(lines);
+    }
+    pub fn log_scroll_prev(&mut self, lines: u16) {
+        self.log_scroll = self.log_scroll.saturating_sub(lines);
     }
 
-    pub fn log_scroll_next(&mut self) {
-        if self.log_scroll < MAX_SCROLL - 1 {
-            self.log_scroll += 1;
-            self.log_scroll %= 10;
+    /// `j`/`k` a line, PageDown/PageUp a page, Home/End to the newest/oldest entries, `f`
+    /// for the next category, `/` to search. Whether the key was for the log.
+    pub fn log_key(&mut self, code: KeyCode) -> bool {
+        let page = self.log_height.max(1);
+        match code {
+            KeyCode::Char('j') => self.log_scroll_next(1),
+            KeyCode::Char('k') => self.log_scroll_prev(1),
+            KeyCode::PageDown => self.log_scroll_next(page),
+            KeyCode::PageUp => self.log_scroll_prev(page),
+            KeyCode::Home => self.log_scroll = 0,
+            KeyCode::End => self.log_scroll = u16::MAX,
+            KeyCode::Char('f') => {
+                self.logs.filter = Category::next_filter(self.logs.filter);
+                self.log_scroll = 0;
+            }
+            KeyCode::Char('/') => self.mode = InputMode::Search,
+            _ => return false,
         }
+        true
     }
-    pub fn log_scroll_prev(&mut self) {
-        if self.log_scroll >= 1 {
-            self.log_scroll -= 1;
-            self.log_scroll %= 10;
+
+    /// Typing the search: Enter keeps it, Esc clears it.
+    pub fn search_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char(c) => self.logs.search.push(c),
+            KeyCode::Backspace => {
+                self.logs.search.pop();
+            }
+            KeyCode::Enter => self.mode = InputMode::Normal,
+            KeyCode::Esc => {
+                self.logs.search.clear();
+                self.mode = InputMode::Normal;
+            }
+            _ => {}
         }
+        self.log_scroll = 0;
     }
 }
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
 
-    let address = format!("{}:{}", args.addr, args.port);
-    let name = args.name.trim();
+    if let Some(seed) = args.puzzle {
+        let mut app = App::new(String::new(), 4);
 
-    let mut app = App::new(name.to_string(), 4);
-    app.name = name.to_string();
+        let mut terminal = setup_terminal()?;
+        let res = run_puzzle(&mut terminal, &mut app, seed.unwrap_or_else(rand::random)).await;
+        restore_terminal(&mut terminal)?;
 
-    let mut buffer = [0u8; 1024];
-    let mut stream = TcpStream::connect(address.clone()).await.unwrap();
-    app.log_add_top(format!("{} {}\n", "Connected to server at", address));
+        if let Err(err) = res {
+            println!("{err:?}");
+        }
+
+        return Ok(());
+    }
+
+    if let Some(path) = &args.replay {
+        let replay = match archive::read(path) {
+            Ok(record) => Replay::new(record),
+            Err(err) => Err(err.to_string()),
+        };
+        let replay = match replay {
+            Ok(replay) => replay,
+            Err(err) => {
+                println!("Can't replay {}: {}", path.display(), err);
+                return Ok(());
+            }
+        };
+        let mut app = App::new(String::new(), 4);
+
+        let mut terminal = setup_terminal()?;
+        let res = run_replay(&mut terminal, &mut app, &replay).await;
+        restore_terminal(&mut terminal)?;
+
+        if let Err(err) = res {
+            println!("{err:?}");
+        }
+
+        return Ok(());
+    }
 
-    let init_message = "init";
-    stream.write_all(init_message.as_bytes()).await.unwrap();
-    stream.flush().await.unwrap();
+    let local = if args.practice {
+        let name = args
+            .name
+            .clone()
+            .unwrap_or_else(|| String::from(PRACTICE_NAME));
+        Some(LocalGame::with_bots(
+            &[name.trim().to_string()],
+            args.bots,
+            args.difficulty,
+        ))
+    } else if !args.local.is_empty() {
+        Some(LocalGame::new(&args.local))
+    } else {
+        None
+    };
+
+    if let Some(local) = local {
+        let mut local = match local {
+            Ok(local) => local,
+            Err(err) => {
+                println!("{}", err);
+                return Ok(());
+            }
+        };
+        let mut app = App::new(local.current().to_string(), 4);
 
-    let bytes_read = stream.read(&mut buffer).await.unwrap();
-    let response = String::from_utf8_lossy(&buffer[..bytes_read]);
+        let mut terminal = setup_terminal()?;
+        let res = run_local(&mut terminal, &mut app, &mut local).await;
+        restore_terminal(&mut terminal)?;
 
-    app.log_add_top(format!("{} {}\n\n\n", "Response from server:", response));
+        if let Err(err) = res {
+            println!("{err:?}");
+        }
+        println!("{}", local.prompt());
 
-    stream.write_all(name.as_bytes()).await.unwrap();
-    stream.flush().await.unwrap();
+        return Ok(());
+    }
 
-    app.log_add_top(format!("{}\n", "Sent name and init to server"));
+    let address = match args.unix {
+        Some(path) => Endpoint::UNIX(path),
+        None => Endpoint::TCP(format!("{}:{}", args.addr, args.port)),
+    };
+    let name = args.name.unwrap_or_default();
+    let name = name.trim();
+
+    let mut app = App::new(name.to_string(), 4);
+    app.name = name.to_string();
+    app.spectating = args.spectate;
+
+    let tls = match Trust::from_args(args.tls_ca, args.tls_fingerprint) {
+        Some(trust) => Some(tls::connector(&trust)?),
+        None => None,
+    };
+
+    let mut stream = transport::connect(&address, tls.as_ref()).await.unwrap();
+    app.logs.push(
+        Category::SYSTEM,
+        &format!("{} {}", "Connected to server at", address),
+    );
+
+    let hello = match (args.spectate, args.host_key) {
+        (true, Some(key)) => format!("{}:{}", SPECTATE_HELLO, key.trim()),
+        (true, None) => SPECTATE_HELLO.to_string(),
+        (false, _) => access::join_hello(name, args.code.as_deref()),
+    };
+    let response = handshake(&mut stream, &hello).await.unwrap();
+
+    app.logs.push(Category::SYSTEM, &response);
+    app.logs
+        .push(Category::SYSTEM, "Sent name and init to server");
+
+    let mut terminal = setup_terminal()?;
+    let res = run_app(&mut terminal, &mut stream, &mut app, &address, tls.as_ref()).await;
+    restore_terminal(&mut terminal)?;
 
-    ///////////////////////////////////////////////////////////////////////////////////
-    // setup terminal
+    if let Err(err) = res {
+        println!("{err:?}");
+    }
+    println!("exit successfully");
+
+    return Ok(());
+}
+
+fn setup_terminal() -> Result<Terminal<CrosstermBackend<Stdout>>, std::io::Error> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
 
-    let res = run_app(&mut terminal, &mut stream, &mut app, name.to_string()).await;
+    Terminal::new(backend)
+}
 
-    // restore terminal
+fn restore_terminal(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+) -> Result<(), std::io::Error> {
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
         DisableMouseCapture
     )?;
-    terminal.show_cursor()?;
-
-    if let Err(err) = res {
-        println!("{err:?}");
-    }
-    println!("exit successfully");
-
-    return Ok(());
+    terminal.show_cursor()
 }
 
 async fn update_ui<B: Backend>(
     terminal: &mut Terminal<B>,
-    app: &App,
+    app: &mut App,
 ) -> Result<(), std::io::Error> {
     terminal.draw(|f| ui2(f, app))?;
     Ok(())
 }
 
+/// Sends "init", waits for the server's ack, then sends `hello` (the name,
+/// resume:<token> when taking a seat back, or spectate[:<host key>] to watch).
+async fn handshake(stream: &mut Stream, hello: &str) -> Result<String, std::io::Error> {
+    let mut buffer = [0u8; 1024];
+
+    stream.write_all("init".as_bytes()).await?;
+    stream.flush().await?;
+
+    let bytes_read = stream.read(&mut buffer).await?;
+    let response = String::from_utf8_lossy(&buffer[..bytes_read]).to_string();
+
+    stream.write_all(hello.as_bytes()).await?;
+    stream.flush().await?;
+
+    Ok(response)
+}
+
+async fn reconnect(address: &Endpoint, token: &str, tls: Option<&TlsConnector>) -> Option<Stream> {
+    let hello = format!("{}{}", RESUME_PREFIX, token);
+
+    for _ in 0..RECONNECT_ATTEMPTS {
+        sleep(Duration::from_secs(RECONNECT_DELAY_SECS)).await;
+
+        if let Ok(mut stream) = transport::connect(address, tls).await {
+            if handshake(&mut stream, &hello).await.is_ok() {
+                return Some(stream);
+            }
+        }
+    }
+
+    None
+}
+
 async fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
-    stream: &mut TcpStream,
+    stream: &mut Stream,
     app: &mut App,
-    _name: String,
+    address: &Endpoint,
+    tls: Option<&TlsConnector>,
 ) -> Result<(), std::io::Error> {
     let mut awaiting_msg_transfer: bool = false;
 
@@ -171,23 +622,32 @@ async fn run_app<B: Backend>(
         let mut buffer = [0u8; 1024];
 
         if poll(Duration::from_millis(500))? {
-            if let Key(key) = event::read()? {
-                match app.mode {
+            match event::read()? {
+                Key(key) => match app.mode {
                     InputMode::Normal => match key.code {
                         KeyCode::Char('q') => {
                             return Ok(());
                         }
-                        KeyCode::Char('i') => {
+                        // spectators are never asked anything
+                        KeyCode::Char('i') if !app.spectating => {
                             app.mode = InputMode::Message;
                         }
-                        KeyCode::Char('j') => {
-                            app.log_scroll_next();
+                        KeyCode::Char('s') if app.picking != Picking::Nothing => {
+                            app.mode = InputMode::Select;
                         }
-                        KeyCode::Char('k') => {
-                            app.log_scroll_prev();
+                        KeyCode::Char('h') if !app.spectating => {
+                            app.message = hint::HINT_COMMAND.to_string();
+                            awaiting_msg_transfer = true;
+                        }
+                        KeyCode::Char('a') => {
+                            app.auto_notes = !app.auto_notes;
+                            let state = if app.auto_notes { "on" } else { "off" };
+                            app.logs
+                                .push(Category::SYSTEM, &format!("Automatic notes {}", state));
+                        }
+                        code => {
+                            app.log_key(code);
                         }
-
-                        _ => {}
                     },
                     InputMode::Message => match key.code {
                         KeyCode::Esc => {
@@ -200,22 +660,56 @@ async fn run_app<B: Backend>(
                         KeyCode::Backspace => {
                             app.input.pop();
                         }
-                        KeyCode::Enter => {
-                            app.message.clear();
-                            app.message.push_str(app.input.trim());
-                            app.input.clear();
-                            awaiting_msg_transfer = true;
-                        }
+                        // notes stay with us, everything else goes to the server
+                        KeyCode::Enter => match notes::parse_note(&app.input) {
+                            Some(note) => {
+                                let (category, reply) = match note {
+                                    Ok((card_idx, note)) => {
+                                        (Category::SYSTEM, app.take_note(card_idx, &note))
+                                    }
+                                    Err(usage) => (Category::ERROR, usage),
+                                };
+                                app.logs.push(category, &reply);
+                                app.input.clear();
+                            }
+                            None => {
+                                app.message.clear();
+                                app.message.push_str(app.input.trim());
+                                app.input.clear();
+                                awaiting_msg_transfer = true;
+                            }
+                        },
                         _ => {}
                     },
+                    InputMode::Select => {
+                        if let Some(idx) = app.select_key(key.code) {
+                            app.message = idx.to_string();
+                            awaiting_msg_transfer = true;
+                        }
+                    }
+                    InputMode::Search => app.search_key(key.code),
+                },
+                Mouse(mouse) => {
+                    if let Some(idx) = app.clicked(&mouse) {
+                        app.message = idx.to_string();
+                        app.mode = InputMode::Normal;
+                        awaiting_msg_transfer = true;
+                    }
                 }
+                _ => {}
             }
         } else {
             if awaiting_msg_transfer {
                 // flush this message to the server
                 if app.message != String::new() {
-                    stream.write_all(app.message.as_bytes()).await.unwrap();
-                    stream.flush().await.unwrap();
+                    if chat::parse_say(&app.message).is_none() && !hint::is_request(&app.message) {
+                        let answer = app.message.clone();
+                        app.answered(&answer);
+                    }
+                    // a broken connection shows up on the next read, which reconnects;
+                    // the server asks again once we're back
+                    let _ = stream.write_all(app.message.as_bytes()).await;
+                    let _ = stream.flush().await;
 
                     awaiting_msg_transfer = false;
                     app.message.clear();
@@ -225,28 +719,120 @@ async fn run_app<B: Backend>(
                 time::timeout(Duration::from_millis(200), stream.read(&mut buffer)).await;
 
             if let Ok(value) = read_timeout {
-                let bytes_read = value.unwrap();
+                let bytes_read = value.unwrap_or(0);
+
+                if bytes_read == 0 {
+                    // connection lost, try to take our seat back
+                    let token = match app.token.clone() {
+                        Some(token) => token,
+                        None => break Ok(()),
+                    };
+                    app.logs
+                        .push(Category::ERROR, "Connection lost, reconnecting...");
+                    let _ = update_ui(terminal, app).await;
+
+                    match reconnect(address, &token, tls).await {
+                        Some(new_stream) => {
+                            *stream = new_stream;
+                            app.logs
+                                .push(Category::SYSTEM, &format!("Reconnected to {}", address));
+                            continue;
+                        }
+                        None => break Ok(()),
+                    }
+                }
+
                 let response = String::from_utf8_lossy(&buffer[..bytes_read]);
 
-                app.log_add_top(format!("{} {}\n\n\n", "Response from server:", response));
+                // chat doesn't change what the game expects from us
+                let (chat_lines, response) = chat::split_chat(&response);
+                for line in chat_lines.iter() {
+                    app.logs.push(Category::CHAT, line);
+                }
+                app.chat.extend(chat_lines);
+                let (hints, response) = hint::split_hints(&response);
+                for hint in hints {
+                    app.logs.push(Category::SYSTEM, &format!("Hint: {}", hint));
+                }
+                let (inserted, response) = notes::split_inserted(&response);
+                for (player, positions) in inserted {
+                    app.notes.follow(&player, &positions);
+                }
+                let (told, response) = opponents::split_opponents(&response);
+                let (asked, response) = prompt::split_prompt(&response);
+                opponents::update(&mut app.opponents, told);
+                if asked {
+                    app.picks.asked(&response);
+                }
+                if response.trim().is_empty() {
+                    continue;
+                }
+
+                app.logs.push(Category::MOVE, &response);
+
+                if let Some(token) = parse_responses(&response, "$$") {
+                    app.token = Some(token);
+                }
 
                 if let Some(deck) = parse_responses(&response, "##") {
                     app.player.deck_from_str(deck);
                 }
 
                 if let Some(deck) = parse_responses(&response, "**") {
+                    app.tmp_ruled_out = davincicode::ruled_out_from_str(&deck);
                     app.tmp_deck = deck_from_str(deck);
+                    app.opponent = None;
                 }
 
+                // the grid shows the opponent we guess, with our notes
+                if let Some(name) = parse_opponent(&response) {
+                    app.opponent = Some(name);
+                }
                 if let Some(deck) = parse_responses(&response, "++") {
-                    app.opp_deck = deck_from_str(deck);
+                    app.opp_deck = deck_from_str(deck.clone());
+                    if let Some(name) = &app.opponent {
+                        app.tmp_ruled_out = davincicode::ruled_out_from_str(&deck);
+                        app.tmp_deck = app.opp_deck.clone();
+
+                        // their panel follows the guesses too
+                        if let Some(opponent) = app.opponents.iter_mut().find(|o| o.name == *name) {
+                            opponent.deck = app.tmp_deck.clone();
+                            opponent.ruled_out = app.tmp_ruled_out.clone();
+                        }
+                    }
+                }
+
+                if let Some(table) = parse_responses(&response, "@@") {
+                    app.table = table;
+                }
+
+                if let Some(commit) = fairness::parse_commit(&response) {
+                    app.commit = Some(commit);
+                }
+                if response.contains("%%reveal:") || !app.reveal.is_empty() {
+                    app.reveal.push_str(&response);
+                    if let Some(reveal) = fairness::Reveal::parse(&app.reveal) {
+                        let verdict = fairness::verdict(
+                            app.commit.as_deref(),
+                            &reveal,
+                            &app.player.deck,
+                            &app.picks.picked(),
+                        );
+                        app.logs.push(Category::SYSTEM, &verdict);
+                        app.reveal.clear();
+                        let _ = update_ui(terminal, app).await;
+                    }
                 }
+
                 if let Some(won_player) = parse_responses(&response, "||") {
-                    if won_player != app.name {
-                        app.log_add_top(format!(
-                            "{} {} is the winner\n\n\n",
-                            "You lost. :(", won_player
-                        ));
+                    if app.spectating {
+                        app.logs
+                            .push(Category::MOVE, &format!("{} is the winner", won_player));
+                    } else if won_player != app.name {
+                        app.logs.push(
+                            Category::MOVE,
+                            &format!("You lost. :( {} is the winner", won_player),
+                        );
                     }
                     let _ = update_ui(terminal, app).await;
 
@@ -254,31 +840,435 @@ async fn run_app<B: Backend>(
                     break Ok(());
                 }
 
-                if response.trim().contains("It's your turn") {
+                // a prompt to pick a card or a value gets the grid ready
+                let picking = parse_picking(&response);
+                if let Some(picking) = picking {
+                    app.start_picking(picking);
+                }
+
+                if app.spectating {
+                    // nothing to answer
+                } else if picking.is_some_and(|picking| picking != Picking::Nothing) {
+                    app.mode = InputMode::Select;
+                } else if asked {
                     app.mode = InputMode::Message;
-                    // app.log_add_top(format!("{}\n", "Enter something"));
                     // let _ = update_ui(terminal, &app).await;
                 } else if response.trim().starts_with("You won! Congrats!") {
+                    // the seed comes before the end of the game
                     app.mode = InputMode::Normal;
-                    app.log_add_top(format!("{}\n", "Nice, You're the winner. Exiting."));
+                    app.logs.push(Category::MOVE, "Nice, You're the winner.");
                     let _ = update_ui(terminal, app).await;
+                } else {
+                    app.mode = InputMode::Normal;
+                }
+            }
+        }
+    }
+}
+
+/// Hot seat: everybody answers on this terminal, in turn.
+async fn run_local<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    local: &mut LocalGame,
+) -> Result<(), std::io::Error> {
+    let mut refused: Option<String> = None;
+    // the events of the game already in the log
+    let mut logged = 0;
+    app.mode = InputMode::Message;
 
-                    sleep(Duration::from_secs(20)).await;
+    loop {
+        show_local(app, local, refused.as_deref(), &mut logged);
+        let _ = update_ui(terminal, app).await;
 
-                    break Ok(());
-                } else {
+        if !poll(Duration::from_millis(500))? {
+            continue;
+        }
+        let key = match event::read()? {
+            Key(key) => key,
+            Mouse(mouse) => {
+                if let Some(idx) = app.clicked(&mouse) {
+                    let answer = idx.to_string();
+                    refused = local.answer(&answer).err();
+                    app.answered(&answer);
+                }
+                continue;
+            }
+            _ => continue,
+        };
+
+        if local.step == Step::PASS || local.step == Step::END {
+            match key.code {
+                KeyCode::Char('q') => return Ok(()),
+                KeyCode::Enter if local.step == Step::PASS => {
+                    let _ = local.answer("");
+                    app.mode = InputMode::Message;
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        match app.mode {
+            InputMode::Normal => match key.code {
+                KeyCode::Char('q') => {
+                    return Ok(());
+                }
+                KeyCode::Char('i') => {
+                    app.mode = InputMode::Message;
+                }
+                KeyCode::Char('s') if app.picking != Picking::Nothing => {
+                    app.mode = InputMode::Select;
+                }
+                KeyCode::Char('h') => {
+                    refused = Some(hint::advice(&local.game, local.current()));
+                }
+                code => {
+                    app.log_key(code);
+                }
+            },
+            InputMode::Message => match key.code {
+                KeyCode::Esc => {
+                    app.clear_msg_filed();
                     app.mode = InputMode::Normal;
                 }
+                KeyCode::Char(c) => {
+                    app.input.push(c);
+                }
+                KeyCode::Backspace => {
+                    app.input.pop();
+                }
+                KeyCode::Enter => {
+                    refused = local.answer(&app.input).err();
+                    let answer = app.input.clone();
+                    app.answered(&answer);
+                    app.input.clear();
+                }
+                _ => {}
+            },
+            InputMode::Select => {
+                if let Some(idx) = app.select_key(key.code) {
+                    let answer = idx.to_string();
+                    refused = local.answer(&answer).err();
+                    app.answered(&answer);
+                    app.mode = InputMode::Message;
+                }
+            }
+            InputMode::Search => app.search_key(key.code),
+        }
+    }
+}
 
-                if bytes_read == 0 {
-                    break Ok(());
+/// Fills the view from the local game, as the current player may see it, the events past
+/// the first `logged` going in the log.
+fn show_local(app: &mut App, local: &LocalGame, refused: Option<&str>, logged: &mut usize) {
+    app.name = local.current().to_string();
+    app.pass_to = match local.step {
+        Step::PASS => Some(local.current().to_string()),
+        _ => None,
+    };
+    if let Some(player) = local.player() {
+        app.player = player.clone();
+    }
+    app.opponents = opponents::of(&local.game, local.current(), local.current());
+
+    let opponents = local.opponents();
+    let opponent = match &local.step {
+        Step::CARD(opponent) | Step::VALUE(opponent, _) => {
+            opponents.iter().find(|player| player.name == *opponent)
+        }
+        _ => None,
+    };
+    app.tmp_deck = match (&local.step, opponent) {
+        (Step::DRAW, _) => local.game.card_avail.clone(),
+        (_, Some(opponent)) => opponent.deck.clone(),
+        _ => Vec::new(),
+    };
+    app.tmp_ruled_out = match opponent {
+        Some(opponent) => (0..opponent.deck.len())
+            .map(|idx| opponent.ruled_out(idx).to_vec())
+            .collect(),
+        None => Vec::new(),
+    };
+
+    // a card or a value to pick, on the grid
+    let picking = match &local.step {
+        Step::DRAW => Picking::Pile,
+        Step::CARD(_) => Picking::Card,
+        Step::VALUE(_, card_idx) => {
+            app.guessed_card = Some(*card_idx);
+            Picking::Value(local.game.set_cards / 2 - 1)
+        }
+        _ => Picking::Nothing,
+    };
+    if app.start_picking(picking) && app.input.is_empty() {
+        app.mode = InputMode::Select;
+    }
+    if app.picking == Picking::Nothing && matches!(app.mode, InputMode::Select) {
+        app.mode = InputMode::Message;
+    }
+
+    // only what everybody may know stays in the logs, the prompt goes with the turn
+    app.logs.header = match refused {
+        Some(refused) => format!("{}\n{}\n", refused, local.prompt()),
+        None => format!("{}\n", local.prompt()),
+    };
+    if local.step == Step::END {
+        app.logs.header.push_str("Press q to quit.\n");
+    }
+    for event in local.events.iter().skip(*logged) {
+        app.logs.push(Category::MOVE, event);
+    }
+    *logged = local.events.len();
+}
+
+/// Steps through a saved game: arrows to move, a turn number then Enter to jump to it, p to
+/// see it as the next player saw it.
+async fn run_replay<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    replay: &Replay,
+) -> Result<(), std::io::Error> {
+    let mut step = 0;
+    // none for every card showing, else the seat we watch from
+    let mut viewer: Option<usize> = None;
+    app.mode = InputMode::Message;
+
+    loop {
+        show_replay(app, replay, step, viewer);
+        let _ = update_ui(terminal, app).await;
+
+        if !poll(Duration::from_millis(500))? {
+            continue;
+        }
+        let key = match event::read()? {
+            Key(key) => key,
+            _ => continue,
+        };
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Right | KeyCode::Down => step = (step + 1).min(replay.moves()),
+            KeyCode::Left | KeyCode::Up => step = step.saturating_sub(1),
+            KeyCode::Home => step = 0,
+            KeyCode::End => step = replay.moves(),
+            KeyCode::Char('p') | KeyCode::Tab => {
+                viewer = match viewer {
+                    None => Some(0),
+                    Some(seat) if seat + 1 < replay.record.seats.len() => Some(seat + 1),
+                    Some(_) => None,
+                };
+            }
+            KeyCode::Char('j') => app.log_scroll_next(1),
+            KeyCode::Char('k') => app.log_scroll_prev(1),
+            KeyCode::PageDown => app.log_scroll_next(app.log_height.max(1)),
+            KeyCode::PageUp => app.log_scroll_prev(app.log_height.max(1)),
+            KeyCode::Char(c) if c.is_ascii_digit() => app.input.push(c),
+            KeyCode::Backspace => {
+                app.input.pop();
+            }
+            KeyCode::Enter => {
+                if let Some(turn_step) = app
+                    .input
+                    .parse()
+                    .ok()
+                    .and_then(|turn| replay.step_of_turn(turn))
+                {
+                    step = turn_step;
                 }
+                app.input.clear();
             }
+            _ => {}
+        }
+    }
+}
+
+/// Fills the view with the position `step`, as the player at the seat `viewer` saw it.
+fn show_replay(app: &mut App, replay: &Replay, step: usize, viewer: Option<usize>) {
+    let viewer = viewer.and_then(|seat| replay.record.seats.get(seat).map(String::as_str));
+    let players = replay.players(step);
+    let event = replay.event(step);
+
+    // our deck is the viewer's, or the one of whoever just moved
+    let me = viewer
+        .or(event.map(|event| event.player()))
+        .unwrap_or(&players[0].name);
+    if let Some(player) = players.iter().find(|player| player.name == me) {
+        app.player = player.clone();
+    }
+    app.name = match viewer {
+        Some(_) => me.to_string(),
+        None => format!("{} (every card showing)", me),
+    };
+
+    // the other grid shows what the move was about
+    app.tmp_deck = match event {
+        Some(GameEvent::GUESS { opponent, .. }) => replay.hand(step, opponent, viewer),
+        Some(GameEvent::DRAW { .. }) => replay.pile(step, viewer),
+        _ => {
+            let seat = players.iter().position(|player| player.name == me);
+            let next = &players[seat.map_or(0, |seat| (seat + 1) % players.len())];
+            replay.hand(step, &next.name, viewer)
+        }
+    };
+
+    app.logs.header = format!(
+        "Turn {} of {}, move {} of {}\nSeen by {}\n",
+        replay.turn(step),
+        replay.turn_count(),
+        step,
+        replay.moves(),
+        viewer.unwrap_or("nobody, every card showing")
+    );
+    if let Some(winner) = &replay.record.winner {
+        app.logs
+            .header
+            .push_str(&format!("{} won the game\n", winner));
+    }
+    app.logs.header.push_str(
+        "Left/Right: step, Home/End: start/end, turn number then Enter: jump, p: perspective, \
+         q: quit\n",
+    );
+    app.logs.header.push_str("_____________________________\n");
+    app.logs.header.push_str(&replay.table(step, viewer));
+    app.logs.header.push_str("_____________________________\n");
+    for past in (1..=step).rev() {
+        if let Some(text) = replay.describe(past, viewer) {
+            app.logs
+                .header
+                .push_str(&format!("{}. {}\n", replay.turn(past), text));
         }
     }
 }
 
-fn ui2(f: &mut Frame, app: &App) {
+/// Puzzles, one after the other: the answer is the number of a rival card and its value.
+async fn run_puzzle<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    seed: u64,
+) -> Result<(), std::io::Error> {
+    let mut puzzle = Puzzle::generate(seed);
+    let mut feedback = String::new();
+    app.mode = InputMode::Message;
+
+    loop {
+        show_puzzle(app, &puzzle, &feedback);
+        let _ = update_ui(terminal, app).await;
+
+        if !poll(Duration::from_millis(500))? {
+            continue;
+        }
+        let key = match event::read()? {
+            Key(key) => key,
+            _ => continue,
+        };
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char('n') => {
+                puzzle = Puzzle::generate(rand::random());
+                feedback.clear();
+                app.input.clear();
+            }
+            KeyCode::Char(c) if c.is_ascii_digit() || c == ' ' => app.input.push(c),
+            KeyCode::Backspace => {
+                app.input.pop();
+            }
+            KeyCode::Enter => {
+                let numbers: Vec<u32> = app
+                    .input
+                    .split_whitespace()
+                    .filter_map(|number| number.parse().ok())
+                    .collect();
+                feedback = match numbers[..] {
+                    [card_idx, value] => match puzzle.check(card_idx as usize, value) {
+                        Ok(()) => format!("Solved! {} Press n for another one.", puzzle.explain()),
+                        Err(why) => why,
+                    },
+                    _ => String::from("Type the number of the card, then its value."),
+                };
+                app.input.clear();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn show_puzzle(app: &mut App, puzzle: &Puzzle, feedback: &str) {
+    app.player = puzzle.me().clone();
+    app.name = format!("- puzzle {}", puzzle.seed);
+    app.tmp_deck = puzzle.rival().deck.iter().map(puzzle::masked).collect();
+
+    app.logs.header = format!(
+        "Puzzle {}, difficulty {}/5\n\
+         One of the rival's hidden cards can only have one value.\n\
+         Type its number and its value, like \"2 7\", then Enter.\n\
+         n: another puzzle, q: quit\n\
+         _____________________________\n",
+        puzzle.seed, puzzle.difficulty
+    );
+    if !feedback.is_empty() {
+        app.logs.header.push_str(&format!("{}\n", feedback));
+    }
+    app.logs.header.push_str(&format!(
+        "{}: {}\n{}: {}\n",
+        puzzle::RIVAL,
+        puzzle.rival().show_hand(true, false),
+        puzzle::ME,
+        puzzle.me().show_hand(false, false)
+    ));
+}
+
+/// The header of the log, then the entries it shows, each in the color of its category.
+fn log_lines(logs: &Logs) -> Vec<Line<'static>> {
+    let mut lines: Vec<Line> = Vec::new();
+    if !logs.header.is_empty() {
+        lines.extend(logs.header.lines().map(|line| Line::from(line.to_string())));
+        lines.push(Line::from("_____________________________"));
+    }
+
+    for entry in logs.shown() {
+        let color = match entry.category {
+            Category::SYSTEM => ratatui::prelude::Color::Gray,
+            Category::MOVE => ratatui::prelude::Color::Reset,
+            Category::CHAT => ratatui::prelude::Color::Cyan,
+            Category::ERROR => ratatui::prelude::Color::Red,
+        };
+        let style = ratatui::prelude::Style::default().fg(color);
+
+        for (idx, text) in entry.text.lines().enumerate() {
+            let stamp = match idx {
+                0 => format!("{} {:<6} ", entry.stamp(), entry.category.label()),
+                _ => String::new(),
+            };
+            lines.push(Line::from(vec![
+                Span::styled(
+                    stamp,
+                    ratatui::prelude::Style::default().fg(ratatui::prelude::Color::DarkGray),
+                ),
+                Span::styled(text.to_string(), style),
+            ]));
+        }
+    }
+
+    lines
+}
+
+fn ui2(f: &mut Frame, app: &mut App) {
+    if let Some(name) = &app.pass_to {
+        let pass_p = Paragraph::new(app.logs.header.clone())
+            .block(
+                Block::new()
+                    .title(format!("Pass to {}, press Enter", name))
+                    .borders(Borders::ALL),
+            )
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true });
+
+        f.render_widget(pass_p, f.area());
+        return;
+    }
+
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
@@ -290,11 +1280,19 @@ fn ui2(f: &mut Frame, app: &App) {
     )
     .split(chunks[0]);
 
+    // one panel per opponent, side by side when they fit
+    let panels = app.opponents.len() as u16;
+    let side_by_side = panels > 0 && chunks[1].width / panels >= PANEL_MIN_WIDTH;
+    let panels_height = match side_by_side {
+        true => PANEL_HEIGHT,
+        false => PANEL_HEIGHT * panels,
+    };
     let inner_layout2 = Layout::new(
         Direction::Vertical,
         [
             Constraint::Percentage(50),
-            Constraint::Percentage(40),
+            Constraint::Length(panels_height),
+            Constraint::Min(0),
             Constraint::Percentage(10),
         ],
     )
@@ -302,7 +1300,11 @@ fn ui2(f: &mut Frame, app: &App) {
 
     ////////////////////////////////////////////////////////////////////////////////////////////////
     // input layout
-    let user_input = Paragraph::new(app.input.to_owned())
+    let user_input = match app.mode {
+        InputMode::Search => format!("/{}", app.logs.search),
+        _ => app.input.to_owned(),
+    };
+    let user_input = Paragraph::new(user_input)
         .block(
             Block::default()
                 .title("Commands")
@@ -311,21 +1313,59 @@ fn ui2(f: &mut Frame, app: &App) {
         )
         .style(ratatui::prelude::Style::default());
 
-    f.render_widget(user_input, inner_layout2[2]);
+    f.render_widget(user_input, inner_layout2[3]);
 
-    // Logs Paragraph
-    let logs = ratatui::prelude::Text::from(app.logs.clone());
-    let log_p = Paragraph::new(logs)
+    let logs_chat_layout = Layout::new(
+        Direction::Vertical,
+        [Constraint::Percentage(65), Constraint::Percentage(35)],
+    )
+    .split(inner_layout2[0]);
+
+    // Logs Paragraph, scrolled no further than its last line
+    let log_title = match app.logs.describe_view() {
+        Some(view) => format!("Logs (newest first, {})", view),
+        None => String::from("Logs (newest first, f: filter, /: search)"),
+    };
+    let log_p = Paragraph::new(log_lines(&app.logs))
+        .block(Block::new().title(log_title).borders(Borders::ALL))
+        .wrap(Wrap { trim: true });
+
+    let log_area = logs_chat_layout[0];
+    app.log_height = log_area.height.saturating_sub(2);
+    let log_length = log_p.line_count(log_area.width.saturating_sub(2)) as u16;
+    app.log_scroll = app
+        .log_scroll
+        .min(log_length.saturating_sub(app.log_height));
+
+    f.render_widget(log_p.scroll((app.log_scroll, 0)), log_area);
+
+    // Chat Paragraph, the last lines that fit
+    let chat_height = logs_chat_layout[1].height.saturating_sub(2) as usize;
+    let chat_lines = app.chat[app.chat.len().saturating_sub(chat_height)..].join("\n");
+    let chat_p = Paragraph::new(chat_lines)
         .block(
             Block::new()
-                .title("Logs (reverse order)")
+                .title(format!("Chat ({} <message>)", chat::SAY_COMMAND))
                 .borders(Borders::ALL),
         )
-        .alignment(Alignment::Center)
-        .wrap(Wrap { trim: true })
-        .scroll((app.log_scroll, 0));
+        .wrap(Wrap { trim: true });
+
+    f.render_widget(chat_p, logs_chat_layout[1]);
+
+    if app.spectating {
+        let table_p = Paragraph::new(app.table.clone())
+            .block(
+                Block::new()
+                    .title("Table (spectating)")
+                    .borders(Borders::ALL),
+            )
+            .wrap(Wrap { trim: true });
+
+        f.render_widget(table_p, chunks[0]);
+        return;
+    }
 
-    f.render_widget(log_p, inner_layout2[0]);
+    render_opponents(f, app, inner_layout2[1], side_by_side);
 
     /////////////////////////////////////////////////////
     // card view player
@@ -402,7 +1442,7 @@ fn ui2(f: &mut Frame, app: &App) {
             Constraint::Percentage(21),
         ],
     )
-    .split(inner_layout2[1]);
+    .split(inner_layout2[2]);
 
     let a_box = Block::default()
         .title("Opponents view")
@@ -472,11 +1512,17 @@ fn ui2(f: &mut Frame, app: &App) {
         ],
     )
     .split(inner_layout[1]);
-    let a_box = Block::default()
-        .title("Current opponent cards / game set")
-        .borders(Borders::ALL);
+    let title = match (app.picking, app.guessed_card) {
+        (Picking::Nothing, _) => String::from("Current opponent cards / game set"),
+        (Picking::Pile, _) => String::from("Pick a card to draw (arrows/hjkl, Enter)"),
+        (Picking::Card, _) => String::from("Pick a card to guess (arrows/hjkl, Enter)"),
+        (Picking::Value(_), Some(card_idx)) => format!("Pick the value of card {}", card_idx),
+        (Picking::Value(_), None) => String::from("Pick the value"),
+    };
+    let a_box = Block::default().title(title).borders(Borders::ALL);
     f.render_widget(a_box, opponent_card_grid_layout[0]);
 
+    let mut cells = Vec::new();
     let mut idx_ = 0;
     for (chunk_id, _) in opponent_card_grid_layout.iter().enumerate() {
         if chunk_id == 0 {
@@ -495,7 +1541,53 @@ fn ui2(f: &mut Frame, app: &App) {
         .split(opponent_card_grid_layout[chunk_id]);
 
         for (chunk, _) in sub_inner_layout.iter().enumerate() {
+            let cell = (chunk_id - 1) * GRID_COLUMNS + chunk;
+            cells.push(sub_inner_layout[chunk]);
+
+            // what can be picked stands out, the rest is greyed out
+            let border = match (app.picking, app.selectable(cell)) {
+                (Picking::Nothing, _) => ratatui::prelude::Style::default(),
+                (_, true) if cell == app.selected => ratatui::prelude::Style::default()
+                    .fg(ratatui::prelude::Color::Green)
+                    .add_modifier(ratatui::prelude::Modifier::BOLD),
+                (_, true) => ratatui::prelude::Style::default(),
+                (_, false) => {
+                    ratatui::prelude::Style::default().fg(ratatui::prelude::Color::DarkGray)
+                }
+            };
+            let border_type = match app.picking != Picking::Nothing && cell == app.selected {
+                true => BorderType::Thick,
+                false => BorderType::Plain,
+            };
+
+            // the values to guess from, in place of the cards
+            if let Picking::Value(max) = app.picking {
+                if cell as u32 > max {
+                    continue;
+                }
+                let style = match app.selectable(cell) {
+                    true => ratatui::prelude::Style::default(),
+                    false => {
+                        ratatui::prelude::Style::default().fg(ratatui::prelude::Color::DarkGray)
+                    }
+                };
+                let value_p = Paragraph::new(Span::styled(cell.to_string(), style))
+                    .block(
+                        Block::new()
+                            .title("value")
+                            .borders(Borders::ALL)
+                            .border_style(border)
+                            .border_type(border_type),
+                    )
+                    .alignment(Alignment::Center);
+
+                f.render_widget(value_p, sub_inner_layout[chunk]);
+                continue;
+            }
+
             if let Some(card_item) = app.tmp_deck.get(idx_) {
+                let ruled_out = app.tmp_ruled_out.get(idx_).cloned().unwrap_or_default();
+                let note = app.note(idx_);
                 idx_ += 1;
                 let s = match card_item.color {
                     davincicode::Color::BLACK => {
@@ -516,16 +1608,123 @@ fn ui2(f: &mut Frame, app: &App) {
                 } else {
                     format!("{}", card_item.value)
                 };
-                let card_p =
-                    Paragraph::new(Span::styled(format!("{} {}", card_color, card_value), s))
-                        .block(Block::new().title("card").borders(Borders::ALL))
-                        .alignment(Alignment::Center)
-                        .wrap(Wrap { trim: true });
+                let mut lines = vec![Line::from(Span::styled(
+                    format!("{} {}", card_color, card_value),
+                    s,
+                ))];
+                // values wrong guesses ruled out, greyed out
+                if !ruled_out.is_empty() {
+                    let values: Vec<String> = ruled_out.iter().map(u32::to_string).collect();
+                    lines.push(Line::from(Span::styled(
+                        format!("not {}", values.join(" ")),
+                        ratatui::prelude::Style::default().fg(ratatui::prelude::Color::DarkGray),
+                    )));
+                }
+                // our own notes stand out, the automatic ones less
+                if let Some((note, own)) = note {
+                    let color = match own {
+                        true => ratatui::prelude::Color::Cyan,
+                        false => ratatui::prelude::Color::Gray,
+                    };
+                    lines.push(Line::from(Span::styled(
+                        note,
+                        ratatui::prelude::Style::default().fg(color),
+                    )));
+                }
+                let card_p = Paragraph::new(lines)
+                    .block(
+                        Block::new()
+                            .title("card")
+                            .borders(Borders::ALL)
+                            .border_style(border)
+                            .border_type(border_type),
+                    )
+                    .alignment(Alignment::Center)
+                    .wrap(Wrap { trim: true });
 
                 f.render_widget(card_p, sub_inner_layout[chunk]);
             }
         }
     }
+    app.grid_cells = cells;
+}
+
+/// A panel per opponent: their hand, how many cards they hold, whether they're out and
+/// whose turn it is.
+fn render_opponents(f: &mut Frame, app: &App, area: Rect, side_by_side: bool) {
+    if app.opponents.is_empty() {
+        return;
+    }
+
+    let direction = match side_by_side {
+        true => Direction::Horizontal,
+        false => Direction::Vertical,
+    };
+    let count = app.opponents.len() as u32;
+    let areas = Layout::new(
+        direction,
+        app.opponents.iter().map(|_| Constraint::Ratio(1, count)),
+    )
+    .split(area);
+
+    for (opponent, area) in app.opponents.iter().zip(areas.iter()) {
+        let mut spans = Vec::new();
+        for card in opponent.deck.iter() {
+            let (color, letter) = match card.color {
+                davincicode::Color::BLACK => (ratatui::prelude::Color::Blue, "B"),
+                davincicode::Color::WHITE => (ratatui::prelude::Color::Yellow, "W"),
+            };
+            let (text, style) = match card.status {
+                davincicode::CardStatus::HIDDEN => (
+                    format!("{}?", letter),
+                    ratatui::prelude::Style::default().fg(color),
+                ),
+                davincicode::CardStatus::REVEALED => (
+                    format!("{}{}", letter, card.value),
+                    ratatui::prelude::Style::default()
+                        .fg(color)
+                        .add_modifier(ratatui::prelude::Modifier::BOLD),
+                ),
+            };
+            spans.push(Span::styled(text, style));
+            spans.push(Span::raw(" "));
+        }
+
+        let status = match opponent.out {
+            true => "out",
+            false => "playing",
+        };
+        let mut title = format!(
+            "{} ({}, {} cards)",
+            opponent.name,
+            status,
+            opponent.deck.len()
+        );
+        let border = if opponent.turn {
+            title.push_str(" their turn");
+            ratatui::prelude::Style::default()
+                .fg(ratatui::prelude::Color::Green)
+                .add_modifier(ratatui::prelude::Modifier::BOLD)
+        } else if opponent.out {
+            ratatui::prelude::Style::default().fg(ratatui::prelude::Color::DarkGray)
+        } else {
+            ratatui::prelude::Style::default()
+        };
+
+        let panel = Paragraph::new(vec![
+            Line::from(spans),
+            Line::from(format!("{} hidden", opponent.hidden())),
+        ])
+        .block(
+            Block::new()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(border),
+        )
+        .wrap(Wrap { trim: true });
+
+        f.render_widget(panel, *area);
+    }
 }
 
 pub fn deck_from_str(deck_str: String) -> Vec<davincicode::Card> {
@@ -565,6 +1764,44 @@ pub fn deck_from_str(deck_str: String) -> Vec<davincicode::Card> {
     ret
 }
 
+/// What the last prompt of the server asks us to pick, if there is one.
+fn parse_picking(input: &str) -> Option<Picking> {
+    let prompts = [
+        (prompt::DRAW_QUESTION, Picking::Pile),
+        ("Which card would you like to guess", Picking::Card),
+        ("Enter your guess: value between", Picking::Value(0)),
+        ("Pick current opponent", Picking::Nothing),
+        ("(yes/no)", Picking::Nothing),
+    ];
+    let (at, picking) = prompts
+        .iter()
+        .filter_map(|(prompt, picking)| input.rfind(prompt).map(|at| (at, *picking)))
+        .max_by_key(|(at, _)| *at)?;
+
+    // "value between (0-11)"
+    if picking == Picking::Value(0) {
+        let re_range = Regex::new(r"\(0-(\d+)\)").unwrap();
+        let max = re_range
+            .captures(&input[at..])
+            .and_then(|caps| caps[1].parse().ok())?;
+        return Some(Picking::Value(max));
+    }
+
+    Some(picking)
+}
+
+/// The opponent whose hand the server sends next, "The chosen opponent name is: bob".
+fn parse_opponent(input: &str) -> Option<String> {
+    let re_opponent =
+        Regex::new(r"The chosen opponent name is: (\S+)|Player: (\S+) Their deck").unwrap();
+
+    re_opponent
+        .captures_iter(input)
+        .last()
+        .and_then(|caps| caps.get(1).or(caps.get(2)))
+        .map(|name| name.as_str().to_string())
+}
+
 fn parse_responses(input: &str, pattern: &str) -> Option<String> {
     let ret: String;
 
@@ -572,6 +1809,8 @@ fn parse_responses(input: &str, pattern: &str) -> Option<String> {
     let re_asterisks = Regex::new(r"\*\*([^*]+)\*\*").unwrap();
     let re_plus = Regex::new(r"\+\+([^+]+)\+\+").unwrap();
     let re_pipe = Regex::new(r"\|\|([^|]+)\|\|").unwrap();
+    let re_dollar = Regex::new(r"\$\$([^$]+)\$\$").unwrap();
+    let re_at = Regex::new(r"@@([^@]+)@@").unwrap();
 
     let hashtags: Vec<&str> = re_hashtags
         .captures_iter(input)
@@ -593,6 +1832,16 @@ fn parse_responses(input: &str, pattern: &str) -> Option<String> {
         .map(|caps| caps.get(1).unwrap().as_str())
         .collect();
 
+    let dollar: Vec<&str> = re_dollar
+        .captures_iter(input)
+        .map(|caps| caps.get(1).unwrap().as_str())
+        .collect();
+
+    let at: Vec<&str> = re_at
+        .captures_iter(input)
+        .map(|caps| caps.get(1).unwrap().as_str())
+        .collect();
+
     match pattern {
         "##" => {
             if let Some(res) = hashtags.first() {
@@ -621,6 +1870,20 @@ fn parse_responses(input: &str, pattern: &str) -> Option<String> {
             }
         }
 
+        "$$" => {
+            if let Some(res) = dollar.first() {
+                ret = res.to_string();
+                return Some(ret);
+            }
+        }
+
+        "@@" => {
+            if let Some(res) = at.last() {
+                ret = res.to_string();
+                return Some(ret);
+            }
+        }
+
         _ => {
             //
         }
@@ -1,13 +1,33 @@
 use colored::Colorize;
 use rand::Rng;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::process;
 use std::sync::Arc;
 use std::{error::Error, io};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{Mutex, Semaphore};
-use tokio::time::{sleep, Duration};
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout, timeout_at, Duration, Instant};
+
+use davincicode::access::{self, Access, Secret};
+use davincicode::analysis;
+use davincicode::archive::{self, GameRecord, ServerCommand};
+use davincicode::bot;
+use davincicode::conn::{self, Conn, SlowPolicy};
+use davincicode::fairness;
+use davincicode::hint;
+use davincicode::notes;
+use davincicode::opponents;
+use davincicode::prompt;
+use davincicode::room::{self, IdlePolicy, Room};
+use davincicode::session::{self, Sessions};
+use davincicode::spectate::{self, Spectators, View};
+use davincicode::tls;
+use davincicode::transport::{Listener, Stream};
+use davincicode::web;
+use socket2::{SockRef, TcpKeepalive};
+use tokio_rustls::TlsAcceptor;
 
 use crossterm::event::poll;
 use crossterm::event::Event::Key;
@@ -24,6 +44,10 @@ use ratatui::widgets::*;
 use ratatui::Terminal;
 
 const GAME_END_CODE: i32 = -44;
+const PLAYER_GONE_CODE: i32 = -45;
+const PLAYER_IDLE_CODE: i32 = -46;
+const HANDSHAKE_TIMEOUT_SECS: u64 = 10;
+const KEEPALIVE_SECS: u64 = 10;
 const CARD_MAX_VAL: u32 = 11;
 const CARD_PER_PLAYER: u32 = 4;
 const START_CARD_N: u32 = 24;
@@ -45,27 +69,100 @@ struct Args {
     /// Number of players
     #[arg(short, long, default_value_t = 2)]
     nplayers: u16,
+
+    /// Seconds to wait for a disconnected player to come back before applying the idle policy
+    #[arg(short, long, default_value_t = session::DEFAULT_GRACE_SECS)]
+    grace: u64,
+
+    /// Seconds a player has for each decision, 0 to wait forever
+    #[arg(short, long, default_value_t = room::DEFAULT_DECISION_SECS)]
+    decision_timeout: u64,
+
+    /// What happens to players who time out or don't come back
+    #[arg(short, long, value_enum, default_value_t = IdlePolicy::AUTO)]
+    idle_policy: IdlePolicy,
+
+    /// What happens to players who read slower than the game writes to them
+    #[arg(long, value_enum, default_value_t = SlowPolicy::DISCONNECT)]
+    slow_clients: SlowPolicy,
+
+    /// Seconds spectators wait to see the whole table, 0 to only show them the revealed cards
+    #[arg(short, long, default_value_t = 0)]
+    spectator_delay: u64,
+
+    /// Port serving the browser client and its WebSocket connections, off by default
+    #[arg(short, long)]
+    web_port: Option<String>,
+
+    /// Unix domain socket to listen on, instead of the TCP port
+    #[arg(short, long, conflicts_with_all = ["tls_cert", "tls_key"])]
+    unix: Option<PathBuf>,
+
+    /// PEM certificate to serve TLS with, plain TCP if not given
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key of the TLS certificate
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Password players need to join the room
+    #[arg(long, conflicts_with = "invite")]
+    password: Option<String>,
+
+    /// Generate an invite code players need to join the room
+    #[arg(long)]
+    invite: bool,
+
+    /// Writes a self-signed cert.pem and key.pem for this address to a directory, and exits
+    #[arg(long)]
+    gen_cert: Option<PathBuf>,
+
+    /// File finished games are appended to
+    #[arg(long, default_value = archive::DEFAULT_FILE)]
+    archive: PathBuf,
+
+    /// Don't keep finished games
+    #[arg(long)]
+    no_archive: bool,
+
+    /// Ranked room: no hints for the players
+    #[arg(long)]
+    ranked: bool,
+
+    #[command(subcommand)]
+    command: Option<ServerCommand>,
 }
 
-async fn loop_read_uint(stream: &mut TcpStream, msg: String, range: Vec<u32>) -> Option<u32> {
+async fn loop_read_uint(
+    stream: &mut Conn,
+    the_game: &davincicode::Game,
+    msg: String,
+    range: Vec<u32>,
+    decision_timeout: Option<Duration>,
+) -> Result<u32, i32> {
     let result: u32;
 
     if range.len() > 2 {
         println!("Only 2 value allowed (min-max).");
-        return None;
+        return Err(-1);
     }
 
     let max = range[1];
+    let deadline = decision_timeout.map(|decision_timeout| Instant::now() + decision_timeout);
+    // whatever was typed out of turn isn't an answer to this
+    stream.discard_pending();
+
     loop {
         let mut to_send = String::new();
         let msg = format!("{} (0-{})", msg, max);
 
-        to_send.push_str(format!("{}\n", msg).as_str());
-        if send_something(stream, &to_send).await {
-            return None;
+        to_send.push_str(&prompt::question_line(&msg));
+        if send_in_game(stream, the_game, &to_send).await {
+            return Err(PLAYER_GONE_CODE);
         }
 
-        let response = recv_something(stream).await?;
+        let response = recv_before(stream, deadline).await?;
         let parsed_response = response.parse::<u32>();
 
         if let Ok(value) = parsed_response {
@@ -76,45 +173,51 @@ async fn loop_read_uint(stream: &mut TcpStream, msg: String, range: Vec<u32>) ->
         }
     }
 
-    Some(result)
+    Ok(result)
 }
 
 async fn loop_read_str(
-    stream: &mut TcpStream,
+    stream: &mut Conn,
+    the_game: &davincicode::Game,
     msg: String,
     variant: Vec<String>,
-) -> Option<String> {
+    decision_timeout: Option<Duration>,
+) -> Result<String, i32> {
     if variant.len() > 2 {
         println!("Only 2 variants allowed.");
-        return None;
+        return Err(-1);
     }
     let v0 = variant[0].clone();
     let v1 = variant[1].clone();
+    let deadline = decision_timeout.map(|decision_timeout| Instant::now() + decision_timeout);
+    // whatever was typed out of turn isn't an answer to this
+    stream.discard_pending();
 
     loop {
         let mut to_send = String::new();
         let msg = format!("{} ({}/{})", msg, variant[0], variant[1]);
 
-        to_send.push_str(format!("{}\n", msg).as_str());
-        if send_something(stream, &to_send).await {
-            return None;
+        to_send.push_str(&prompt::question_line(&msg));
+        if send_in_game(stream, the_game, &to_send).await {
+            return Err(PLAYER_GONE_CODE);
         }
 
-        let response = recv_something(stream).await?;
+        let response = recv_before(stream, deadline).await?;
 
         let value = response.trim();
         if value == v0 || value == v1 {
-            return Some(response);
+            return Ok(response);
         }
     }
 }
 
 async fn yes_variant_guess<B: Backend>(
     terminal: &mut Terminal<B>,
-    stream: &mut TcpStream,
+    stream: &mut Conn,
     player_name: String,
     the_game: &mut davincicode::Game,
     dialog_status: &mut i32,
+    room: &Room<Stream>,
 ) -> i32 {
     // loop to keep guessing the opponent card
     loop {
@@ -153,18 +256,33 @@ async fn yes_variant_guess<B: Backend>(
             the_game,
             opponents_names.clone(),
             &player_name,
+            room,
         )
         .await;
 
+        if returned_val == PLAYER_GONE_CODE || returned_val == PLAYER_IDLE_CODE {
+            *dialog_status = returned_val;
+            break;
+        }
+
         if returned_val == 1 {
             // good guess, either continue or break and save hidden card
-            let response = loop_read_str(
+            offer_hint(stream, the_game, &player_name, room);
+            let response = match loop_read_str(
                 stream,
+                the_game,
                 "It's your turn: Would you like to make another guess?".to_string(),
                 vec!["yes".to_string(), "no".to_string()],
+                room.decision_timeout,
             )
             .await
-            .expect("Got no response");
+            {
+                Ok(response) => response,
+                Err(code) => {
+                    *dialog_status = code;
+                    break;
+                }
+            };
 
             match response.as_str() {
                 "yes" => {
@@ -195,8 +313,9 @@ async fn yes_variant_guess<B: Backend>(
 async fn player_move<B: Backend>(
     terminal: &mut Terminal<B>,
     player_name: String,
-    player_tcp_name: &mut HashMap<String, TcpStream>,
+    player_tcp_name: &mut HashMap<String, Conn>,
     the_game: &mut davincicode::Game,
+    room: &Room<Stream>,
 ) -> i32 {
     let mut dialog_status: i32 = -1;
     let stream = player_tcp_name.get_mut(&(player_name.clone())).unwrap();
@@ -206,10 +325,6 @@ async fn player_move<B: Backend>(
     let max_card_avail_value = the_game.card_avail.len() as u32;
     let mut can_t_draw_any: bool = false;
 
-    if max_card_avail_value < 1 {
-        can_t_draw_any = true;
-    }
-
     the_game.shuffle_avail_card();
 
     let current_player = the_game
@@ -218,6 +333,13 @@ async fn player_move<B: Backend>(
         .find(|player| player.name == player_name)
         .expect("No player found\n");
 
+    // a player resuming their turn after a reconnection already holds the card they drew
+    let mut current_player_side_card: Option<davincicode::Card> = current_player.side_card;
+
+    if max_card_avail_value < 1 && current_player_side_card.is_none() {
+        can_t_draw_any = true;
+    }
+
     if can_t_draw_any {
         // no more cards on the set to draw, so take turn guessing op card
         game_context
@@ -238,7 +360,7 @@ async fn player_move<B: Backend>(
     }
     game_context.push_str(format!("\n{}", "Your opponents deck: ").as_str());
     for opponent in the_game.players.iter() {
-        if opponent.name == *opponents_names.first().unwrap() {
+        if opponents_names.contains(&opponent.name) {
             game_context.push_str(format!("\n{}", "Player: ").as_str());
             game_context.push_str(&opponent.name);
             game_context.push(' ');
@@ -246,16 +368,16 @@ async fn player_move<B: Backend>(
             game_context.push('\n');
         }
     }
-    if send_something(stream, &game_context).await {
-        return 1;
+    if send_in_game(stream, the_game, &game_context).await {
+        return PLAYER_GONE_CODE;
     }
 
     game_context.clear();
     game_context.push_str("Your deck: ##");
     game_context.push_str(&current_player.show_hand(false, false));
     game_context.push_str("##\n");
-    if send_something(stream, &game_context).await {
-        return 1;
+    if send_in_game(stream, the_game, &game_context).await {
+        return PLAYER_GONE_CODE;
     }
 
     game_context.clear();
@@ -263,36 +385,35 @@ async fn player_move<B: Backend>(
     game_context.push_str(&current_player.show_hand(true, false));
     game_context.push('\n');
 
-    if send_something(stream, &game_context).await {
-        return 1;
+    if send_in_game(stream, the_game, &game_context).await {
+        return PLAYER_GONE_CODE;
     }
 
     // read user input (card to pick)
     let picked_card_number: usize;
-    let mut current_player_side_card: Option<davincicode::Card> = None;
 
-    if !can_t_draw_any {
+    if !can_t_draw_any && current_player_side_card.is_none() {
         let to_send = format!("{} {}\n", "It's your turn", player_name,);
-        if send_something(stream, &to_send).await {
-            return 1;
+        if send_in_game(stream, the_game, &to_send).await {
+            return PLAYER_GONE_CODE;
         }
 
-        let value = loop_read_uint(
+        let value = match loop_read_uint(
             stream,
-            "Enter card number to draw it".to_string(),
+            the_game,
+            prompt::DRAW_QUESTION.to_string(),
             vec![0, max_card_avail_value - 1],
+            room.decision_timeout,
         )
         .await
-        .expect("Got no response");
+        {
+            Ok(value) => value,
+            Err(code) => return code,
+        };
 
         picked_card_number = value as usize;
         // draw the card here
-        for p in the_game.players.iter_mut() {
-            if p.name == player_name.clone() {
-                current_player_side_card =
-                    Some(p.draw_specific_card(&mut the_game.card_avail, picked_card_number));
-            }
-        }
+        current_player_side_card = the_game.draw(&player_name, picked_card_number);
 
         let _ = update_ui(terminal, the_game).await;
     }
@@ -320,18 +441,23 @@ async fn player_move<B: Backend>(
             to_send.push_str(&the_game.show_avail_cards(true, false));
             to_send.push_str("**\n");
 
-            if send_something(stream, &to_send).await {
-                return 1;
+            if send_in_game(stream, the_game, &to_send).await {
+                return PLAYER_GONE_CODE;
             }
 
             // player drawn a card, so they can decide not to make a guess
-            let response = loop_read_str(
+            let response = match loop_read_str(
                 stream,
+                the_game,
                 "It's your turn: Would you like to make a guess?".to_string(),
                 vec!["yes".to_string(), "no".to_string()],
+                room.decision_timeout,
             )
             .await
-            .expect("Got no response");
+            {
+                Ok(response) => response,
+                Err(code) => return code,
+            };
 
             match response.as_str() {
                 "yes" => {
@@ -342,6 +468,7 @@ async fn player_move<B: Backend>(
                         player_name.to_string(),
                         the_game,
                         &mut dialog_status,
+                        room,
                     )
                     .await;
                 }
@@ -362,20 +489,21 @@ async fn player_move<B: Backend>(
                 player_name.to_string(),
                 the_game,
                 &mut dialog_status,
+                room,
             )
             .await;
         }
     }
 
     match dialog_status {
+        PLAYER_GONE_CODE | PLAYER_IDLE_CODE => {
+            // lost the connection or ran out of time mid-turn, the side card (if any) stays with the player
+            return dialog_status;
+        }
         1 => {
             // player picked a card, and decided to keep it
             // or picked a card, guessed and won too and refused to keep guessing
-            for p in the_game.players.iter_mut() {
-                if p.name == player_name.clone() {
-                    p.save_side_card(true);
-                }
-            }
+            the_game.save_side_card(&player_name, true);
 
             let mut to_send: String = "Okay, saving your side card as hidden.\n".to_owned();
 
@@ -386,7 +514,7 @@ async fn player_move<B: Backend>(
                 }
             }
             to_send.push_str("##");
-            if send_something(stream, &to_send).await {
+            if send_in_game(stream, the_game, &to_send).await {
                 return 1;
             }
 
@@ -398,17 +526,13 @@ async fn player_move<B: Backend>(
                     to_send.push_str(&p.show_hand(true, false));
                 }
             }
-            if send_something(stream, &to_send).await {
+            if send_in_game(stream, the_game, &to_send).await {
                 return 1;
             }
         }
         2 => {
             // player picked a card, guess and lost, revealing their card
-            for p in the_game.players.iter_mut() {
-                if p.name == player_name.clone() {
-                    p.save_side_card(false);
-                }
-            }
+            the_game.save_side_card(&player_name, false);
 
             let mut to_send = String::new();
 
@@ -423,7 +547,7 @@ async fn player_move<B: Backend>(
                 }
             }
             to_send.push_str("##");
-            if send_something(stream, &to_send).await {
+            if send_in_game(stream, the_game, &to_send).await {
                 return 1;
             }
 
@@ -435,7 +559,7 @@ async fn player_move<B: Backend>(
                 }
             }
 
-            if send_something(stream, &to_send).await {
+            if send_in_game(stream, the_game, &to_send).await {
                 return 1;
             }
         }
@@ -443,7 +567,7 @@ async fn player_move<B: Backend>(
             // game ended
             // announce this to the remaining player
             let to_send = "You won! Congrats!";
-            if send_something(stream, to_send).await {
+            if send_in_game(stream, the_game, to_send).await {
                 return 1;
             }
 
@@ -464,10 +588,11 @@ async fn player_move<B: Backend>(
 
 async fn guess_opponent_card_loop<B: Backend>(
     terminal: &mut Terminal<B>,
-    stream: &mut TcpStream,
+    stream: &mut Conn,
     the_game: &mut davincicode::Game,
     opponents_names: Vec<String>,
     player_name: &str,
+    room: &Room<Stream>,
 ) -> i32 {
     let mut picked_card_number: usize;
     let mut golden_value: u32 = u32::MAX;
@@ -477,6 +602,8 @@ async fn guess_opponent_card_loop<B: Backend>(
     let mut skip_chose_op = false;
 
     loop {
+        offer_hint(stream, the_game, player_name, room);
+
         if opponents_names.len() == 1 {
             skip_chose_op = true;
         }
@@ -500,27 +627,32 @@ async fn guess_opponent_card_loop<B: Backend>(
             );
         }
 
-        if send_something(stream, &to_send).await {
-            return -2;
+        if send_in_game(stream, the_game, &to_send).await {
+            return PLAYER_GONE_CODE;
         }
 
         let mut op_idx = 0;
         if !skip_chose_op {
             // pick the opponent
-            op_idx = loop_read_uint(
+            op_idx = match loop_read_uint(
                 stream,
+                the_game,
                 "It's your turn! Pick current opponent for this guess: \n".to_string(),
                 vec![0, opponents_names.len() as u32 - 1],
+                room.decision_timeout,
             )
             .await
-            .expect("Got no response");
+            {
+                Ok(value) => value,
+                Err(code) => return code,
+            };
         } // else, skip, and the op_idx will be 0
 
         let opponent_name_ = String::from(opponents_names.get(op_idx as usize).unwrap()); //.unwrap());
 
         let to_send = format!("{} {}", "The chosen opponent name is:", opponent_name_,);
-        if send_something(stream, &to_send).await {
-            return -2;
+        if send_in_game(stream, the_game, &to_send).await {
+            return PLAYER_GONE_CODE;
         }
 
         the_game
@@ -537,45 +669,50 @@ async fn guess_opponent_card_loop<B: Backend>(
             }
         }
         to_send.push_str("++\n");
-        if send_something(stream, &to_send).await {
-            return -2;
+        if send_in_game(stream, the_game, &to_send).await {
+            return PLAYER_GONE_CODE;
         }
 
         to_send.clear();
         to_send.push_str(format!("\n{}", "Your current deck: ##").as_str());
         for p in the_game.players.iter() {
-            if p.name != opponent_name_ {
-                // so it gotta be the current player
+            // with more than one opponent, not every other player is the current one
+            if p.name == player_name {
                 to_send.push_str(&p.show_hand(false, false));
             }
         }
         to_send.push_str("##");
-        if send_something(stream, &to_send).await {
-            return -2;
+        if send_in_game(stream, the_game, &to_send).await {
+            return PLAYER_GONE_CODE;
         }
 
         to_send.clear();
         to_send.push_str("What your opponents see: ");
         to_send.push_str("");
         for p in the_game.players.iter() {
-            if p.name != opponent_name_ {
-                // so it gotta be the current player
+            // with more than one opponent, not every other player is the current one
+            if p.name == player_name {
                 to_send.push_str(&p.show_hand(true, false));
             }
         }
 
-        if send_something(stream, &to_send).await {
-            return -2;
+        if send_in_game(stream, the_game, &to_send).await {
+            return PLAYER_GONE_CODE;
         }
 
         // request the player which opponent card they want to guess the value
-        let value = loop_read_uint(
+        let value = match loop_read_uint(
             stream,
+            the_game,
             "It's your turn: Which card would you like to guess".to_string(),
             vec![0, opponent_deck_len - 1],
+            room.decision_timeout,
         )
         .await
-        .expect("Got no response");
+        {
+            Ok(value) => value,
+            Err(code) => return code,
+        };
 
         picked_card_number = value as usize;
 
@@ -587,10 +724,9 @@ async fn guess_opponent_card_loop<B: Backend>(
         // only 1 opponent, read their specific card value
         for opponent in the_game.players.iter() {
             if opponent.name == opponent_name_ {
-                golden_value = match opponent.get_specific_card_value(picked_card_number) {
-                    None => u32::MAX,
-                    Some(val) => val,
-                };
+                golden_value = opponent
+                    .get_specific_card_value(picked_card_number)
+                    .unwrap_or(u32::MAX);
             }
         }
         // valid pick?
@@ -599,16 +735,39 @@ async fn guess_opponent_card_loop<B: Backend>(
             continue;
         }
         // request the player to give their guessed value of the card
-        let guessed_value = loop_read_uint(
+        let guessed_value = match loop_read_uint(
             stream,
+            the_game,
             "It's your turn: Enter your guess: value between".to_string(),
             vec![0, CARD_MAX_VAL],
+            room.decision_timeout,
         )
         .await
-        .expect("Got no response");
+        {
+            Ok(value) => value,
+            Err(code) => return code,
+        };
+
+        // evaluate the guess, the opponent card gets revealed when right
+        let right = the_game.guess(
+            player_name,
+            &opponent_name_,
+            picked_card_number,
+            guessed_value,
+        ) == Some(true);
+
+        room.spectators
+            .public(&format!(
+                "{} guessed {}'s card {} is {}: {}\n",
+                player_name,
+                opponent_name_,
+                picked_card_number,
+                guessed_value,
+                if right { "right" } else { "wrong" }
+            ))
+            .await;
 
-        // evaluate the guess
-        if golden_value == guessed_value {
+        if right {
             correct_guess = true;
             the_game
                 .logs
@@ -618,16 +777,8 @@ async fn guess_opponent_card_loop<B: Backend>(
             // ack correct guess
             let mut to_send = String::new();
             to_send.push_str("You got it right! Guessed card revealed\n");
-            if send_something(stream, &to_send).await {
-                return -2;
-            }
-
-            // mutate the opponent card to revealed
-            for opponent in the_game.players.iter_mut() {
-                if opponent.name == opponent_name_ {
-                    // the opponent card get's revealed
-                    opponent.reveal_card(picked_card_number);
-                }
+            if send_in_game(stream, the_game, &to_send).await {
+                return PLAYER_GONE_CODE;
             }
 
             let mut to_send = String::new();
@@ -639,8 +790,8 @@ async fn guess_opponent_card_loop<B: Backend>(
                 }
             }
             to_send.push_str("++\n");
-            if send_something(stream, &to_send).await {
-                return -2;
+            if send_in_game(stream, the_game, &to_send).await {
+                return PLAYER_GONE_CODE;
             }
             break;
         } else {
@@ -656,11 +807,314 @@ async fn guess_opponent_card_loop<B: Backend>(
     0
 }
 
+async fn play_turn<B: Backend>(
+    terminal: &mut Terminal<B>,
+    player_name: String,
+    player_tcp_name: &mut HashMap<String, Conn>,
+    the_game: &mut davincicode::Game,
+    room: &Room<Stream>,
+) -> i32 {
+    show_table(the_game, room).await;
+    room.spectators
+        .public(&format!("\nIt's {}'s turn\n", player_name))
+        .await;
+
+    loop {
+        absorb_rejoins(terminal, player_tcp_name, the_game, room).await;
+
+        if room.access.is_kicked(&player_name).await {
+            return kick_out(terminal, player_tcp_name, the_game, room, &player_name).await;
+        }
+
+        // the seat of a player who went idle is played by a bot until they come back
+        if room.is_bot(&player_name).await {
+            let summary = bot::play_turn(the_game, &player_name);
+            return announce_turn(terminal, player_tcp_name, the_game, &summary, room).await;
+        }
+
+        let ret = player_move(
+            terminal,
+            player_name.clone(),
+            player_tcp_name,
+            the_game,
+            room,
+        )
+        .await;
+        // the hints were about that turn
+        if let Some(stream) = player_tcp_name.get(&player_name) {
+            stream.clear_hint();
+        }
+        match ret {
+            PLAYER_IDLE_CODE => {
+                let reason = format!("didn't answer in {}s", decision_secs(room));
+                return idle_out(
+                    terminal,
+                    player_tcp_name,
+                    the_game,
+                    room,
+                    &player_name,
+                    &reason,
+                )
+                .await;
+            }
+            PLAYER_GONE_CODE => {}
+            _ => return ret,
+        }
+
+        // pause the turn until the player comes back or the grace period runs out
+        the_game.logs.push_str(
+            format!(
+                "{} lost their connection, waiting {}s\n",
+                player_name,
+                room.sessions.grace.as_secs()
+            )
+            .as_str(),
+        );
+        let _ = update_ui(terminal, the_game).await;
+        broadcast_msg(
+            player_tcp_name,
+            the_game,
+            &format!(
+                "\n{} lost their connection, waiting for them.\n",
+                player_name
+            ),
+            room,
+        )
+        .await;
+
+        match wait_for_rejoin(room, &player_name).await {
+            Some(stream) => {
+                rejoin(
+                    terminal,
+                    player_tcp_name,
+                    the_game,
+                    room,
+                    &player_name,
+                    stream,
+                )
+                .await;
+            }
+            None => {
+                // they can still come back later and take their seat back
+                let reason = "didn't come back";
+                return idle_out(
+                    terminal,
+                    player_tcp_name,
+                    the_game,
+                    room,
+                    &player_name,
+                    reason,
+                )
+                .await;
+            }
+        }
+    }
+}
+
+async fn absorb_rejoins<B: Backend>(
+    terminal: &mut Terminal<B>,
+    player_tcp_name: &mut HashMap<String, Conn>,
+    the_game: &mut davincicode::Game,
+    room: &Room<Stream>,
+) {
+    let names: Vec<String> = player_tcp_name.keys().cloned().collect();
+
+    for name in names {
+        if let Some(stream) = room.sessions.take(&name).await {
+            rejoin(terminal, player_tcp_name, the_game, room, &name, stream).await;
+        }
+    }
+}
+
+async fn wait_for_rejoin(room: &Room<Stream>, player_name: &str) -> Option<Stream> {
+    let rejoined = room.sessions.wait_for(player_name);
+    tokio::pin!(rejoined);
+
+    loop {
+        tokio::select! {
+            stream = &mut rejoined => {
+                return stream;
+            }
+            _ = event_task() => {}
+        }
+    }
+}
+
+async fn rejoin<B: Backend>(
+    terminal: &mut Terminal<B>,
+    player_tcp_name: &mut HashMap<String, Conn>,
+    the_game: &mut davincicode::Game,
+    room: &Room<Stream>,
+    player_name: &str,
+    stream: Stream,
+) {
+    // the old connection is dropped, the player gets a full view of the game on the new one
+    let mut conn = Conn::spawn(
+        stream,
+        player_name,
+        Arc::clone(&room.chat),
+        room.access.door(player_name).await,
+        room.slow_policy,
+    )
+    .await;
+    let _ = send_in_game(&mut conn, the_game, &resync_view(the_game, player_name)).await;
+    player_tcp_name.insert(player_name.to_string(), conn);
+
+    the_game
+        .logs
+        .push_str(format!("{} reconnected\n", player_name).as_str());
+    if room.give_back(player_name).await {
+        the_game
+            .logs
+            .push_str(format!("{} takes their seat back from the bot\n", player_name).as_str());
+    }
+    let _ = update_ui(terminal, the_game).await;
+}
+
+/// Applies the idle policy of the room to a player who went silent.
+async fn idle_out<B: Backend>(
+    terminal: &mut Terminal<B>,
+    player_tcp_name: &mut HashMap<String, Conn>,
+    the_game: &mut davincicode::Game,
+    room: &Room<Stream>,
+    player_name: &str,
+    reason: &str,
+) -> i32 {
+    let mut summary = format!(
+        "\n{} {}, {}.\n",
+        player_name,
+        reason,
+        room.idle_policy.describe()
+    );
+
+    match room.idle_policy {
+        IdlePolicy::AUTO => summary.push_str(&bot::safe_move(the_game, player_name)),
+        IdlePolicy::BOT => {
+            room.hand_to_bot(player_name).await;
+            summary.push_str(&bot::play_turn(the_game, player_name));
+        }
+        IdlePolicy::FORFEIT => {
+            the_game.forfeit(player_name);
+        }
+    }
+
+    announce_turn(terminal, player_tcp_name, the_game, &summary, room).await
+}
+
+/// Takes a player the host sent away out of the game.
+async fn kick_out<B: Backend>(
+    terminal: &mut Terminal<B>,
+    player_tcp_name: &mut HashMap<String, Conn>,
+    the_game: &mut davincicode::Game,
+    room: &Room<Stream>,
+    player_name: &str,
+) -> i32 {
+    the_game.forfeit(player_name);
+
+    let summary = format!(
+        "\n{} was sent away by the host, all their cards get revealed.\n",
+        player_name
+    );
+    announce_turn(terminal, player_tcp_name, the_game, &summary, room).await
+}
+
+/// Tells everyone what happened during a turn nobody answered prompts for.
+async fn announce_turn<B: Backend>(
+    terminal: &mut Terminal<B>,
+    player_tcp_name: &mut HashMap<String, Conn>,
+    the_game: &mut davincicode::Game,
+    summary: &str,
+    room: &Room<Stream>,
+) -> i32 {
+    the_game.logs.push_str(summary);
+    let _ = update_ui(terminal, the_game).await;
+    broadcast_msg(player_tcp_name, the_game, summary, room).await;
+
+    // same as player_move: 0 once the game is over
+    if the_game.game_status() {
+        return 0;
+    }
+
+    2
+}
+
+/// Sends the spectators the table, masked or revealed depending on what they may see.
+async fn show_table(the_game: &davincicode::Game, room: &Room<Stream>) {
+    room.spectators
+        .table(
+            format!("Table @@{}@@\n", spectate::table_view(the_game, false)),
+            format!("Table @@{}@@\n", spectate::table_view(the_game, true)),
+        )
+        .await;
+}
+
+fn decision_secs(room: &Room<Stream>) -> u64 {
+    room.decision_timeout
+        .map(|decision_timeout| decision_timeout.as_secs())
+        .unwrap_or(0)
+}
+
+fn resync_view(the_game: &davincicode::Game, player_name: &str) -> String {
+    let mut to_send = format!("\nWelcome back {}, resuming the game.\n", player_name);
+
+    let player = the_game
+        .players
+        .iter()
+        .chain(the_game.lost_players.iter())
+        .find(|player| player.name == player_name);
+
+    if let Some(player) = player {
+        to_send.push_str("Your deck: ##");
+        to_send.push_str(&player.show_hand(false, false));
+        to_send.push_str("##\n");
+
+        if let Some(card) = player.side_card {
+            match card.color {
+                davincicode::Color::BLACK => {
+                    to_send.push_str(format!("Your side card: B{}\n", card.value).as_str());
+                }
+                davincicode::Color::WHITE => {
+                    to_send.push_str(format!("Your side card: W{}\n", card.value).as_str());
+                }
+            }
+        }
+
+        to_send.push_str("What your opponents see: ");
+        to_send.push_str(&player.show_hand(true, false));
+        to_send.push('\n');
+    }
+
+    if !the_game.card_avail.is_empty() {
+        to_send.push_str("All avail cards: **");
+        to_send.push_str(&the_game.show_avail_cards(true, false));
+        to_send.push_str("**\n");
+    }
+
+    let mut opponents = the_game
+        .players
+        .iter()
+        .filter(|opponent| opponent.name != player_name);
+
+    if let Some(opponent) = opponents.next() {
+        to_send.push_str(format!("Player: {} Their deck: ++", opponent.name).as_str());
+        to_send.push_str(&opponent.show_hand(true, false));
+        to_send.push_str("++\n");
+    }
+    for opponent in opponents {
+        to_send.push_str(format!("Player: {} ", opponent.name).as_str());
+        to_send.push_str(&opponent.show_hand(true, false));
+        to_send.push('\n');
+    }
+
+    to_send
+}
+
 async fn game_process<B: Backend>(
     terminal: &mut Terminal<B>,
     the_game: &mut davincicode::Game,
     selected_player_name: String,
-    player_tcp_name: &mut HashMap<String, TcpStream>,
+    player_tcp_name: &mut HashMap<String, Conn>,
+    room: &Room<Stream>,
 ) -> i32 {
     let mut player_order: Vec<String> = Vec::new();
     let mut current_player = selected_player_name.clone();
@@ -678,7 +1132,7 @@ async fn game_process<B: Backend>(
 
             if player_tcp_name.contains_key(&lost_player.name.clone()) {
                 let stream = player_tcp_name.get_mut(&lost_player.name.clone()).unwrap();
-                send_something(stream, &to_send).await;
+                send_in_game(stream, the_game, &to_send).await;
             }
         }
 
@@ -710,12 +1164,30 @@ async fn game_process<B: Backend>(
                 // the current player has been dropped cause they lost
                 continue;
             }
-            if player_move(terminal, player.to_string(), player_tcp_name, the_game).await == 0 {
+            send_opponents(player_tcp_name, the_game, player).await;
+            let turn = play_turn(
+                terminal,
+                player.to_string(),
+                player_tcp_name,
+                the_game,
+                room,
+            )
+            .await;
+
+            // where the saved cards went, for the players' notes
+            let inserted = notes::inserted_markers(the_game);
+            for client_stream in player_tcp_name.values_mut() {
+                send_in_game(client_stream, the_game, &inserted).await;
+            }
+            if turn == 0 {
                 break;
             }
         }
     }
 
+    // how the table ended up
+    send_opponents(player_tcp_name, the_game, "").await;
+
     the_game
         .logs
         .push_str(format!("{}\n", "left game_process").as_str());
@@ -727,8 +1199,10 @@ async fn game_process<B: Backend>(
 async fn game_run(
     player_names: HashMap<u32, String>,
     selected_player_name: String,
-    player_tcp_name: &mut HashMap<String, TcpStream>,
+    player_tcp_name: &mut HashMap<String, Conn>,
+    room: &Room<Stream>,
 ) -> Result<(), Box<dyn Error>> {
+    let started = archive::now();
     let mut the_game = davincicode::Game::new(START_CARD_N);
 
     for player in player_names.iter() {
@@ -750,6 +1224,7 @@ async fn game_run(
         &mut the_game,
         selected_player_name,
         player_tcp_name,
+        room,
     )
     .await;
 
@@ -765,6 +1240,7 @@ async fn game_run(
     if let Err(err) = res {
         println!("{err:?}");
     }
+    archive_game(&the_game, started, room);
     println!("exit successfully");
 
     Ok(())
@@ -908,33 +1384,40 @@ fn ui2(f: &mut Frame, the_game: &davincicode::Game) {
     }
 }
 
-async fn init_players(client_streams_vec: Arc<Mutex<Vec<TcpStream>>>) {
-    let mut streams = client_streams_vec.lock().await;
-    let num_clients = streams.len();
-
-    if num_clients == 0 {
+async fn init_players(players: Vec<(String, Stream)>, room: Arc<Room<Stream>>) {
+    if players.is_empty() {
         return;
     }
 
     let mut player_names: HashMap<u32, String> = HashMap::new();
-    let mut player_tcp_name: HashMap<String, TcpStream> = HashMap::new();
-
-    // rq to clients to identify themselves
-    for (index, client_stream) in streams.iter_mut().enumerate() {
-        let request = recv_something(client_stream).await.unwrap();
+    let mut player_tcp_name: HashMap<String, Conn> = HashMap::new();
 
-        let name = request.trim().to_string();
+    for (index, (name, stream)) in players.into_iter().enumerate() {
         player_names.insert(index as u32, name.clone());
-    }
 
-    let indices: Vec<usize> = (0..player_names.len()).rev().collect();
-    for idx in indices {
-        let name = player_names.get(&(idx as u32)).unwrap();
-        let tcp_stream = streams.remove(idx);
-        player_tcp_name.insert(name.to_string(), tcp_stream);
+        let door = room.access.door(&name).await;
+        let conn = Conn::spawn(
+            stream,
+            &name,
+            Arc::clone(&room.chat),
+            door,
+            room.slow_policy,
+        )
+        .await;
+        player_tcp_name.insert(name, conn);
     }
 
-    println!("Players {:?}", player_tcp_name);
+    println!("Players {:?}", player_tcp_name.keys());
+
+    // the token lets a player take their seat back if their connection drops
+    for (name, client_stream) in player_tcp_name.iter_mut() {
+        let token = room.sessions.issue(name).await;
+        send_something(
+            client_stream,
+            &format!("Your session token: $${}$$\n", token),
+        )
+        .await;
+    }
 
     let mut rng = rand::rng();
     let selected_player_index = rng.random_range(0..player_tcp_name.len());
@@ -948,7 +1431,7 @@ async fn init_players(client_streams_vec: Arc<Mutex<Vec<TcpStream>>>) {
     let selected_player_name = some_player_name.clone();
 
     // tell the randomly selected player to move
-    println!("Picked {:?} as the first to move.\n", some_player_stream);
+    println!("Picked {} as the first to move.\n", some_player_name);
 
     let mut to_send = String::new();
     to_send.push_str(format!("{}\n", "It's your turn, move").as_str());
@@ -974,81 +1457,126 @@ async fn init_players(client_streams_vec: Arc<Mutex<Vec<TcpStream>>>) {
         player_names.clone(),
         selected_player_name,
         &mut player_tcp_name,
+        &room,
     )
     .await;
+    // let the last messages through before hanging up
+    for (_, conn) in player_tcp_name.drain() {
+        conn.finish().await;
+    }
 }
 
-async fn handle_client(client_id: &mut u32, client_streams_vec: Arc<Mutex<Vec<TcpStream>>>) {
-    let mut buffer = [0u8; 1024];
-
-    let mut streams = client_streams_vec.lock().await;
-    let stream = &mut streams[*client_id as usize];
-    let mut init_player: bool = false;
+/// Tells every player about their opponents, as the turn of `turn` starts.
+async fn send_opponents(
+    player_tcp_name: &mut HashMap<String, Conn>,
+    the_game: &davincicode::Game,
+    turn: &str,
+) {
+    for (name, client_stream) in player_tcp_name.iter_mut() {
+        let markers = opponents::opponent_markers(the_game, name, turn);
+        send_in_game(client_stream, the_game, &markers).await;
+    }
+}
 
-    loop {
-        buffer.fill(0);
-        let bytes_read = match stream.read(&mut buffer).await {
-            Ok(bytes_read) => bytes_read,
-            Err(error) => {
-                eprintln!("Error reading from client {}: {}", client_id, error);
-                break;
-            }
-        };
+async fn broadcast_msg(
+    player_tcp_name: &mut HashMap<String, Conn>,
+    the_game: &davincicode::Game,
+    cmd: &str,
+    room: &Room<Stream>,
+) {
+    room.spectators.public(cmd).await;
 
-        if bytes_read == 0 {
-            println!(
-                "{} {} {}",
-                "Client".red(),
-                client_id,
-                "has disconnected".red()
-            );
-            break;
+    for client_stream in player_tcp_name.values_mut() {
+        if send_in_game(client_stream, the_game, cmd).await {
+            continue;
         }
+    }
+}
 
-        let request = String::from_utf8_lossy(&buffer[..bytes_read]);
-        println!(
-            "{} {} {}",
-            "Received from client".blue(),
-            client_id,
-            request
-        );
+/// Appends the game to the room's archive, once it's over.
+fn archive_game(the_game: &davincicode::Game, started: u64, room: &Room<Stream>) {
+    let path = match &room.archive {
+        Some(path) if the_game.state == davincicode::GameState::END => path,
+        _ => return,
+    };
 
-        if request == "init" {
-            if let Err(error) = stream
-                .write_all(String::from("Init successfull").as_bytes())
-                .await
-            {
-                eprintln!("Error writing response to client {}: {}", client_id, error);
-                break;
-            }
+    match archive::append(path, &GameRecord::of(the_game, started)) {
+        Ok(()) => println!("{} {}", "Game saved to".green(), path.display()),
+        Err(error) => eprintln!("{} {}", "Can't archive the game:".red(), error),
+    }
+}
 
-            init_player = true;
-            break;
+/// Lets the player ask for hints on the guess they're about to make.
+fn offer_hint(stream: &Conn, the_game: &davincicode::Game, player_name: &str, room: &Room<Stream>) {
+    let hint = match room.ranked {
+        true => String::from(hint::RANKED),
+        false => hint::advice(the_game, player_name),
+    };
+    stream.set_hint(the_game, hint);
+}
+
+/// Tells everybody how each decision of the game compared with the odds.
+async fn send_analysis(
+    player_tcp_name: &mut HashMap<String, Conn>,
+    the_game: &davincicode::Game,
+    room: &Room<Stream>,
+) {
+    match analysis::Report::of(&GameRecord::of(the_game, 0)) {
+        Ok(report) => {
+            let report = format!("\n{}", report);
+            room.spectators.public(&report).await;
+            for client_stream in player_tcp_name.values_mut() {
+                send_something(client_stream, &report).await;
+            }
         }
+        Err(error) => eprintln!("{} {}", "Can't analyze the game:".red(), error),
     }
+}
 
-    if !init_player {
-        streams.remove(*client_id as usize);
-        *client_id -= 1;
+/// Sends each player the seed and the log of the pile, so they can check the shuffles.
+async fn send_reveals(player_tcp_name: &mut HashMap<String, Conn>, the_game: &davincicode::Game) {
+    for (name, client_stream) in player_tcp_name.iter_mut() {
+        if let Some(reveal) = fairness::Reveal::of(the_game, name) {
+            send_something(client_stream, &format!("{}\n", reveal.to_marker())).await;
+        }
     }
 }
 
-async fn broadcast_msg(player_tcp_name: &mut HashMap<String, TcpStream>, cmd: &str) {
-    for client_stream in player_tcp_name.values_mut() {
-        if send_something(client_stream, cmd).await {
+async fn send_something(some_player: &mut Conn, cmd: &str) -> bool {
+    // true when the player's connection is gone
+    !some_player.send(cmd)
+}
+
+/// Same as `send_something`, once the message is checked against what the player may see.
+async fn send_in_game(some_player: &mut Conn, the_game: &davincicode::Game, cmd: &str) -> bool {
+    !some_player.send_in(the_game, cmd)
+}
+
+/// Hangs up on the players waiting for the game the host sent away.
+async fn drop_kicked(players: &mut Vec<(String, Stream)>, room: &Room<Stream>) {
+    let mut kept = Vec::new();
+
+    for (name, mut stream) in players.drain(..) {
+        if room.access.is_kicked(&name).await {
+            println!("{} {}", name, "was sent away by the host".yellow());
+            send_raw(&mut stream, conn::KICKED_MSG).await;
             continue;
         }
+        kept.push((name, stream));
     }
+
+    *players = kept;
 }
 
-async fn send_something(some_player: &mut TcpStream, cmd: &str) -> bool {
+/// Writes to a connection that isn't a player yet.
+async fn send_raw(stream: &mut Stream, cmd: &str) -> bool {
     let mut ret = false;
 
-    if let Err(error) = some_player.write_all(cmd.as_bytes()).await {
+    if let Err(error) = stream.write_all(cmd.as_bytes()).await {
         eprintln!("Error writing response to client: {}", error);
         ret = true;
     }
-    if let Err(error) = some_player.flush().await {
+    if let Err(error) = stream.flush().await {
         eprintln!("Error flushing response to client {}", error);
         ret = true;
     }
@@ -1056,27 +1584,228 @@ async fn send_something(some_player: &mut TcpStream, cmd: &str) -> bool {
     ret
 }
 
-async fn recv_something(stream: &mut TcpStream) -> Option<String> {
-    let mut buffer = [0; 1024];
+/// Reads an answer that has to come before the deadline of the current decision.
+async fn recv_before(stream: &mut Conn, deadline: Option<Instant>) -> Result<String, i32> {
+    // None means the connection is gone, the player may come back with their token
+    let response = match deadline {
+        Some(deadline) => match timeout_at(deadline, stream.recv()).await {
+            Ok(response) => response,
+            Err(_) => return Err(PLAYER_IDLE_CODE),
+        },
+        None => stream.recv().await,
+    };
+
+    response.ok_or(PLAYER_GONE_CODE)
+}
 
-    let response_received = Mutex::new(false);
+fn keep_alive(stream: &TcpStream) {
+    // probe idle connections, so a vanished peer errors out instead of hanging the game
+    let keepalive = TcpKeepalive::new()
+        .with_time(Duration::from_secs(KEEPALIVE_SECS))
+        .with_interval(Duration::from_secs(KEEPALIVE_SECS));
 
-    loop {
-        tokio::select! {
-            bytes_read = stream.read(&mut buffer) => {
+    if let Err(error) = SockRef::from(stream).set_tcp_keepalive(&keepalive) {
+        eprintln!("Cannot enable keepalive: {}", error);
+    }
+}
 
-                let bytes_read = bytes_read.unwrap();
-                let request = String::from_utf8_lossy(&buffer[..bytes_read]);
+/// Keeps accepting connections once the game started, so that players who lost theirs
+/// can take their seat back with their session token.
+async fn accept_connections(
+    listener: TcpListener,
+    room: Arc<Room<Stream>>,
+    joining: mpsc::Sender<(String, Stream)>,
+    tls: Option<TlsAcceptor>,
+) {
+    while let Ok((stream, _)) = listener.accept().await {
+        keep_alive(&stream);
+
+        let room = Arc::clone(&room);
+        let joining = joining.clone();
+        let tls = tls.clone();
+        tokio::spawn(async move {
+            if let Some(stream) = secure(stream, tls).await {
+                handshake(stream, room, joining).await;
+            }
+        });
+    }
+}
 
-                return Some(request.to_string());
+/// Same as accept_connections, for a Unix domain socket or clients in this process.
+async fn accept_streams(
+    mut listener: Listener,
+    room: Arc<Room<Stream>>,
+    joining: mpsc::Sender<(String, Stream)>,
+) {
+    while let Some(stream) = listener.accept().await {
+        tokio::spawn(handshake(stream, Arc::clone(&room), joining.clone()));
+    }
+}
+
+/// Same as accept_connections, for browsers: the page of the web client, then its WebSocket.
+async fn accept_web(
+    listener: TcpListener,
+    room: Arc<Room<Stream>>,
+    joining: mpsc::Sender<(String, Stream)>,
+    tls: Option<TlsAcceptor>,
+) {
+    while let Ok((stream, _)) = listener.accept().await {
+        keep_alive(&stream);
+
+        let room = Arc::clone(&room);
+        let joining = joining.clone();
+        let tls = tls.clone();
+        tokio::spawn(async move {
+            let stream = match secure(stream, tls).await {
+                Some(stream) => stream,
+                None => return,
+            };
+            if let Some(stream) = web::accept(stream).await {
+                handshake(Box::new(stream), room, joining).await;
             }
-            _ = event_task(&response_received) => {
-                let flag = *response_received.lock().await;
-                if flag {
-                    break None;
+        });
+    }
+}
+
+/// The TLS handshake, when the server has a certificate.
+async fn secure(stream: TcpStream, tls: Option<TlsAcceptor>) -> Option<Stream> {
+    let secured = timeout(
+        Duration::from_secs(HANDSHAKE_TIMEOUT_SECS),
+        tls::accept(stream, tls.as_ref()),
+    )
+    .await;
+
+    match secured {
+        Ok(Ok(stream)) => Some(stream),
+        Ok(Err(error)) => {
+            eprintln!("TLS handshake failed: {}", error);
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+/// Writes a self-signed pair for LAN games, to pass to the clients with --tls-ca or
+/// --tls-fingerprint.
+fn gen_cert(dir: &std::path::Path, addr: &str) {
+    let mut names = vec![String::from("localhost"), String::from("127.0.0.1")];
+    if !names.iter().any(|name| name == addr) {
+        names.push(addr.to_string());
+    }
+
+    match tls::write_self_signed(dir, &names) {
+        Ok(fingerprint) => {
+            println!(
+                "{} {} {}",
+                "Wrote".green(),
+                dir.join(tls::CERT_FILE).display(),
+                dir.join(tls::KEY_FILE).display()
+            );
+            println!("{} {}", "Certificate fingerprint:".blue(), fingerprint);
+        }
+        Err(error) => {
+            eprintln!("Cannot write the certificate: {}", error);
+            process::exit(1);
+        }
+    }
+}
+
+fn load_tls(args: &Args) -> Option<TlsAcceptor> {
+    let (cert, key) = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => (cert, key),
+        _ => return None,
+    };
+
+    let acceptor = tls::acceptor(cert, key).unwrap_or_else(|error| {
+        eprintln!("Cannot load the TLS certificate: {}", error);
+        process::exit(1);
+    });
+    if let Ok(fingerprint) = tls::file_fingerprint(cert) {
+        println!("{} {}", "TLS certificate fingerprint:".blue(), fingerprint);
+    }
+
+    Some(acceptor)
+}
+
+/// "init", then the name of a new player, resume:<token> to take a seat back, or
+/// spectate[:<host key>] to watch the game.
+async fn handshake(
+    mut stream: Stream,
+    room: Arc<Room<Stream>>,
+    joining: mpsc::Sender<(String, Stream)>,
+) {
+    if read_handshake(&mut stream).await.as_deref() != Some("init") {
+        return;
+    }
+    if send_raw(&mut stream, "Init successfull").await {
+        return;
+    }
+
+    let request = match read_handshake(&mut stream).await {
+        Some(request) => request,
+        None => return,
+    };
+
+    if let Some(key) = spectate::parse_spectate(&request) {
+        let view = room.spectators.view_for(key);
+        let mut said = room.spectators.join(stream, view).await;
+
+        // the host can send players away, for the whole game
+        if view == View::HOST {
+            while let Some(msg) = said.recv().await {
+                if let Some(command) = access::parse_host_command(&msg) {
+                    room.host_command(command).await;
                 }
             }
         }
+        return;
+    }
+
+    if let Some(token) = session::parse_resume(&request) {
+        // nobody the host sent away takes their seat back
+        if let Some(name) = room.sessions.owner(token).await {
+            if room.access.is_kicked(&name).await {
+                let error = access::AccessError::KICKED;
+                println!("{} {}: {}", "Refused".red(), name, error);
+                send_raw(&mut stream, &format!("{}\n", error)).await;
+                return;
+            }
+        }
+        if let Err(mut stream) = room.sessions.resume(token, stream).await {
+            send_raw(&mut stream, "Unknown session token.\n").await;
+        }
+        return;
+    }
+
+    // a new player, until the game starts
+    let (name, secret) = access::parse_join(&request);
+    if name.is_empty() {
+        return;
+    }
+    if let Err(error) = room.access.admit(name, secret).await {
+        println!("{} {}: {}", "Refused".red(), name, error);
+        send_raw(&mut stream, &format!("{}\n", error)).await;
+        return;
+    }
+    let name = name.to_string();
+    if let Err(mpsc::error::SendError((_, mut stream))) = joining.send((name, stream)).await {
+        send_raw(&mut stream, "The game already started.\n").await;
+    }
+}
+
+async fn read_handshake(stream: &mut Stream) -> Option<String> {
+    let mut buffer = [0u8; 1024];
+
+    match timeout(
+        Duration::from_secs(HANDSHAKE_TIMEOUT_SECS),
+        stream.read(&mut buffer),
+    )
+    .await
+    {
+        Ok(Ok(bytes_read)) if bytes_read > 0 => {
+            Some(String::from_utf8_lossy(&buffer[..bytes_read]).to_string())
+        }
+        _ => None,
     }
 }
 
@@ -1084,52 +1813,126 @@ async fn recv_something(stream: &mut TcpStream) -> Option<String> {
 async fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
 
-    let address = format!("{}:{}", args.addr, args.port);
+    if let Some(ServerCommand::ARCHIVE { command }) = &args.command {
+        archive::run(&args.archive, command)?;
+        return Ok(());
+    }
+    if let Some(dir) = &args.gen_cert {
+        gen_cert(dir, &args.addr);
+        return Ok(());
+    }
+    let tls = load_tls(&args);
+
+    let address = match &args.unix {
+        Some(path) => path.display().to_string(),
+        None => format!("{}:{}", args.addr, args.port),
+    };
     let required_clients = args.nplayers;
 
-    let listener = TcpListener::bind(address.clone()).await.unwrap();
+    // a Unix domain socket replaces the TCP port, for games on one machine
+    let listener = match &args.unix {
+        Some(_) => None,
+        None => Some(TcpListener::bind(address.clone()).await.unwrap()),
+    };
+    let unix_listener = args
+        .unix
+        .as_deref()
+        .map(|path| Listener::bind_unix(path).unwrap());
     println!("{} {}", "Server listening on".green(), address);
 
-    let client_streams_vec = Arc::new(Mutex::new(Vec::new()));
-    let client_counter = Arc::new(Semaphore::new(0));
-    let mut client_handles = Vec::new();
-
-    let mut client_id = 0;
-
-    while let Ok((stream, _)) = listener.accept().await {
-        client_streams_vec.lock().await.push(stream);
+    // the port stays open during the game, for spectators and players who need to reconnect
+    let mut room = Room::new(
+        Sessions::new(Duration::from_secs(args.grace)),
+        room::secs_or_none(args.decision_timeout),
+        args.idle_policy,
+        args.slow_clients,
+        Spectators::new(room::secs_or_none(args.spectator_delay)),
+        Access::new(Secret::from_args(args.password, args.invite)),
+        (!args.no_archive).then_some(args.archive),
+    );
+    room.ranked = args.ranked;
+    let room = Arc::new(room);
+    println!(
+        "{} {}",
+        "Host key to watch the whole game:".blue(),
+        room.spectators.host_key()
+    );
+    match room.access.secret() {
+        Some(Secret::PASSWORD(_)) => {
+            println!("{}", "Players need the room password to join".blue())
+        }
+        Some(Secret::INVITE(code)) => println!("{} {}", "Invite code to join:".blue(), code),
+        None => {}
+    }
 
-        client_counter.add_permits(1);
+    let (joining, mut joined) = mpsc::channel(1);
+    if let Some(web_port) = args.web_port {
+        let web_address = format!("{}:{}", args.addr, web_port);
+        let web_listener = TcpListener::bind(web_address.clone()).await.unwrap();
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        println!(
+            "{} {}://{}",
+            "Browser client served at".green(),
+            scheme,
+            web_address
+        );
 
-        // spawn a task to handle new client
-        let cloned_streams_vec = Arc::clone(&client_streams_vec);
-        let handle = tokio::spawn(async move {
-            handle_client(&mut client_id, cloned_streams_vec).await;
-        });
-        client_handles.push(handle);
+        tokio::spawn(accept_web(
+            web_listener,
+            Arc::clone(&room),
+            joining.clone(),
+            tls.clone(),
+        ));
+    }
+    if let Some(listener) = listener {
+        tokio::spawn(accept_connections(
+            listener,
+            Arc::clone(&room),
+            joining.clone(),
+            tls,
+        ));
+    }
+    if let Some(listener) = unix_listener {
+        tokio::spawn(accept_streams(listener, Arc::clone(&room), joining));
+    }
 
-        client_id += 1;
-        if client_id < 2 {
-            println!("{} {}", "current client count:".blue(), client_id);
-        } else {
-            println!("{} {}", "current clients count:".blue(), client_id);
+    let mut players = Vec::new();
+    while players.len() < required_clients as usize {
+        tokio::select! {
+            player = joined.recv() => match player {
+                Some(player) => {
+                    room.access.seated(&player.0).await;
+                    players.push(player);
+                }
+                None => break,
+            },
+            _ = room.access.changed.notified() => {}
         }
 
-        if client_id >= required_clients as u32 {
-            for handle in client_handles {
-                handle.await.unwrap();
-            }
+        // the host may have sent someone away before the game starts
+        drop_kicked(&mut players, &room).await;
 
-            println!(
-                "{} {} {}",
-                "Starting the game with".green(),
-                required_clients,
-                "clients!".green()
-            );
-            init_players(Arc::clone(&client_streams_vec)).await;
-            break;
+        if players.len() < 2 {
+            println!("{} {}", "current client count:".blue(), players.len());
+        } else {
+            println!("{} {}", "current clients count:".blue(), players.len());
         }
     }
+    // late comers are told the game already started
+    drop(joined);
+
+    println!(
+        "{} {} {}",
+        "Starting the game with".green(),
+        required_clients,
+        "clients!".green()
+    );
+
+    init_players(players, Arc::clone(&room)).await;
+    room.spectators.finish().await;
+    if let Some(path) = &args.unix {
+        let _ = std::fs::remove_file(path);
+    }
 
     Ok(())
 }
@@ -1142,7 +1945,7 @@ async fn update_ui<B: Backend>(
     Ok(())
 }
 
-async fn event_task(response_received: &Mutex<bool>) -> Result<(), std::io::Error> {
+async fn event_task() -> Result<(), std::io::Error> {
     loop {
         if poll(Duration::from_millis(80))? {
             if let Key(key) = event::read()? {
@@ -1168,8 +1971,7 @@ async fn event_task(response_received: &Mutex<bool>) -> Result<(), std::io::Erro
                         process::exit(0);
                     }
                     _ => {
-                        let mut flag = response_received.lock().await;
-                        *flag = true;
+                        // only 'q' means something to the server
                         break;
                     }
                 }
@@ -1186,7 +1988,8 @@ async fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
     the_game: &mut davincicode::Game,
     selected_player_name: String,
-    player_tcp_name: &mut HashMap<String, TcpStream>,
+    player_tcp_name: &mut HashMap<String, Conn>,
+    room: &Room<Stream>,
 ) -> Result<(), std::io::Error> {
     the_game
         .logs
@@ -1196,6 +1999,10 @@ async fn run_app<B: Backend>(
     {
         let _ = update_ui(terminal, the_game).await;
 
+        // commit to the seed now, it is revealed once the game is over
+        let commit = format!("{}\n", fairness::commit_marker(the_game));
+        broadcast_msg(player_tcp_name, the_game, &commit, room).await;
+
         // send each player their own view of their deck
         for (name, client_stream) in player_tcp_name.iter_mut() {
             let mut to_send = String::new();
@@ -1210,14 +2017,14 @@ async fn run_app<B: Backend>(
             to_send.push_str(&current_player.show_hand(false, false));
             to_send.push_str("##");
 
-            if send_something(client_stream, &to_send).await {
+            if send_in_game(client_stream, the_game, &to_send).await {
                 return Ok(());
             }
         }
 
         // at each turn, show each others card
         let mut ret = String::new();
-        broadcast_msg(player_tcp_name, "\n").await;
+        broadcast_msg(player_tcp_name, the_game, "\n", room).await;
 
         ret.push('\n');
         for player in the_game.players.iter() {
@@ -1227,7 +2034,7 @@ async fn run_app<B: Backend>(
             ret.push('\n');
         }
 
-        broadcast_msg(player_tcp_name, &ret).await;
+        broadcast_msg(player_tcp_name, the_game, &ret, room).await;
 
         // process cmd of all clients
         game_process(
@@ -1235,13 +2042,17 @@ async fn run_app<B: Backend>(
             the_game,
             selected_player_name.clone(),
             player_tcp_name,
+            room,
         )
         .await;
         println!("{}", "\n\nGame over\n\n".green());
+        show_table(the_game, room).await;
+        send_analysis(player_tcp_name, the_game, room).await;
+        send_reveals(player_tcp_name, the_game).await;
 
         let mut ret = String::new();
         ret.push_str(format!("||{}|| {}", the_game.players[0].name, " won. __exiting__.").as_str());
-        broadcast_msg(player_tcp_name, &ret).await;
+        broadcast_msg(player_tcp_name, the_game, &ret, room).await;
 
         sleep(Duration::from_secs(15)).await;
 
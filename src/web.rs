@@ -0,0 +1,257 @@
+//! Lets people play from a browser.
+//!
+//! A single port serves both the page of the browser client and its WebSocket
+//! connections. Each text message carries what a TCP client would read or write, so a
+//! WebSocket client ends up as a stream like any other.
+
+use futures_util::{SinkExt, StreamExt};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{
+    duplex, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream, ReadBuf,
+};
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+/// The browser client, served at `/`.
+pub const INDEX_HTML: &str = include_str!("web/index.html");
+const MAX_REQUEST_BYTES: usize = 4096;
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+const BRIDGE_BUFFER_BYTES: usize = 64 * 1024;
+
+/// Serves the page to a browser, or returns the stream of a WebSocket client.
+pub async fn accept<S>(mut stream: S) -> Option<DuplexStream>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let request = timeout(
+        Duration::from_secs(REQUEST_TIMEOUT_SECS),
+        read_request(&mut stream),
+    )
+    .await
+    .ok()??;
+
+    if is_upgrade(&request) {
+        // tungstenite reads the request again
+        let stream = Rewind::new(request.clone().into_bytes(), stream);
+        let ws = tokio_tungstenite::accept_async(stream).await.ok()?;
+        return Some(bridge(ws));
+    }
+
+    serve_page(stream, &request).await;
+    None
+}
+
+/// Turns a WebSocket into a byte stream, one text message per write.
+pub fn bridge<S>(ws: WebSocketStream<S>) -> DuplexStream
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (ours, theirs) = duplex(BRIDGE_BUFFER_BYTES);
+    tokio::spawn(pump(ws, theirs));
+
+    ours
+}
+
+async fn pump<S>(mut ws: WebSocketStream<S>, mut pipe: DuplexStream)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut buffer = [0u8; BRIDGE_BUFFER_BYTES];
+
+    loop {
+        tokio::select! {
+            msg = ws.next() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text.to_string(),
+                    Some(Ok(Message::Binary(bytes))) => String::from_utf8_lossy(&bytes).to_string(),
+                    // pings are answered by tungstenite
+                    Some(Ok(Message::Ping(_) | Message::Pong(_) | Message::Frame(_))) => continue,
+                    Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                };
+
+                if pipe.write_all(text.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+            read = pipe.read(&mut buffer) => {
+                let bytes_read = match read {
+                    Ok(0) | Err(_) => break,
+                    Ok(bytes_read) => bytes_read,
+                };
+                let text = String::from_utf8_lossy(&buffer[..bytes_read]).to_string();
+
+                if ws.send(Message::text(text)).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    // dropping the pipe tells the server the client is gone
+    let _ = ws.close(None).await;
+}
+
+async fn read_request<S>(stream: &mut S) -> Option<String>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut request = Vec::new();
+    let mut buffer = [0u8; MAX_REQUEST_BYTES];
+
+    // the headers may come in pieces
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        if request.len() >= MAX_REQUEST_BYTES {
+            break;
+        }
+
+        let bytes_read = stream.read(&mut buffer).await.ok()?;
+        if bytes_read == 0 {
+            return None;
+        }
+        request.extend_from_slice(&buffer[..bytes_read]);
+    }
+
+    String::from_utf8(request).ok()
+}
+
+fn is_upgrade(request: &str) -> bool {
+    request.to_lowercase().contains("upgrade: websocket")
+}
+
+async fn serve_page<S>(mut stream: S, request: &str)
+where
+    S: AsyncWrite + Unpin,
+{
+    let path = request.split_whitespace().nth(1).unwrap_or("");
+    let response = match path {
+        "/" | "/index.html" => format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            INDEX_HTML.len(),
+            INDEX_HTML
+        ),
+        _ => String::from(
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        ),
+    };
+
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// A stream whose first bytes were already read, and are given back first.
+struct Rewind<S> {
+    prefix: Vec<u8>,
+    pos: usize,
+    inner: S,
+}
+
+impl<S> Rewind<S> {
+    fn new(prefix: Vec<u8>, inner: S) -> Rewind<S> {
+        Rewind {
+            prefix,
+            pos: 0,
+            inner,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Rewind<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.pos < self.prefix.len() {
+            let len = buf.remaining().min(self.prefix.len() - self.pos);
+            let start = self.pos;
+            buf.put_slice(&self.prefix[start..start + len]);
+            self.pos += len;
+
+            return Poll::Ready(Ok(()));
+        }
+
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Rewind<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+///
+///# Testing
+///
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn serve_once() -> (String, tokio::task::JoinHandle<Option<DuplexStream>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            accept(stream).await
+        });
+
+        (address, handle)
+    }
+
+    #[tokio::test]
+    async fn test_serves_the_page() {
+        let (address, handle) = serve_once().await;
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with(INDEX_HTML));
+        assert!(handle.await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_websocket_messages_go_through() {
+        let (address, handle) = serve_once().await;
+
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", address))
+            .await
+            .unwrap();
+        let mut stream = handle.await.unwrap().expect("No websocket stream");
+
+        ws.send(Message::text("init")).await.unwrap();
+        let mut buffer = [0u8; 1024];
+        let bytes_read = stream.read(&mut buffer).await.unwrap();
+        assert_eq!(&buffer[..bytes_read], b"init");
+
+        stream.write_all(b"Init successfull").await.unwrap();
+        let msg = ws.next().await.unwrap().unwrap();
+        assert_eq!(msg.into_text().unwrap().as_str(), "Init successfull");
+
+        // the server sees the browser leave
+        ws.close(None).await.unwrap();
+        assert_eq!(stream.read(&mut buffer).await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,94 @@
+//! Whole local games, checking that no player is ever shown a card they can't see.
+
+use davincicode::bot::Difficulty;
+use davincicode::leak;
+use davincicode::local::{LocalGame, Step};
+use rand::Rng;
+
+/// A valid answer to the current prompt, at random.
+fn random_answer(local: &LocalGame) -> String {
+    let mut rng = rand::rng();
+
+    match &local.step {
+        Step::DRAW => rng.random_range(0..local.game.card_avail.len()).to_string(),
+        Step::OPPONENT => rng.random_range(0..local.opponents().len()).to_string(),
+        Step::CARD(opponent) => {
+            let deck = &local
+                .opponents()
+                .into_iter()
+                .find(|player| player.name == *opponent)
+                .unwrap()
+                .deck;
+            let hidden: Vec<usize> = (0..deck.len())
+                .filter(|idx| deck[*idx].status == davincicode::CardStatus::HIDDEN)
+                .collect();
+            hidden[rng.random_range(0..hidden.len())].to_string()
+        }
+        Step::VALUE(_, _) => rng.random_range(0..local.game.set_cards / 2).to_string(),
+        _ => String::from(if rng.random_bool(0.5) { "yes" } else { "no" }),
+    }
+}
+
+/// Plays `local` to the end, checking every prompt and every public event.
+fn play_checked(mut local: LocalGame) {
+    let mut seen_events = 0;
+
+    for _ in 0..10_000 {
+        let names: Vec<String> = local
+            .game
+            .players
+            .iter()
+            .chain(local.game.lost_players.iter())
+            .map(|player| player.name.clone())
+            .collect();
+
+        for event in local.events[seen_events..].iter() {
+            for name in names.iter() {
+                assert!(
+                    leak::leaks(&local.game, name, event).is_empty(),
+                    "{} could see: {}",
+                    name,
+                    event
+                );
+            }
+        }
+        seen_events = local.events.len();
+
+        let prompt = local.prompt();
+        assert!(
+            leak::leaks(&local.game, local.current(), &prompt).is_empty(),
+            "{} could see: {}",
+            local.current(),
+            prompt
+        );
+
+        if local.step == Step::END {
+            return;
+        }
+        local.answer(&random_answer(&local)).unwrap();
+    }
+
+    panic!("The game never ended");
+}
+
+#[test]
+fn test_hot_seat_games() {
+    for _ in 0..20 {
+        let names = vec![
+            String::from("alice"),
+            String::from("bob"),
+            String::from("carol"),
+        ];
+        play_checked(LocalGame::new(&names).unwrap());
+    }
+}
+
+#[test]
+fn test_practice_games() {
+    for difficulty in [Difficulty::EASY, Difficulty::NORMAL, Difficulty::HARD] {
+        for _ in 0..10 {
+            let local = LocalGame::with_bots(&[String::from("me")], 3, difficulty).unwrap();
+            play_checked(local);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! TLS between a client and a server over loopback, with a self-signed pair.
+
+use davincicode::tls::{self, Trust};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// A fresh directory holding a self-signed pair for localhost and 127.0.0.1, removed once
+/// the test is done with it.
+struct CertDir {
+    path: PathBuf,
+    fingerprint: String,
+}
+
+impl CertDir {
+    fn new(test: &str) -> CertDir {
+        let path = std::env::temp_dir().join(format!("davincicode-tls-{}-{}", test, process::id()));
+        let names = vec![String::from("localhost"), String::from("127.0.0.1")];
+        let fingerprint = tls::write_self_signed(&path, &names).unwrap();
+
+        CertDir { path, fingerprint }
+    }
+
+    fn cert(&self) -> PathBuf {
+        self.path.join(tls::CERT_FILE)
+    }
+}
+
+impl Drop for CertDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Serves one connection that answers "init" with "Init successfull", like the servers do.
+async fn serve_once(dir: &Path) -> String {
+    let acceptor = tls::acceptor(&dir.join(tls::CERT_FILE), &dir.join(tls::KEY_FILE)).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut stream = match tls::accept(stream, Some(&acceptor)).await {
+            Ok(stream) => stream,
+            Err(_) => return,
+        };
+
+        let mut buffer = [0u8; 1024];
+        let bytes_read = stream.read(&mut buffer).await.unwrap();
+        if &buffer[..bytes_read] == b"init" {
+            stream.write_all(b"Init successfull").await.unwrap();
+            stream.flush().await.unwrap();
+        }
+    });
+
+    address
+}
+
+async fn say_init(address: &str, trust: &Trust) -> std::io::Result<String> {
+    let connector = tls::connector(trust)?;
+    let mut stream = tls::connect(address, Some(&connector)).await?;
+
+    stream.write_all(b"init").await?;
+    stream.flush().await?;
+    let mut buffer = [0u8; 1024];
+    let bytes_read = stream.read(&mut buffer).await?;
+
+    Ok(String::from_utf8_lossy(&buffer[..bytes_read]).to_string())
+}
+
+#[tokio::test]
+async fn test_trust_anchor() {
+    let dir = CertDir::new("anchor");
+    let address = serve_once(&dir.path).await;
+
+    let trust = Trust::ANCHOR(dir.cert());
+    assert_eq!(
+        say_init(&address, &trust).await.unwrap(),
+        "Init successfull"
+    );
+}
+
+#[tokio::test]
+async fn test_pinned_fingerprint() {
+    let dir = CertDir::new("pinned");
+    let address = serve_once(&dir.path).await;
+
+    let trust = Trust::PINNED(dir.fingerprint.to_uppercase());
+    assert_eq!(
+        say_init(&address, &trust).await.unwrap(),
+        "Init successfull"
+    );
+}
+
+#[tokio::test]
+async fn test_wrong_fingerprint_is_rejected() {
+    let dir = CertDir::new("wrong-pin");
+    let other = CertDir::new("other-pin");
+    let address = serve_once(&dir.path).await;
+
+    let trust = Trust::PINNED(other.fingerprint.clone());
+    assert!(say_init(&address, &trust).await.is_err());
+}
+
+#[tokio::test]
+async fn test_unknown_anchor_is_rejected() {
+    let dir = CertDir::new("wrong-anchor");
+    let other = CertDir::new("other-anchor");
+    let address = serve_once(&dir.path).await;
+
+    let trust = Trust::ANCHOR(other.cert());
+    assert!(say_init(&address, &trust).await.is_err());
+}